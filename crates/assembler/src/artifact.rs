@@ -0,0 +1,110 @@
+use std::fmt::Write;
+
+use crate::{Segment, Warning};
+
+/// Output of a successful assembly.
+pub struct Assembly {
+    /// Placed bytes in order of address, without the gaps between them.
+    pub segments: Vec<Segment>,
+    pub warnings: Vec<Warning>,
+    /// Text listing of the program, when requested with
+    /// [`Assembler::with_listing`](crate::Assembler::with_listing).
+    pub listing: Option<String>,
+    /// Every label in order of value.
+    pub symbols: Vec<Symbol>,
+    /// Lines of the source that placed bytes, in order of address.
+    pub lines: Vec<SourceLine>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A label like `main`, holding its address.
+    Global,
+    /// A label like `main.loop`, holding its address.
+    Local,
+    /// An entry like `main~.loop`, holding the distance from `main` to
+    /// `main.loop`.
+    Relative,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u32,
+    pub kind: SymbolKind,
+}
+
+/// Bytes placed for a line of the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    pub address: u32,
+    pub length: u32,
+    /// Included file the line is in, or `None` for the main source.
+    pub file: Option<String>,
+    pub line: usize,
+}
+
+impl Assembly {
+    /// Image of memory from address 0 to the last placed byte, with zeros
+    /// in the gaps.
+    pub fn bytes(&self) -> Vec<u8> {
+        let end = self.segments.iter().map(Segment::end).max().unwrap_or(0);
+        let mut bytes = vec![0; end as usize];
+        for segment in &self.segments {
+            let start = segment.address as usize;
+            bytes[start..start + segment.bytes.len()].copy_from_slice(&segment.bytes);
+        }
+        bytes
+    }
+
+    /// Name `address` after the closest label at or before it, like
+    /// `vblank.after1` or `vblank.after1+3`.
+    pub fn symbolize(&self, address: u32) -> Option<String> {
+        let symbol = self
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.kind != SymbolKind::Relative && symbol.value <= address)
+            .max_by_key(|symbol| (symbol.value, symbol.kind == SymbolKind::Local))?;
+        Some(match address - symbol.value {
+            0 => symbol.name.clone(),
+            offset => format!("{}+{}", symbol.name, offset),
+        })
+    }
+
+    /// Line of the source that placed the byte at `address`.
+    pub fn source_line(&self, address: u32) -> Option<&SourceLine> {
+        self.lines
+            .iter()
+            .find(|line| (line.address..line.address + line.length).contains(&address))
+    }
+
+    /// Symbol table as a `.sym` file, with one `value name` pair per line.
+    pub fn sym(&self) -> String {
+        let mut sym = String::new();
+        for Symbol { name, value, .. } in &self.symbols {
+            writeln!(sym, "{:06X} {}", value, name).unwrap();
+        }
+        sym
+    }
+
+    /// Source map as a `.map` file, with the address and length of the
+    /// bytes of each line followed by the line, prefixed with its file when
+    /// it lies in an included file.
+    pub fn map(&self) -> String {
+        let mut map = String::new();
+        for SourceLine {
+            address,
+            length,
+            file,
+            line,
+        } in &self.lines
+        {
+            write!(map, "{:06X} {} ", address, length).unwrap();
+            if let Some(file) = file {
+                write!(map, "{}:", file).unwrap();
+            }
+            writeln!(map, "{}", line).unwrap();
+        }
+        map
+    }
+}
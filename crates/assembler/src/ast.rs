@@ -0,0 +1,700 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use pest::{iterators::Pair, Parser};
+
+pub use crate::expression::{BinaryOperator, UnaryOperator};
+use crate::{
+    binary_operator, operators,
+    preprocessor::{first_word, split_arguments},
+    syntax::{self, strip_comment},
+    unary_operator, AssembleError, AssembleErrors, ErrorKind, KittyAssemblyParser, Location, Rule,
+    Span,
+};
+
+/// Directives the preprocessor handles, which only start a line.
+const PREPROCESSOR_DIRECTIVES: &[&str] = &[
+    ".macro", ".endm", ".include", ".if", ".ifdef", ".ifndef", ".elif", ".else", ".endif", ".rept",
+    ".endr",
+];
+
+/// Width that a mnemonic or directive is padded to before its operands.
+const MNEMONIC_WIDTH: usize = 7;
+
+/// A source file as written, before files are included and macros are
+/// expanded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    /// Statements and comments in source order.
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatementKind {
+    /// A label definition like `main:`, `.loop:`, `1:` or `:`, named
+    /// without its colon.
+    Label(String),
+    /// A machine or pseudo-instruction like `addi r1, r1, 1` or `nop`.
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+    /// A directive with operands separated by commas, like `.equ SIZE, 4`,
+    /// `data 1, 2` or `.include "file"`, named as written.
+    Directive {
+        name: String,
+        operands: Vec<Operand>,
+    },
+    /// `.alias name = register`.
+    Alias { name: String, register: String },
+    /// A name inside `.enum`, with the value it is given, if any.
+    Member {
+        name: String,
+        value: Option<Expression>,
+    },
+    /// `.macro name parameters`, whose body lasts up to `.endm`.
+    Macro {
+        name: String,
+        parameters: Vec<String>,
+    },
+    /// An invocation of a macro defined before it, with its arguments as
+    /// written.
+    Invocation {
+        name: String,
+        arguments: Vec<String>,
+    },
+    /// A line of a macro body that only parses once the arguments are
+    /// substituted, as written.
+    Verbatim(String),
+    /// A `;` comment, without the `;`.
+    Comment(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Register(String, Span),
+    Expression(Expression),
+    /// A string literal as written between its quotes, escapes included.
+    String(String, Span),
+    /// A name that a directive defines or declares, like the constant of
+    /// `.equ` or the labels of `.global`.
+    Name(String, Span),
+}
+
+/// An expression, without the parentheses that only group it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expression {
+    /// A number literal as written, like `0x1F`, `1_000` or `1.5q12`.
+    Number(String, Span),
+    /// A character literal as written between its quotes, like `a` or `\n`.
+    Character(String, Span),
+    /// A reference to a label or constant as written, like `main.loop`,
+    /// `.loop`, `main~.loop`, `:-` or `1b`.
+    Label(String, Span),
+    /// The distance to a label, like `~.loop`, without the `~`.
+    Distance(String, Span),
+    /// `sizeof(Name)`, naming the `.struct`.
+    SizeOf(String, Span),
+    Unary(UnaryOperator, Box<Expression>, Span),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>, Span),
+}
+
+impl Operand {
+    pub fn span(&self) -> Span {
+        match self {
+            Operand::Register(_, span) | Operand::String(_, span) | Operand::Name(_, span) => *span,
+            Operand::Expression(expression) => expression.span(),
+        }
+    }
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        use Expression::*;
+        match self {
+            Number(_, span)
+            | Character(_, span)
+            | Label(_, span)
+            | Distance(_, span)
+            | SizeOf(_, span)
+            | Unary(_, _, span)
+            | Binary(_, _, _, span) => *span,
+        }
+    }
+}
+
+/// Parse `source` into its statements and comments without assembling it.
+///
+/// Lines that the preprocessor handles become statements of their own, and
+/// so do invocations of the macros defined before them. Included files are
+/// not read.
+pub fn parse(source: &str) -> Result<Program, AssembleErrors> {
+    let lines: Vec<_> = source.split('\n').collect();
+    let mut statements = vec![];
+    let mut errors = vec![];
+    let mut macros = HashSet::new();
+    // The lines left to the grammar, with the others blanked so statements
+    // keep their lines.
+    let mut code = vec![];
+    let mut bodies = HashSet::new();
+    let mut in_macro = false;
+    for (index, line) in lines.iter().enumerate() {
+        let number = index + 1;
+        statements.extend(comment(line, number));
+        let (column, word) = first_word(line);
+        let directive = word.to_lowercase();
+        let start = Location {
+            line: number,
+            column,
+        };
+        if PREPROCESSOR_DIRECTIVES.contains(&directive.as_str()) {
+            in_macro = match directive.as_str() {
+                ".macro" => true,
+                ".endm" => false,
+                _ => in_macro,
+            };
+            match preprocessor_statement(line, start, &directive) {
+                Ok(statement) => {
+                    if let StatementKind::Macro { name, .. } = &statement.kind {
+                        macros.insert(name.clone());
+                    }
+                    statements.push(statement);
+                }
+                Err(error) => errors.push(error),
+            }
+            code.push("");
+        } else if macros.contains(word) {
+            statements.push(invocation(line, start, word));
+            code.push("");
+        } else {
+            if in_macro {
+                bodies.insert(number);
+            }
+            code.push(line);
+        }
+    }
+
+    let code = code.join("\n");
+    let program = match KittyAssemblyParser::parse(Rule::Program, &code) {
+        // The parse was successful; unwrap cannot fail here.
+        Ok(mut program) => program.next().unwrap(),
+        Err(error) => {
+            errors.push(syntax::from_pest(error, Location { line: 1, column: 1 }));
+            return Err(sorted(errors));
+        }
+    };
+    let builder = Builder {
+        start: Location { line: 1, column: 1 },
+    };
+    let mut parsed = vec![];
+    let mut verbatim = BTreeSet::new();
+    let mut previous: Option<Pair<Rule>> = None;
+    for pair in program.into_inner() {
+        match pair.as_rule() {
+            Rule::EOI => break,
+            Rule::Invalid if bodies.contains(&pair.line_col().0) => {
+                verbatim.insert(pair.line_col().0);
+            }
+            Rule::Invalid => errors.push(invalid(&pair, previous)),
+            _ => parsed.push(builder.statement(pair.clone())),
+        }
+        previous = Some(pair);
+    }
+    if !errors.is_empty() {
+        return Err(sorted(errors));
+    }
+
+    // A macro body line is kept whole when any of it does not parse.
+    parsed.retain(|statement: &Statement| !verbatim.contains(&statement.span.start.line));
+    for number in verbatim {
+        let code = strip_comment(lines[number - 1]).trim_end();
+        let text = code.trim_start();
+        let column = code.len() - text.len() + 1;
+        let span = Span {
+            start: Location {
+                line: number,
+                column,
+            },
+            end: Location {
+                line: number,
+                column: code.len() + 1,
+            },
+        };
+        let kind = StatementKind::Verbatim(text.to_string());
+        parsed.push(Statement { kind, span });
+    }
+    statements.extend(parsed);
+    statements.sort_by_key(|statement| statement.span.start);
+    Ok(Program { statements })
+}
+
+fn sorted(mut errors: Vec<AssembleError>) -> AssembleErrors {
+    errors.sort_by_key(|error| error.span.start);
+    AssembleErrors(errors)
+}
+
+/// The comment on `line`, the `number`th line of the source.
+fn comment(line: &str, number: usize) -> Option<Statement> {
+    let code = strip_comment(line);
+    let text = line[code.len()..].strip_prefix(';')?.trim_end();
+    let span = Span {
+        start: Location {
+            line: number,
+            column: code.len() + 1,
+        },
+        end: Location {
+            line: number,
+            column: code.len() + 2 + text.len(),
+        },
+    };
+    let kind = StatementKind::Comment(text.to_string());
+    Some(Statement { kind, span })
+}
+
+/// Describe the text of an `Invalid` pair, along with a statement before it
+/// on its line that only parsed up to it, like the assembler does.
+fn invalid(pair: &Pair<Rule>, previous: Option<Pair<Rule>>) -> AssembleError {
+    let start = match previous {
+        Some(previous)
+            if previous.as_rule() != Rule::LabelDefinition
+                && previous.line_col().0 == pair.line_col().0 =>
+        {
+            previous
+        }
+        _ => pair.clone(),
+    };
+    let (line, column) = start.line_col();
+    let text = &pair.get_input()[start.as_span().start()..pair.as_span().end()];
+    syntax::diagnose(text, Location { line, column }, &HashMap::new())
+}
+
+/// Parse the line of a preprocessor `directive`, which starts at `start`.
+fn preprocessor_statement(
+    line: &str,
+    start: Location,
+    directive: &str,
+) -> Result<Statement, AssembleError> {
+    let code = strip_comment(line).trim_end();
+    let text = &code[start.column - 1..];
+    let span = Span {
+        start,
+        end: Location {
+            line: start.line,
+            column: code.len() + 1,
+        },
+    };
+    let error = |message: &str| AssembleError::new(ErrorKind::Syntax(message.to_string()), span);
+    let (rule, message) = match directive {
+        ".include" => (Rule::Include, "expected file name in quotes"),
+        ".if" | ".elif" => (Rule::Condition, "expected condition"),
+        ".ifdef" | ".ifndef" => (Rule::Defined, "expected constant name"),
+        ".rept" => (Rule::Repeat, "expected repeat count"),
+        ".macro" => {
+            let rest = text[directive.len()..].trim();
+            let (name, parameters) = match rest.split_once(char::is_whitespace) {
+                Some((name, parameters)) => (name, split_arguments(parameters)),
+                None => (rest, vec![]),
+            };
+            if name.is_empty() {
+                return Err(error("expected macro name"));
+            }
+            let kind = StatementKind::Macro {
+                name: name.to_string(),
+                parameters,
+            };
+            return Ok(Statement { kind, span });
+        }
+        _ if text.len() > directive.len() => {
+            return Err(error(&format!("`{}` takes no operands", directive)));
+        }
+        _ => {
+            let kind = StatementKind::Directive {
+                name: text.to_string(),
+                operands: vec![],
+            };
+            return Ok(Statement { kind, span });
+        }
+    };
+    match KittyAssemblyParser::parse(rule, text) {
+        Ok(mut pairs) if pairs.as_str().len() == text.len() => {
+            let builder = Builder { start };
+            Ok(builder.statement(pairs.next().unwrap()))
+        }
+        _ => Err(error(message)),
+    }
+}
+
+/// The invocation of the macro `name` that starts at `start` of `line`.
+fn invocation(line: &str, start: Location, name: &str) -> Statement {
+    let code = strip_comment(line).trim_end();
+    let arguments = split_arguments(&code[start.column - 1 + name.len()..]);
+    let span = Span {
+        start,
+        end: Location {
+            line: start.line,
+            column: code.len() + 1,
+        },
+    };
+    let kind = StatementKind::Invocation {
+        name: name.to_string(),
+        arguments,
+    };
+    Statement { kind, span }
+}
+
+/// Builds statements from the pairs of text that starts at `start` in the
+/// source.
+struct Builder {
+    start: Location,
+}
+
+impl Builder {
+    /// Span of `pair` in the source, without the whitespace and comments
+    /// that rules ending in a repetition take in.
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        let span = pair.as_span();
+        let end = span.start() + trim_end(pair.as_str()).len();
+        // The end lies within the span, so it is a valid position.
+        let span = Span::from(pest::Span::new(span.get_input(), span.start(), end).unwrap());
+        let offset = |location: Location| match location.line {
+            1 => Location {
+                line: self.start.line,
+                column: self.start.column + location.column - 1,
+            },
+            line => Location {
+                line: self.start.line + line - 1,
+                column: location.column,
+            },
+        };
+        Span {
+            start: offset(span.start),
+            end: offset(span.end),
+        }
+    }
+
+    fn statement(&self, pair: Pair<Rule>) -> Statement {
+        let span = self.span(&pair);
+        let rule = pair.as_rule();
+        let mut pairs = pair.into_inner();
+        let kind = match rule {
+            Rule::LabelDefinition => {
+                let label = pairs.next().unwrap();
+                StatementKind::Label(match label.as_rule() {
+                    Rule::AnonymousLabel => String::new(),
+                    _ => label.as_str().to_string(),
+                })
+            }
+            Rule::Instruction => StatementKind::Instruction {
+                mnemonic: pairs.next().unwrap().as_str().to_string(),
+                operands: pairs.map(|pair| self.operand(pair)).collect(),
+            },
+            Rule::Alias if pairs.peek().unwrap().as_rule() == Rule::AliasDirective => {
+                StatementKind::Alias {
+                    name: pairs.nth(1).unwrap().as_str().to_string(),
+                    register: pairs.next().unwrap().as_str().to_string(),
+                }
+            }
+            Rule::Member => StatementKind::Member {
+                name: pairs.next().unwrap().as_str().to_string(),
+                value: pairs.next().map(|pair| self.expression(pair)),
+            },
+            _ => StatementKind::Directive {
+                name: pairs.next().unwrap().as_str().to_string(),
+                operands: pairs
+                    .flat_map(|pair| match pair.as_rule() {
+                        Rule::DataValues => pair.into_inner().collect(),
+                        _ => vec![pair],
+                    })
+                    .map(|pair| self.operand(pair))
+                    .collect(),
+            },
+        };
+        Statement { kind, span }
+    }
+
+    fn operand(&self, pair: Pair<Rule>) -> Operand {
+        let span = self.span(&pair);
+        match pair.as_rule() {
+            Rule::Register => Operand::Register(pair.as_str().to_string(), span),
+            Rule::GlobalLabel => Operand::Name(pair.as_str().to_string(), span),
+            Rule::String => {
+                let characters = pair.into_inner().next().unwrap();
+                Operand::String(characters.as_str().to_string(), span)
+            }
+            Rule::Expression => Operand::Expression(self.expression(pair)),
+            Rule::RelativeLabelReference | Rule::AbsoluteLabelReference => {
+                Operand::Expression(self.reference(pair))
+            }
+            _ => unreachable!("Operand: {:?}", pair.as_rule()),
+        }
+    }
+
+    fn expression(&self, pair: Pair<Rule>) -> Expression {
+        operators()
+            .map_primary(|primary| self.primary(primary))
+            .map_prefix(|operator, operand| {
+                let span = Span {
+                    start: self.span(&operator).start,
+                    end: operand.span().end,
+                };
+                let operator = unary_operator(operator.as_rule());
+                Expression::Unary(operator, Box::new(operand), span)
+            })
+            .map_infix(|left, operator, right| {
+                let span = Span {
+                    start: left.span().start,
+                    end: right.span().end,
+                };
+                let operator = binary_operator(operator.as_rule());
+                Expression::Binary(operator, Box::new(left), Box::new(right), span)
+            })
+            .parse(pair.into_inner())
+    }
+
+    fn primary(&self, pair: Pair<Rule>) -> Expression {
+        let span = self.span(&pair);
+        match pair.as_rule() {
+            Rule::Expression => self.expression(pair),
+            Rule::SizeOf => {
+                let name = pair.into_inner().next().unwrap().as_str();
+                Expression::SizeOf(name.to_string(), span)
+            }
+            Rule::Function => {
+                let mut pairs = pair.into_inner();
+                let operator = match pairs.next().unwrap().as_str().to_lowercase().as_str() {
+                    "hi" => UnaryOperator::High,
+                    "lo" => UnaryOperator::Low,
+                    function => unreachable!("Function: {}", function),
+                };
+                let operand = self.expression(pairs.next().unwrap());
+                Expression::Unary(operator, Box::new(operand), span)
+            }
+            Rule::CharacterLiteral => {
+                let character = pair.into_inner().next().unwrap();
+                Expression::Character(character.as_str().to_string(), span)
+            }
+            Rule::Number => Expression::Number(pair.as_str().to_string(), span),
+            Rule::LabelReference => self.reference(pair.into_inner().next().unwrap()),
+            _ => unreachable!("Primary: {} ({:?})", pair.as_str(), pair.as_rule()),
+        }
+    }
+
+    fn reference(&self, pair: Pair<Rule>) -> Expression {
+        let span = self.span(&pair);
+        let text = pair.as_str();
+        match pair.as_rule() {
+            Rule::RelativeLabelReference => Expression::Distance(text[1..].to_string(), span),
+            _ => Expression::Label(text.to_string(), span),
+        }
+    }
+}
+
+/// `text` without the whitespace and comments after its last token.
+fn trim_end(text: &str) -> &str {
+    let mut text = text.trim_end();
+    loop {
+        let start = text.rfind('\n').map_or(0, |index| index + 1);
+        let code = strip_comment(&text[start..]);
+        if start + code.len() == text.len() {
+            return text;
+        }
+        text = text[..start + code.len()].trim_end();
+    }
+}
+
+/// Canonical source: one statement per line, followed by the comment that
+/// ends its line, with at most one blank line between statements. Global
+/// labels and comments that start their line in the first column are not
+/// indented, everything else by four spaces. The trailing comments of
+/// lines between blank lines start in the same column.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Each line with its trailing comment, or `None` for a blank line.
+        let mut lines: Vec<Option<(String, Option<&str>)>> = vec![];
+        let mut previous: Option<&Statement> = None;
+        for statement in &self.statements {
+            let line = statement.span.start.line;
+            if let Some(previous) = previous {
+                if let (StatementKind::Comment(text), Some(Some((_, comment @ None)))) =
+                    (&statement.kind, lines.last_mut())
+                {
+                    if line == previous.span.end.line {
+                        *comment = Some(text);
+                        continue;
+                    }
+                }
+                if line > previous.span.end.line + 1 {
+                    lines.push(None);
+                }
+            }
+            let indented = match &statement.kind {
+                StatementKind::Label(name) => {
+                    name.is_empty()
+                        || name.starts_with(|character: char| {
+                            character == '.' || character.is_ascii_digit()
+                        })
+                }
+                StatementKind::Comment(_) => statement.span.start.column > 1,
+                _ => true,
+            };
+            let indentation = if indented { "    " } else { "" };
+            lines.push(Some((format!("{}{}", indentation, statement), None)));
+            previous = Some(statement);
+        }
+
+        let mut columns = vec![0; lines.len()];
+        let mut block = 0;
+        for index in 0..=lines.len() {
+            if let Some(Some(_)) = lines.get(index) {
+                continue;
+            }
+            let width = lines[block..index]
+                .iter()
+                .flatten()
+                .filter(|(_, comment)| comment.is_some())
+                .map(|(code, _)| code.chars().count())
+                .max();
+            columns[block..index].fill(width.unwrap_or(0));
+            block = index + 1;
+        }
+        for (line, width) in lines.iter().zip(columns) {
+            match line {
+                None => writeln!(f)?,
+                Some((code, None)) => writeln!(f, "{}", code)?,
+                Some((code, Some(comment))) => writeln!(f, "{:<width$} ;{}", code, comment)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl fmt::Display for StatementKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use StatementKind::*;
+        match self {
+            Label(name) => write!(f, "{}:", name),
+            Instruction { mnemonic, operands } => write_operation(f, mnemonic, operands),
+            Directive { name, operands } => write_operation(f, name, operands),
+            Alias { name, register } => write!(
+                f,
+                "{:<width$} {} = {}",
+                ".alias",
+                name,
+                register,
+                width = MNEMONIC_WIDTH
+            ),
+            Member { name, value: None } => write!(f, "{}", name),
+            Member {
+                name,
+                value: Some(value),
+            } => write!(f, "{} = {}", name, value),
+            Macro { name, parameters } => {
+                write!(f, "{:<width$} {}", ".macro", name, width = MNEMONIC_WIDTH)?;
+                match parameters.is_empty() {
+                    true => Ok(()),
+                    false => write!(f, " {}", parameters.join(", ")),
+                }
+            }
+            Invocation { name, arguments } => write_operation(f, name, arguments),
+            Verbatim(text) => write!(f, "{}", text),
+            Comment(text) => write!(f, ";{}", text),
+        }
+    }
+}
+
+/// Write `name` padded to line up the operands after it, unless it has
+/// none.
+fn write_operation(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    operands: &[impl fmt::Display],
+) -> fmt::Result {
+    if operands.is_empty() {
+        return write!(f, "{}", name);
+    }
+    write!(f, "{:<width$} ", name, width = MNEMONIC_WIDTH)?;
+    for (index, operand) in operands.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", operand)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(text, _) | Operand::Name(text, _) => write!(f, "{}", text),
+            Operand::Expression(expression) => write!(f, "{}", expression),
+            Operand::String(text, _) => write!(f, "\"{}\"", text),
+        }
+    }
+}
+
+/// The expression with the fewest parentheses that keep its meaning.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Expression::*;
+        match self {
+            Number(text, _) | Label(text, _) => write!(f, "{}", text),
+            Character(text, _) => write!(f, "'{}'", text),
+            Distance(name, _) => write!(f, "~{}", name),
+            SizeOf(name, _) => write!(f, "sizeof({})", name),
+            Unary(UnaryOperator::High, operand, _) => write!(f, "hi({})", operand),
+            Unary(UnaryOperator::Low, operand, _) => write!(f, "lo({})", operand),
+            Unary(operator, operand, _) => {
+                let symbol = match operator {
+                    UnaryOperator::BitNot => "~",
+                    _ => "-",
+                };
+                // `~label` would be the distance to the label.
+                let grouped = match **operand {
+                    Binary(..) => true,
+                    Label(..) => *operator == UnaryOperator::BitNot,
+                    _ => false,
+                };
+                match grouped {
+                    true => write!(f, "{}({})", symbol, operand),
+                    false => write!(f, "{}{}", symbol, operand),
+                }
+            }
+            Binary(operator, left, right, _) => {
+                let precedence = operator.precedence();
+                // Operators of the same precedence group to the left.
+                write_grouped(f, left, |inner| inner < precedence)?;
+                write!(f, " {} ", operator)?;
+                write_grouped(f, right, |inner| inner <= precedence)
+            }
+        }
+    }
+}
+
+/// Write `expression`, in parentheses when it applies a binary operator
+/// whose precedence `grouped` holds for.
+fn write_grouped(
+    f: &mut fmt::Formatter<'_>,
+    expression: &Expression,
+    grouped: impl Fn(u8) -> bool,
+) -> fmt::Result {
+    match expression {
+        Expression::Binary(operator, ..) if grouped(operator.precedence()) => {
+            write!(f, "({})", expression)
+        }
+        _ => write!(f, "{}", expression),
+    }
+}
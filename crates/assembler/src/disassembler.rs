@@ -0,0 +1,125 @@
+use std::fmt::Write;
+
+use common::{
+    Format, Instruction, Op, REGISTER_GLOBAL, REGISTER_INTERRUPT, REGISTER_LINK,
+    REGISTER_PROGRAM_COUNTER, REGISTER_TEMPORARY,
+};
+
+use crate::{Symbol, SymbolKind};
+
+/// Turns machine code back into kittyasm that assembles to the same bytes.
+#[derive(Default)]
+pub struct Disassembler<'a> {
+    symbols: &'a [Symbol],
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define the labels of `symbols` where they point and name the targets
+    /// of relative branches after them.
+    pub fn with_symbols(mut self, symbols: &'a [Symbol]) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    /// Disassemble `bytes` placed at `address`, with one instruction per
+    /// line followed by its address. Bytes left over after the last whole
+    /// instruction become `data`.
+    pub fn disassemble(&self, address: u32, bytes: &[u8]) -> String {
+        let mut output = String::new();
+        if address != 0 {
+            writeln!(output, ".org {:#08X}", address).unwrap();
+        }
+        let mut address = address;
+        for word in bytes.chunks(3) {
+            self.write_labels(&mut output, address, word.len() as u32);
+            let text = match word {
+                &[a, b, c] => instruction(u32::from_be_bytes([0, a, b, c])),
+                _ => {
+                    let values: Vec<_> = word.iter().map(|byte| byte.to_string()).collect();
+                    format!("data {}", values.join(", "))
+                }
+            };
+            write!(output, "    {:<24}; {:06X}", text, address).unwrap();
+            if let Some(target) = self.branch_target(address, word) {
+                write!(output, " -> {}", target).unwrap();
+            }
+            writeln!(output).unwrap();
+            address += word.len() as u32;
+        }
+        output
+    }
+
+    /// Define the labels that point into the `length` bytes at `address`.
+    /// Labels that point inside an instruction can only be noted.
+    fn write_labels(&self, output: &mut String, address: u32, length: u32) {
+        for symbol in self.symbols {
+            if symbol.kind == SymbolKind::Relative
+                || !(address..address + length).contains(&symbol.value)
+            {
+                continue;
+            }
+            let name = match symbol.name.find('.') {
+                Some(index) => &symbol.name[index..],
+                None => &symbol.name,
+            };
+            match symbol.value - address {
+                0 => writeln!(output, "{}:", name).unwrap(),
+                offset => writeln!(output, "; {} is at {:06X}+{}", name, address, offset).unwrap(),
+            }
+        }
+    }
+
+    /// Label that the instruction `word` at `address` branches to, when it
+    /// is an `addi` or `subi` of `pc` to itself.
+    fn branch_target(&self, address: u32, word: &[u8]) -> Option<&str> {
+        let &[a, b, c] = word else {
+            return None;
+        };
+        let Instruction { op, r, s, u, .. } = Instruction::decode(u32::from_be_bytes([0, a, b, c]));
+        let pc = REGISTER_PROGRAM_COUNTER;
+        if r != pc || s != pc {
+            return None;
+        }
+        let target = match op {
+            Op::Addi => address + 3 + u,
+            Op::Subi => (address + 3).wrapping_sub(u),
+            _ => return None,
+        };
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.kind != SymbolKind::Relative && symbol.value == target)
+            .map(|symbol| symbol.name.as_str())
+    }
+}
+
+/// Kittyasm for the instruction `word`.
+fn instruction(word: u32) -> String {
+    let instruction = Instruction::decode(word);
+    let mnemonic = instruction.mnemonic();
+    let r = register(instruction.r);
+    let s = register(instruction.s);
+    match instruction.op {
+        Op::Let => format!("{} {}, {:#05X}", mnemonic, r, instruction.u),
+        Op::Lethi => format!("{} {}, {:#08X}", mnemonic, r, instruction.u << 12),
+        op => match op.format() {
+            Format::R => format!("{} {}, {}, {}", mnemonic, r, s, register(instruction.u)),
+            _ => format!("{} {}, {}, {}", mnemonic, r, s, instruction.immediate()),
+        },
+    }
+}
+
+/// Name of a register, using the aliases of the special registers.
+fn register(register: u32) -> String {
+    match register {
+        REGISTER_GLOBAL => "sp".to_string(),
+        REGISTER_LINK => "lr".to_string(),
+        REGISTER_TEMPORARY => "at".to_string(),
+        REGISTER_INTERRUPT => "ir".to_string(),
+        REGISTER_PROGRAM_COUNTER => "pc".to_string(),
+        register => format!("r{:x}", register),
+    }
+}
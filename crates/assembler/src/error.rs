@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::Lint;
+
+/// Position in the source, with lines and columns starting at 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Range of source text a diagnostic refers to, end exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span) -> Self {
+        let (line, column) = span.start_pos().line_col();
+        let start = Location { line, column };
+        let (line, column) = span.end_pos().line_col();
+        let end = Location { line, column };
+        Self { start, end }
+    }
+}
+
+/// Where a diagnostic starts, prefixed with its file when it lies in an
+/// included file.
+struct Position<'a>(&'a Option<String>, Location);
+
+impl fmt::Display for Position<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Position(file, Location { line, column }) = self;
+        if let Some(file) = file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", line, column)
+    }
+}
+
+/// Invocation of a macro that a diagnostic lies in the body of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expansion {
+    pub name: String,
+    pub span: Span,
+    /// Included file the invocation lies in, or `None` for the main source.
+    pub file: Option<String>,
+}
+
+impl fmt::Display for Expansion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = Position(&self.file, self.span.start);
+        write!(f, "{}: in expansion of macro `{}`", position, self.name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The source does not match the kittyasm grammar.
+    Syntax(String),
+    /// A number literal has no digits.
+    InvalidNumber(String),
+    /// A number literal does not fit in 32 bits.
+    NumberTooLarge(String),
+    /// A referenced label is never defined.
+    UnknownLabel(String),
+    /// A label reference points past the end of the output.
+    ReferenceOutOfBounds(String),
+    /// A local label is defined before any global label.
+    MissingScope(String),
+    /// A value does not fit in the field it is encoded into.
+    OutOfRange { value: i64, min: i64, max: i64 },
+    /// An `addi` or `subi` moves away from the `~label` it refers to.
+    WrongDirection { label: String, distance: i64 },
+    /// An expression divides by zero.
+    DivisionByZero,
+    /// A label or `.equ` constant is given a second value.
+    DuplicateSymbol(String),
+    /// A constant is defined in terms of itself.
+    CircularConstant(String),
+    /// A `.macro` has no matching `.endm`.
+    UnclosedMacro(String),
+    /// A macro is invoked with the wrong number of arguments.
+    MacroArguments {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Macros invoke each other too deeply, most likely without end.
+    MacroRecursion(String),
+    /// An included file cannot be read.
+    UnreadableFile { name: String, reason: String },
+    /// A file includes itself, directly or through other files.
+    IncludeCycle(String),
+    /// `.org` places bytes at addresses that already have bytes, from
+    /// `start` up to `end`.
+    Overlap { start: u32, end: u32 },
+    /// A value depends on the address of a label, which a relocatable
+    /// object only knows once it is linked.
+    NotRelocatable(String),
+    /// Bytes are placed past the last address, 0xFFFFFF.
+    OutsideAddressSpace,
+    /// `.org` is used in a relocatable object, which the linker places.
+    FixedAddress,
+    /// An `.extern` symbol is not exported by any linked object.
+    UndefinedExtern(String),
+    /// An object file does not follow the object format.
+    InvalidObject(String),
+    /// A `.if` or `.rept` refers to a label, or to a name that is not yet
+    /// defined, while only constants defined before it have a value.
+    NotConstant(String),
+    /// A `\u{...}` escape or `.charmap` names a code point that is not a
+    /// character.
+    InvalidCharacter(String),
+    /// A `.if` or `.rept` has no matching `.endif` or `.endr` in its file
+    /// or macro, or a `.struct` or `.enum` no `.endstruct` or `.endenum`.
+    UnclosedBlock(String),
+    /// `.unalias` names a register alias that is not defined.
+    UnknownAlias(String),
+    /// A fixed-point literal has no `q` suffix and follows no `.fixed`.
+    MissingFixedFormat(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ErrorKind::*;
+        match self {
+            Syntax(message) => write!(f, "{}", message),
+            InvalidNumber(number) => write!(f, "invalid number `{}`", number),
+            NumberTooLarge(number) => write!(f, "number `{}` is too large", number),
+            UnknownLabel(label) => write!(f, "unknown label `{}`", label),
+            ReferenceOutOfBounds(label) => {
+                write!(f, "reference to `{}` lies outside the output", label)
+            }
+            MissingScope(label) => {
+                write!(
+                    f,
+                    "local label `{}` is not preceded by a global label",
+                    label
+                )
+            }
+            OutOfRange { value, min, max } => {
+                write!(f, "value {} is out of range {}..={}", value, min, max)
+            }
+            WrongDirection { label, distance } if *distance < 0 => write!(
+                f,
+                "`{}` is {} bytes backward; use `subi` or `branch`",
+                label, -distance
+            ),
+            WrongDirection { label, distance } => write!(
+                f,
+                "`{}` is {} bytes forward; use `addi` or `branch`",
+                label, distance
+            ),
+            DivisionByZero => write!(f, "division by zero"),
+            DuplicateSymbol(name) => write!(f, "`{}` is already defined", name),
+            CircularConstant(name) => write!(f, "constant `{}` depends on itself", name),
+            UnclosedMacro(name) => write!(f, "macro `{}` is missing `.endm`", name),
+            MacroArguments {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "macro `{}` expects {} arguments, found {}",
+                name, expected, found
+            ),
+            MacroRecursion(name) => write!(f, "macro `{}` is nested too deeply", name),
+            UnreadableFile { name, reason } => write!(f, "cannot read `{}`: {}", name, reason),
+            IncludeCycle(name) => write!(f, "`{}` includes itself", name),
+            OutsideAddressSpace => write!(f, "bytes are placed past the last address, 0xFFFFFF"),
+            Overlap { start, end } => write!(
+                f,
+                "bytes from {:#08X} to {:#08X} are already placed",
+                start,
+                end - 1
+            ),
+            NotRelocatable(name) => {
+                write!(f, "value of `{}` is only known after linking", name)
+            }
+            FixedAddress => write!(f, "`.org` cannot be used in a relocatable object"),
+            UndefinedExtern(name) => write!(f, "no linked object exports `{}`", name),
+            InvalidObject(reason) => write!(f, "invalid object: {}", reason),
+            NotConstant(name) => write!(f, "`{}` is not a constant defined before here", name),
+            InvalidCharacter(text) => write!(f, "`{}` is not a character", text),
+            UnclosedBlock(directive) => {
+                let end = match directive.as_str() {
+                    ".rept" => ".endr",
+                    ".struct" => ".endstruct",
+                    ".enum" => ".endenum",
+                    _ => ".endif",
+                };
+                write!(f, "`{}` is missing `{}`", directive, end)
+            }
+            UnknownAlias(name) => write!(f, "`{}` is not a register alias", name),
+            MissingFixedFormat(literal) => write!(
+                f,
+                "`{}` needs a `q` suffix or a `.fixed` before it for its fraction bits",
+                literal
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    pub kind: ErrorKind,
+    pub span: Span,
+    /// Included file the error lies in, or `None` for the main source.
+    pub file: Option<String>,
+    /// Macro invocations the error lies in, innermost first.
+    pub expansions: Vec<Expansion>,
+}
+
+impl AssembleError {
+    pub fn new(kind: ErrorKind, span: impl Into<Span>) -> Self {
+        let span = span.into();
+        Self {
+            kind,
+            span,
+            file: None,
+            expansions: vec![],
+        }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = Position(&self.file, self.span.start);
+        write!(f, "{}: {}", position, self.kind)?;
+        for expansion in &self.expansions {
+            write!(f, "\n  {}", expansion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+/// Every error found while assembling a program, in source order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleErrors(pub Vec<AssembleError>);
+
+impl AssembleErrors {
+    pub fn iter(&self) -> std::slice::Iter<'_, AssembleError> {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for AssembleErrors {
+    type Item = AssembleError;
+    type IntoIter = std::vec::IntoIter<AssembleError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl fmt::Display for AssembleErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssembleErrors {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A `let` value does not fit in 12 bits and no `lethi` loads the rest.
+    Truncated { value: i64, bits: u32 },
+    /// `.alias` names a register that another alias of the scope already
+    /// names.
+    SharedRegister { alias: String, other: String },
+    /// A fixed-point literal has more precision than its fraction bits and
+    /// is rounded to `value`, with `bits` fraction bits.
+    Rounded {
+        literal: String,
+        value: i64,
+        bits: u32,
+    },
+    /// A mistake that [`crate::Assembler::with_lints`] looks for.
+    Lint(Lint),
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use WarningKind::*;
+        match self {
+            Truncated { value, bits } => write!(
+                f,
+                "value {} does not fit in {} bits and is truncated; follow with `lethi` to load all bits",
+                value, bits
+            ),
+            SharedRegister { alias, other } => {
+                write!(f, "`{}` names the same register as `{}`", alias, other)
+            }
+            Rounded {
+                literal,
+                value,
+                bits,
+            } => write!(
+                f,
+                "`{}` is rounded to {} with {} fraction bits",
+                literal,
+                *value as f64 / (1_u64 << bits) as f64,
+                bits
+            ),
+            Lint(lint) => write!(f, "{} [{}]", lint, lint.name()),
+        }
+    }
+}
+
+/// A suspicious construct that still assembles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub span: Span,
+    /// Included file the warning lies in, or `None` for the main source.
+    pub file: Option<String>,
+    /// Macro invocations the warning lies in, innermost first.
+    pub expansions: Vec<Expansion>,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, span: impl Into<Span>) -> Self {
+        let span = span.into();
+        Self {
+            kind,
+            span,
+            file: None,
+            expansions: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = Position(&self.file, self.span.start);
+        write!(f, "{}: warning: {}", position, self.kind)?;
+        for expansion in &self.expansions {
+            write!(f, "\n  {}", expansion)?;
+        }
+        Ok(())
+    }
+}
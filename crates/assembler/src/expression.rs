@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{AssembleError, ErrorKind, Span};
+
+/// Operand of an instruction or directive, evaluated once every label has an
+/// address.
+#[derive(Clone, Debug)]
+pub(crate) enum Expression {
+    Number(i64),
+    /// Address of a label or value of a constant.
+    Symbol(String, Span),
+    /// Signed distance from the end of the instruction to a label.
+    Distance(String, Span),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>, Span),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    BitNot,
+    /// `hi`, bits 12 to 23.
+    High,
+    /// `lo`, bits 0 to 11.
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    ShiftLeft,
+    ShiftRight,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOperator {
+    /// How tightly the operator binds, from `|` at 1 to `*`, `/` and `%`
+    /// at 6.
+    pub(crate) fn precedence(self) -> u8 {
+        use BinaryOperator::*;
+        match self {
+            BitOr => 1,
+            BitXor => 2,
+            BitAnd => 3,
+            ShiftLeft | ShiftRight => 4,
+            Add | Subtract => 5,
+            Multiply | Divide | Remainder => 6,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BinaryOperator::*;
+        let symbol = match self {
+            ShiftLeft => "<<",
+            ShiftRight => ">>",
+            BitOr => "|",
+            BitXor => "^",
+            BitAnd => "&",
+            Add => "+",
+            Subtract => "-",
+            Multiply => "*",
+            Divide => "/",
+            Remainder => "%",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// Value of an expression in a relocatable object: a number plus multiples
+/// of addresses that are only known after linking, like the start of a
+/// section or a symbol of another object.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Value {
+    pub(crate) number: i64,
+    /// Each address with the number of times it is added, never 0.
+    pub(crate) terms: BTreeMap<String, i64>,
+}
+
+impl Value {
+    /// The address of `name` plus `number`.
+    pub(crate) fn address(name: &str, number: i64) -> Self {
+        Self {
+            number,
+            terms: BTreeMap::from([(name.to_string(), 1)]),
+        }
+    }
+
+    /// The number, unless the value depends on addresses.
+    pub(crate) fn number(&self) -> Option<i64> {
+        self.terms.is_empty().then_some(self.number)
+    }
+
+    pub(crate) fn scale(mut self, factor: i64) -> Self {
+        self.number = self.number.wrapping_mul(factor);
+        for count in self.terms.values_mut() {
+            *count *= factor;
+        }
+        self.terms.retain(|_, count| *count != 0);
+        self
+    }
+
+    pub(crate) fn add(mut self, other: Value) -> Self {
+        self.number = self.number.wrapping_add(other.number);
+        for (name, count) in other.terms {
+            *self.terms.entry(name).or_default() += count;
+        }
+        self.terms.retain(|_, count| *count != 0);
+        self
+    }
+}
+
+impl From<i64> for Value {
+    fn from(number: i64) -> Self {
+        Self {
+            number,
+            terms: BTreeMap::new(),
+        }
+    }
+}
+
+/// Values of the labels and constants an expression refers to.
+pub(crate) trait Symbols {
+    /// Address of the label or value of the constant `name`.
+    fn value(&mut self, name: &str, span: Span) -> Result<Value, AssembleError>;
+
+    /// Distance from the end of the instruction to the label `name`.
+    fn distance(&mut self, name: &str, span: Span) -> Result<Value, AssembleError>;
+}
+
+impl Expression {
+    /// Whether the expression can be evaluated without knowing any symbol.
+    pub(crate) fn is_constant(&self) -> bool {
+        use Expression::*;
+        match self {
+            Number(_) => true,
+            Symbol(..) | Distance(..) => false,
+            Unary(_, operand) => operand.is_constant(),
+            Binary(_, left, right, _) => left.is_constant() && right.is_constant(),
+        }
+    }
+
+    /// Names of the labels and constants the expression refers to.
+    pub(crate) fn symbols(&self) -> Vec<&str> {
+        use Expression::*;
+        match self {
+            Number(_) => vec![],
+            Symbol(name, _) | Distance(name, _) => vec![name],
+            Unary(_, operand) => operand.symbols(),
+            Binary(_, left, right, _) => [left.symbols(), right.symbols()].concat(),
+        }
+    }
+
+    /// Evaluate the expression to a number, which fails when it depends
+    /// on addresses only known after linking.
+    pub(crate) fn evaluate(&self, symbols: &mut impl Symbols) -> Result<i64, AssembleError> {
+        let value = self.relocate(symbols)?;
+        value.number().ok_or_else(|| self.not_relocatable())
+    }
+
+    /// Evaluate the expression to a number plus the addresses it depends
+    /// on, which may only be added, subtracted and multiplied by numbers.
+    pub(crate) fn relocate(&self, symbols: &mut impl Symbols) -> Result<Value, AssembleError> {
+        use BinaryOperator::*;
+        use UnaryOperator::*;
+        match self {
+            Expression::Number(value) => Ok(Value::from(*value)),
+            Expression::Symbol(name, span) => symbols.value(name, *span),
+            Expression::Distance(name, span) => symbols.distance(name, *span),
+            Expression::Unary(Negate, operand) => Ok(operand.relocate(symbols)?.scale(-1)),
+            Expression::Unary(operator, operand) => {
+                let value = operand.relocate(symbols)?;
+                let value = value.number().ok_or_else(|| operand.not_relocatable())?;
+                Ok(Value::from(match operator {
+                    Negate => unreachable!(),
+                    BitNot => !value,
+                    High => (value >> 12) & 0o77_77,
+                    Low => value & 0o77_77,
+                }))
+            }
+            Expression::Binary(operator, left_expression, right_expression, span) => {
+                let left = left_expression.relocate(symbols)?;
+                let right = right_expression.relocate(symbols)?;
+                match operator {
+                    Add => return Ok(left.add(right)),
+                    Subtract => return Ok(left.add(right.scale(-1))),
+                    Multiply => match (left.number(), right.number()) {
+                        (Some(factor), _) => return Ok(right.scale(factor)),
+                        (_, Some(factor)) => return Ok(left.scale(factor)),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                let left = left
+                    .number()
+                    .ok_or_else(|| left_expression.not_relocatable())?;
+                let right = right
+                    .number()
+                    .ok_or_else(|| right_expression.not_relocatable())?;
+                match operator {
+                    ShiftLeft | ShiftRight if !(0..64).contains(&right) => {
+                        let kind = ErrorKind::OutOfRange {
+                            value: right,
+                            min: 0,
+                            max: 63,
+                        };
+                        Err(AssembleError::new(kind, *span))
+                    }
+                    Divide | Remainder if right == 0 => {
+                        Err(AssembleError::new(ErrorKind::DivisionByZero, *span))
+                    }
+                    ShiftLeft => Ok(Value::from(left << right)),
+                    ShiftRight => Ok(Value::from(left >> right)),
+                    BitOr => Ok(Value::from(left | right)),
+                    BitXor => Ok(Value::from(left ^ right)),
+                    BitAnd => Ok(Value::from(left & right)),
+                    Multiply => Ok(Value::from(left.wrapping_mul(right))),
+                    Divide => Ok(Value::from(left.wrapping_div(right))),
+                    Remainder => Ok(Value::from(left.wrapping_rem(right))),
+                    Add | Subtract => unreachable!(),
+                }
+            }
+        }
+    }
+
+    /// Error for using the value of a label that is only known after
+    /// linking, pointing at the first label the expression refers to.
+    pub(crate) fn not_relocatable(&self) -> AssembleError {
+        let (name, span) = self.first_symbol().unwrap_or_default();
+        AssembleError::new(ErrorKind::NotRelocatable(written(name).to_string()), span)
+    }
+
+    fn first_symbol(&self) -> Option<(&str, Span)> {
+        use Expression::*;
+        match self {
+            Number(_) => None,
+            Symbol(name, span) | Distance(name, span) => Some((name, *span)),
+            Unary(_, operand) => operand.first_symbol(),
+            Binary(_, left, right, _) => left.first_symbol().or_else(|| right.first_symbol()),
+        }
+    }
+}
+
+/// Text of the reference to the label or constant `name`. A reference to
+/// an anonymous label is named after its text followed by the label it
+/// reaches, like `:- :2`.
+pub(crate) fn written(name: &str) -> &str {
+    name.split_once(' ').map_or(name, |(text, _)| text)
+}
+
+/// Label or constant that the reference `name` reaches.
+pub(crate) fn referred(name: &str) -> &str {
+    name.split_once(' ').map_or(name, |(_, label)| label)
+}
@@ -0,0 +1,61 @@
+use crate::{
+    ast::{self, Expression, Operand, StatementKind},
+    AssembleErrors,
+};
+
+/// Format `source` in the canonical layout of [`ast::Program`], with
+/// mnemonics and directives in lower case and number literals written as
+/// `0xFF`, `0b1010`, `0o17` and `1.5q12`. Comments are kept, and formatting
+/// formatted source leaves it as it is.
+pub fn format(source: &str) -> Result<String, AssembleErrors> {
+    let mut program = ast::parse(source)?;
+    for statement in &mut program.statements {
+        normalize(&mut statement.kind);
+    }
+    Ok(program.to_string())
+}
+
+fn normalize(kind: &mut StatementKind) {
+    match kind {
+        StatementKind::Instruction { mnemonic, operands } => {
+            *mnemonic = mnemonic.to_lowercase();
+            operands.iter_mut().for_each(normalize_operand);
+        }
+        StatementKind::Directive { name, operands } => {
+            *name = name.to_lowercase();
+            operands.iter_mut().for_each(normalize_operand);
+        }
+        StatementKind::Member {
+            value: Some(value), ..
+        } => normalize_expression(value),
+        _ => {}
+    }
+}
+
+fn normalize_operand(operand: &mut Operand) {
+    if let Operand::Expression(expression) = operand {
+        normalize_expression(expression);
+    }
+}
+
+fn normalize_expression(expression: &mut Expression) {
+    match expression {
+        Expression::Number(text, _) => *text = normalize_number(text),
+        Expression::Unary(_, operand, _) => normalize_expression(operand),
+        Expression::Binary(_, left, right, _) => {
+            normalize_expression(left);
+            normalize_expression(right);
+        }
+        _ => {}
+    }
+}
+
+/// Write the prefix of a number literal, and the `q` of a fixed-point one,
+/// in lower case and hexadecimal digits in upper case.
+fn normalize_number(text: &str) -> String {
+    let lower = text.to_lowercase();
+    match lower.strip_prefix("0x") {
+        Some(digits) => format!("0x{}", digits.to_uppercase()),
+        None => lower,
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{AssembleError, ErrorKind, Span};
+
+/// Bytes placed at consecutive addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl Segment {
+    /// Address just past the last byte.
+    pub fn end(&self) -> u32 {
+        self.address + self.bytes.len() as u32
+    }
+}
+
+/// Number of addresses, which every byte must be placed below.
+pub(crate) const ADDRESS_SPACE: u64 = 1 << 24;
+
+/// Segments in the order the source places them, each with the span of the
+/// `.org` that started it.
+pub(crate) struct Image {
+    segments: Vec<(Segment, Span)>,
+    /// Number of bytes placed so far.
+    length: u32,
+}
+
+impl Default for Image {
+    fn default() -> Self {
+        let segment = Segment {
+            address: 0,
+            bytes: vec![],
+        };
+        Self {
+            segments: vec![(segment, Span::default())],
+            length: 0,
+        }
+    }
+}
+
+impl Image {
+    /// Address the next byte is placed at.
+    pub(crate) fn address(&self) -> u32 {
+        self.current().end()
+    }
+
+    fn current(&self) -> &Segment {
+        // There is always at least one segment.
+        &self.segments.last().unwrap().0
+    }
+
+    /// Number of bytes placed so far.
+    pub(crate) fn len(&self) -> u32 {
+        self.length
+    }
+
+    /// Whether `length` more bytes fit below the end of the address space.
+    pub(crate) fn fits(&self, length: u64) -> bool {
+        self.address() as u64 + length <= ADDRESS_SPACE
+    }
+
+    pub(crate) fn extend(&mut self, bytes: impl IntoIterator<Item = u8>) {
+        let segment = &mut self.segments.last_mut().unwrap().0;
+        let length = segment.bytes.len();
+        segment.bytes.extend(bytes);
+        self.length += (segment.bytes.len() - length) as u32;
+    }
+
+    /// Place the next byte at `address`, starting a new segment unless it
+    /// follows on from the current one.
+    pub(crate) fn set_address(&mut self, address: u32, span: Span) {
+        if address == self.address() {
+            return;
+        }
+        let segment = Segment {
+            address,
+            bytes: vec![],
+        };
+        if self.current().bytes.is_empty() {
+            self.segments.pop();
+        }
+        self.segments.push((segment, span));
+    }
+
+    /// The `length` bytes at `address`, if they have all been placed.
+    pub(crate) fn get(&self, address: u32, length: usize) -> Option<&[u8]> {
+        self.segments.iter().rev().find_map(|(segment, _)| {
+            let start = address.checked_sub(segment.address)? as usize;
+            segment.bytes.get(start..start + length)
+        })
+    }
+
+    /// The `length` bytes at `address`, if they have all been placed.
+    pub(crate) fn get_mut(&mut self, address: u32, length: usize) -> Option<&mut [u8]> {
+        self.segments.iter_mut().rev().find_map(|(segment, _)| {
+            let start = address.checked_sub(segment.address)? as usize;
+            segment.bytes.get_mut(start..start + length)
+        })
+    }
+
+    /// OR `bits` into the big-endian number in the `length` bytes at
+    /// `address`. Returns whether all of them have been placed.
+    pub(crate) fn patch(&mut self, address: u32, length: u32, bits: u32) -> bool {
+        let Some(bytes) = self.get_mut(address, length as usize) else {
+            return false;
+        };
+        let number = bytes
+            .iter()
+            .fold(0, |number, &byte| number << 8 | byte as u32);
+        let number = (number | bits).to_be_bytes();
+        bytes.copy_from_slice(&number[4 - length as usize..]);
+        true
+    }
+
+    /// Errors for the segments placed over the bytes of earlier segments.
+    pub(crate) fn overlaps(&self) -> Vec<AssembleError> {
+        let mut errors = vec![];
+        for (index, (segment, span)) in self.segments.iter().enumerate() {
+            for (earlier, _) in &self.segments[..index] {
+                let start = segment.address.max(earlier.address);
+                let end = segment.end().min(earlier.end());
+                if start < end {
+                    let kind = ErrorKind::Overlap { start, end };
+                    errors.push(AssembleError::new(kind, *span));
+                    break;
+                }
+            }
+        }
+        errors
+    }
+
+    /// The segments with bytes, in order of address.
+    pub(crate) fn segments(&self) -> Vec<Segment> {
+        let mut segments: Vec<_> = self
+            .segments
+            .iter()
+            .map(|(segment, _)| segment.clone())
+            .filter(|segment| !segment.bytes.is_empty())
+            .collect();
+        segments.sort_by_key(|segment| segment.address);
+        segments
+    }
+}
@@ -1,149 +1,840 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use common::Op;
+use common::{
+    io, Format, Instruction, Op, REGISTER_GLOBAL, REGISTER_LINK, REGISTER_PROGRAM_COUNTER,
+    REGISTER_TEMPORARY,
+};
+use expression::{BinaryOperator, Expression, Symbols, UnaryOperator, Value};
+use image::Image;
+use lint::Linter;
+use listing::Statement;
 use pest::{
     iterators::{Pair, Pairs},
+    pratt_parser::{Assoc, Op as Operator, PrattParser},
     Parser,
 };
 use pest_derive::Parser;
+use preprocessor::{Expanded, SourceMap};
+use text::{Charmap, Unit};
+
+mod artifact;
+pub mod ast;
+mod disassembler;
+mod error;
+mod expression;
+mod formatter;
+mod image;
+mod linker;
+mod lint;
+mod listing;
+mod object;
+mod preprocessor;
+mod resolver;
+mod syntax;
+mod text;
+
+pub use artifact::{Assembly, SourceLine, Symbol, SymbolKind};
+pub use disassembler::Disassembler;
+pub use error::{
+    AssembleError, AssembleErrors, ErrorKind, Expansion, Location, Span, Warning, WarningKind,
+};
+pub use formatter::format;
+pub use image::Segment;
+pub use linker::{Linked, Linker, Placement};
+pub use lint::Lint;
+pub use object::{Object, ObjectSymbol, Relocation, RelocationKind, Section, SectionKind};
+pub use resolver::{FileResolver, MemoryResolver, SourceResolver};
 
 #[derive(Parser)]
 #[grammar = "kittyasm.pest"]
 struct KittyAssemblyParser;
 
-struct LabelReference {
-    identifier: String,
-    address: u32,
+/// Values a field accepts. A negative `min` allows two's complement values.
+#[derive(Clone, Copy)]
+struct ValueRange {
+    min: i64,
+    max: i64,
+}
+
+impl ValueRange {
+    /// Values that fit in `bits` bits, either signed or unsigned.
+    const fn bits(bits: u32) -> Self {
+        Self {
+            min: -(1 << (bits - 1)),
+            max: (1 << bits) - 1,
+        }
+    }
+
+    /// Values that fit in `bits` bits as a signed number.
+    const fn signed(bits: u32) -> Self {
+        Self {
+            min: -(1 << (bits - 1)),
+            max: (1 << (bits - 1)) - 1,
+        }
+    }
+
+    /// Values that fit in `bits` bits as an unsigned number.
+    const fn unsigned(bits: u32) -> Self {
+        Self {
+            min: 0,
+            max: (1 << bits) - 1,
+        }
+    }
+
+    fn contains(&self, value: i64) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+}
+
+/// Bits of an instruction or data value a value is encoded into.
+#[derive(Clone, Copy)]
+struct Field {
+    /// Number of bytes from the address of the reference that hold the
+    /// field, as a big-endian number.
+    bytes: u32,
     length: u32,
     shift: u32,
+    range: ValueRange,
+}
+
+/// Sign of the distance to a `~label`, given by the instruction that uses it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// The distance is encoded as a signed value.
+    Any,
+    /// `addi` only adds the distance, so the label must lie ahead.
+    Forward,
+    /// `subi` only subtracts the distance, so the label must lie behind.
+    Backward,
+    /// `branch` becomes `addi` or `subi` depending on where the label lies.
+    Either,
+}
+
+/// An operand that refers to labels or constants, patched in once they all
+/// have values.
+#[derive(Clone)]
+struct Reference {
+    expression: Expression,
+    operand: String,
+    address: u32,
+    field: Field,
+    /// Whether a value outside the range of the field is truncated with a
+    /// warning instead of rejected.
+    truncates: bool,
+    /// Index of the branch instruction to relax when the distance does
+    /// not fit in the field.
+    relaxation: Option<usize>,
+    direction: Direction,
+    section: SectionKind,
+    span: Span,
+}
+
+/// A `let` whose value does not fit in 12 bits, waiting for the `lethi`
+/// that loads the remaining bits into the same register.
+struct PendingLet {
+    register: u32,
+    operand: String,
+    value: i64,
+    /// Index into the references when the operand refers to labels or
+    /// constants.
+    reference: Option<usize>,
+    span: Span,
+}
+
+/// A `.struct` or `.enum` whose fields or names are being defined.
+struct Layout {
+    /// `.struct` or `.enum`.
+    directive: String,
+    name: String,
+    /// Offset of the next field, or value of the next name.
+    next: i64,
+    span: Span,
+}
+
+/// A constant defined with `.equ` or `.set`, or by `.struct` or `.enum`.
+struct Constant {
+    expression: Expression,
+    /// Address of the definition, which `~label` distances are taken from.
+    address: u32,
+    section: SectionKind,
+    /// Whether `.set` may give the constant a new value.
+    redefinable: bool,
 }
 
 pub struct Assembler {
-    bytes: Vec<u8>,
+    image: Image,
     labels: HashMap<String, u32>,
+    constants: HashMap<String, Constant>,
     scope: String,
-    relative_references: Vec<LabelReference>,
-    absolute_references: Vec<LabelReference>,
-    delta_references: Vec<LabelReference>,
+    references: Vec<Reference>,
+    pending_lets: Vec<PendingLet>,
+    errors: Vec<AssembleError>,
+    warnings: Vec<Warning>,
+    /// Index of the instruction being parsed, the same across passes.
+    instruction: usize,
+    /// Branches emitted as far branches because their distance does not fit
+    /// in the `u` field. Kept across passes.
+    relaxed: HashSet<usize>,
+    /// Reads the files of `.include` and `.incbin`. Kept across passes.
+    resolver: Option<Box<dyn SourceResolver>>,
+    /// Whether to produce a listing. Kept across passes.
+    listing: bool,
+    /// Where each statement placed its bytes.
+    statements: Vec<Statement>,
+    /// Whether to assemble a relocatable object. Kept across passes.
+    object: bool,
+    /// Section that `image` holds the bytes of.
+    section: SectionKind,
+    /// Bytes of the other section of an object.
+    parked: Image,
+    /// Largest `.align` of each section of an object.
+    alignments: HashMap<SectionKind, u32>,
+    /// Section of each label of an object.
+    label_sections: HashMap<String, SectionKind>,
+    /// Names declared with `.global`.
+    globals: Vec<(String, Span)>,
+    /// Names declared with `.extern`.
+    externs: Vec<(String, Span)>,
+    /// Fields of an object to patch once it is linked.
+    relocations: Vec<Relocation>,
+    /// Constants defined by the caller. Kept across passes.
+    defines: Vec<(String, i64)>,
+    /// Bytes of the characters given by `.charmap` so far.
+    charmap: Charmap,
+    /// Number of anonymous labels defined so far, by their digits or ``
+    /// for `:`.
+    anonymous: HashMap<String, usize>,
+    /// Registers named with `.alias` since the last global label.
+    aliases: HashMap<String, u32>,
+    /// The `.struct` or `.enum` up to its end.
+    layout: Option<Layout>,
+    /// Fraction bits of fixed-point literals without a `q`, from `.fixed`.
+    fixed: Option<u32>,
+    /// Whether to look for the mistakes of [`Lint`]. Kept across passes.
+    lints: bool,
+    /// Labels in the order they are defined, for the lints.
+    definitions: Vec<(String, Span)>,
+    /// Labels and constants that expressions refer to, for the lints.
+    referenced: HashSet<String>,
 }
 
 impl Assembler {
-    pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
-        match KittyAssemblyParser::parse(Rule::Program, source) {
+    pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleErrors> {
+        Self::default()
+            .build(source)
+            .map(|assembly| assembly.bytes())
+    }
+
+    /// Read the files that `.include` and `.incbin` name through `resolver`.
+    pub fn with_resolver(mut self, resolver: impl SourceResolver + 'static) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// List every line with its address, bytes and the values of the
+    /// symbols it refers to.
+    pub fn with_listing(mut self) -> Self {
+        self.listing = true;
+        self
+    }
+
+    /// Report the mistakes of [`Lint`] in the assembled program as
+    /// warnings, unless a `; lint: allow(name)` comment on their line names
+    /// them. Only [`Self::build`] lints, as the addresses of an object are
+    /// not final.
+    pub fn with_lints(mut self) -> Self {
+        self.lints = true;
+        self
+    }
+
+    /// Define the constant `name` as `value` before the first line, so
+    /// `.if` and `.ifdef` can choose what to assemble.
+    pub fn with_define(mut self, name: impl Into<String>, value: i64) -> Self {
+        self.defines.push((name.into(), value));
+        self
+    }
+
+    /// Assemble `source`, keeping the warnings along with the bytes.
+    ///
+    /// Sections and linkage only matter to objects: `.code` and `.data`
+    /// are ignored, so the bytes follow the source, and `.extern` names
+    /// must be defined like any other label.
+    pub fn build(mut self, source: &str) -> Result<Assembly, AssembleErrors> {
+        let expanded = self.run(source)?;
+        if self.lints {
+            self.lint(&expanded.source);
+        }
+        Ok(self.assembly(&expanded))
+    }
+
+    /// Assemble `source` into a relocatable object, which the [`Linker`]
+    /// places along with other objects. Labels are offsets into their
+    /// section and every field that depends on their addresses, or on an
+    /// `.extern` symbol, becomes a relocation.
+    pub fn build_object(mut self, source: &str) -> Result<Object, AssembleErrors> {
+        self.object = true;
+        let expanded = self.run(source)?;
+        self.finish_object(&expanded)
+    }
+
+    fn run(&mut self, source: &str) -> Result<Expanded, AssembleErrors> {
+        let expanded = match preprocessor::expand(source, self.resolver.as_deref(), &self.defines) {
+            Ok(expanded) => expanded,
+            // The preprocessor reports its errors in source order.
+            Err(errors) => return Err(AssembleErrors(errors)),
+        };
+        let program = match KittyAssemblyParser::parse(Rule::Program, &expanded.source) {
             // The parse was successful; unwrap cannot fail here.
-            Ok(mut program) => Self::default().parse_program(program.next().unwrap()),
-            Err(error) => Err(error.to_string()),
+            Ok(mut program) => program.next().unwrap(),
+            Err(error) => {
+                let error = syntax::from_pest(error, Location { line: 1, column: 1 });
+                self.errors.push(error);
+                return Err(self.take_errors(&expanded.map));
+            }
+        };
+        // Relaxing a branch moves the labels after it, which may push other
+        // branches out of range, so repeat until no more branches relax.
+        loop {
+            let relaxed = self.relaxed.len();
+            self.parse_program(program.clone());
+            if self.relaxed.len() == relaxed {
+                break;
+            }
+            self.reset();
+        }
+        match self.errors.is_empty() {
+            true => Ok(expanded),
+            false => Err(self.take_errors(&expanded.map)),
         }
     }
 
-    fn parse_program(&mut self, pair: Pair<Rule>) -> Result<Vec<u8>, String> {
+    /// Clear everything but the relaxed branches and the options for another
+    /// pass.
+    fn reset(&mut self) {
+        *self = Self {
+            relaxed: std::mem::take(&mut self.relaxed),
+            resolver: self.resolver.take(),
+            listing: self.listing,
+            lints: self.lints,
+            object: self.object,
+            defines: std::mem::take(&mut self.defines),
+            ..Default::default()
+        };
+    }
+
+    fn parse_program(&mut self, pair: Pair<Rule>) {
+        self.define_constants();
+        let mut previous = None;
         for statement in pair.into_inner() {
+            let length = self.image.len();
+            let fits = self.image.fits(0);
             match statement.as_rule() {
-                Rule::Instruction => self.parse_instruction(statement),
-                Rule::LabelDefinition => self.parse_label_definition(statement),
-                Rule::Data => self.parse_data(statement),
+                Rule::Layout => self.parse_layout(statement.clone()),
+                Rule::Invalid => self.parse_invalid(statement.clone(), previous),
                 Rule::EOI => break,
+                _ if self.layout.is_some() => self.parse_layout_entry(statement.clone()),
+                Rule::Instruction => self.parse_instruction(statement.clone()),
+                Rule::LabelDefinition => self.parse_label_definition(statement.clone()),
+                Rule::Data => self.parse_data(statement.clone()),
+                Rule::Text => self.parse_text(statement.clone()),
+                Rule::Charmap => self.parse_charmap(statement.clone()),
+                Rule::IncludeBinary => self.parse_include_binary(statement.clone()),
+                Rule::Placement => self.parse_placement(statement.clone()),
+                Rule::FixedFormat => self.parse_fixed_format(statement.clone()),
+                Rule::Constant => self.parse_constant(statement.clone()),
+                Rule::Alias => self.parse_alias(statement.clone()),
+                Rule::Linkage => self.parse_linkage(statement.clone()),
+                Rule::Section => {
+                    // The bytes of the statements after it go elsewhere.
+                    self.parse_section(statement.clone());
+                    previous = Some(statement);
+                    continue;
+                }
+                Rule::Reserve => {
+                    let kind = ErrorKind::Syntax("`.res` outside `.struct`".to_string());
+                    self.errors
+                        .push(AssembleError::new(kind, statement.as_span()));
+                }
+                // Only names in `.enum` are statements on their own.
+                Rule::Member => self.parse_invalid(statement.clone(), previous),
                 _ => unreachable!(),
             }
+            if fits && !self.image.fits(0) {
+                let kind = ErrorKind::OutsideAddressSpace;
+                self.errors
+                    .push(AssembleError::new(kind, statement.as_span()));
+            }
+            let length = self.image.len() - length;
+            self.statements.push(Statement {
+                span: statement.as_span().into(),
+                address: self.image.address() - length,
+                length,
+                instruction: statement.as_rule() == Rule::Instruction,
+            });
+            previous = Some(statement);
+        }
+        if let Some(layout) = self.layout.take() {
+            let kind = ErrorKind::UnclosedBlock(layout.directive);
+            self.errors.push(AssembleError::new(kind, layout.span));
+        }
+        self.flush_pending_lets();
+        self.resolve_references();
+        self.check_constants();
+        self.errors.extend(self.image.overlaps());
+    }
+
+    /// Add the addresses of the IO registers, as `IO.NAME`, and the
+    /// constants defined by the caller.
+    fn define_constants(&mut self) {
+        let registers = io::REGISTERS
+            .iter()
+            .map(|register| (format!("IO.{}", register.name), register.address as i64));
+        for (name, value) in registers.chain(self.defines.iter().cloned()) {
+            let constant = Constant {
+                expression: Expression::Number(value),
+                address: 0,
+                section: SectionKind::Code,
+                redefinable: false,
+            };
+            self.constants.insert(name, constant);
+        }
+    }
+
+    /// Sort warnings in the order of the expanded source, which keeps
+    /// included files and macro bodies where they are used, and map them
+    /// back to the original source.
+    fn take_warnings(&mut self, map: &SourceMap) -> Vec<Warning> {
+        self.warnings.sort_by_key(|warning| warning.span.start);
+        std::mem::take(&mut self.warnings)
+            .into_iter()
+            .map(|warning| map.map_warning(warning))
+            .collect()
+    }
+
+    /// Sort and map errors like [`Self::take_warnings`].
+    fn take_errors(&mut self, map: &SourceMap) -> AssembleErrors {
+        self.errors.sort_by_key(|error| error.span.start);
+        let mut errors: Vec<_> = std::mem::take(&mut self.errors)
+            .into_iter()
+            .map(|error| map.map_error(error))
+            .collect();
+        // Pseudo-instructions may parse an operand more than once.
+        errors.dedup();
+        AssembleErrors(errors)
+    }
+
+    fn lint(&mut self, source: &str) {
+        let linter = Linter::new(
+            &self.statements,
+            &self.image,
+            &self.labels,
+            &self.definitions,
+            &self.referenced,
+        );
+        let warnings = linter.run(source);
+        self.warnings.extend(warnings);
+    }
+
+    fn assembly(&mut self, expanded: &Expanded) -> Assembly {
+        let map = &expanded.map;
+        let listing = self.listing.then(|| {
+            let symbols = self.referenced_symbols();
+            listing::write(
+                &expanded.source,
+                map,
+                &self.statements,
+                &self.image,
+                &symbols,
+            )
+        });
+        Assembly {
+            segments: self.image.segments(),
+            warnings: self.take_warnings(map),
+            listing,
+            symbols: self.symbols(),
+            lines: self.source_lines(map),
+        }
+    }
+
+    fn finish_object(&mut self, expanded: &Expanded) -> Result<Object, AssembleErrors> {
+        let map = &expanded.map;
+        let mut symbols: Vec<_> = self
+            .symbols()
+            .into_iter()
+            .filter(|symbol| symbol.kind != SymbolKind::Relative)
+            .map(|symbol| ObjectSymbol {
+                section: Some(self.label_sections[&symbol.name]),
+                global: self.globals.iter().any(|(name, _)| *name == symbol.name),
+                name: symbol.name,
+                value: symbol.value,
+            })
+            .collect();
+        for (name, span) in &self.globals {
+            if self.labels.contains_key(name) {
+                continue;
+            }
+            let Some(constant) = self.constants.get(name) else {
+                let kind = ErrorKind::UnknownLabel(name.clone());
+                self.errors.push(AssembleError::new(kind, *span));
+                continue;
+            };
+            let mut resolver = self.lookup(constant.address, constant.section);
+            match constant.expression.evaluate(&mut resolver) {
+                Ok(value) => symbols.push(ObjectSymbol {
+                    name: name.clone(),
+                    section: None,
+                    value: value as u32,
+                    global: true,
+                }),
+                Err(error) => self.errors.push(error),
+            }
+        }
+        for (name, span) in &self.externs {
+            if self.labels.contains_key(name) || self.constants.contains_key(name) {
+                let kind = ErrorKind::DuplicateSymbol(name.clone());
+                self.errors.push(AssembleError::new(kind, *span));
+            }
+        }
+        if !self.errors.is_empty() {
+            return Err(self.take_errors(map));
+        }
+        let sections = SectionKind::ALL
+            .into_iter()
+            .map(|kind| Section {
+                kind,
+                alignment: self.alignments.get(&kind).copied().unwrap_or(1),
+                bytes: std::mem::take(self.image_mut(kind))
+                    .segments()
+                    .into_iter()
+                    .flat_map(|segment| segment.bytes)
+                    .collect(),
+            })
+            .collect();
+        let relocations = std::mem::take(&mut self.relocations)
+            .into_iter()
+            .map(|mut relocation| {
+                (relocation.file, relocation.span) = map.map_span(relocation.span);
+                relocation
+            })
+            .collect();
+        Ok(Object {
+            sections,
+            symbols,
+            externs: self.externs.iter().map(|(name, _)| name.clone()).collect(),
+            relocations,
+            warnings: self.take_warnings(map),
+        })
+    }
+
+    /// Every label, in order of value.
+    fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<_> = self
+            .labels
+            .iter()
+            // Anonymous labels are only for the references near them.
+            .filter(|(name, _)| !name.contains(':'))
+            .map(|(name, &value)| {
+                let kind = if name.contains('~') {
+                    SymbolKind::Relative
+                } else if name.contains('.') {
+                    SymbolKind::Local
+                } else {
+                    SymbolKind::Global
+                };
+                Symbol {
+                    name: name.clone(),
+                    value,
+                    kind,
+                }
+            })
+            .collect();
+        symbols.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+        symbols
+    }
+
+    /// Lines of the original source that placed bytes, in order of address.
+    fn source_lines(&self, map: &SourceMap) -> Vec<SourceLine> {
+        let mut lines: Vec<SourceLine> = vec![];
+        for statement in &self.statements {
+            if statement.length == 0 {
+                continue;
+            }
+            let (file, line) = map.locate(statement.span.start.line);
+            match lines.last_mut() {
+                // Statements on the same line, like those of a macro body
+                // line, are one entry.
+                Some(last)
+                    if last.file == file
+                        && last.line == line
+                        && last.address + last.length == statement.address =>
+                {
+                    last.length += statement.length;
+                }
+                _ => lines.push(SourceLine {
+                    address: statement.address,
+                    length: statement.length,
+                    file,
+                    line,
+                }),
+            }
+        }
+        lines.sort_by_key(|line| line.address);
+        lines
+    }
+
+    /// Values of the symbols each line of the expanded source refers to,
+    /// as `name = value`.
+    fn referenced_symbols(&self) -> BTreeMap<usize, Vec<String>> {
+        let mut symbols: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for reference in &self.references {
+            let line = symbols.entry(reference.span.start.line).or_default();
+            for name in reference.expression.symbols() {
+                let mut resolver = self.lookup(reference.address, reference.section);
+                let Ok(value) = resolver.value(name, reference.span) else {
+                    continue;
+                };
+                let value = value.number;
+                let symbol = match self.labels.contains_key(expression::referred(name)) {
+                    true => format!("{} = {:06X}", expression::written(name), value),
+                    false => format!("{} = {}", name, value),
+                };
+                if !line.contains(&symbol) {
+                    line.push(symbol);
+                }
+            }
+        }
+        symbols
+    }
+
+    /// Describe a line that is not a statement. A statement that only
+    /// parsed up to the invalid text on its line, like `1` in `let r1, 1 +`,
+    /// is described along with it.
+    fn parse_invalid(&mut self, pair: Pair<Rule>, previous: Option<Pair<Rule>>) {
+        let start = match previous {
+            Some(previous)
+                if previous.as_rule() != Rule::LabelDefinition
+                    && previous.line_col().0 == pair.line_col().0 =>
+            {
+                previous
+            }
+            _ => pair.clone(),
+        };
+        let (line, column) = start.line_col();
+        let text = &pair.get_input()[start.as_span().start()..pair.as_span().end()];
+        let error = syntax::diagnose(text, Location { line, column }, &self.aliases);
+        self.errors.push(error);
+    }
+
+    fn resolve_references(&mut self) {
+        // The references stay for the listing.
+        for mut reference in self.references.clone() {
+            let mut resolver = self.lookup(reference.address, reference.section);
+            let value = match reference.expression.relocate(&mut resolver) {
+                Ok(value) => value,
+                Err(error) => {
+                    self.errors.push(error);
+                    continue;
+                }
+            };
+            let value = match (value.number(), reference.relaxation) {
+                (Some(number), _) => number,
+                // Only a far branch reaches a label in another section or
+                // object.
+                (None, Some(index)) => {
+                    self.relaxed.insert(index);
+                    continue;
+                }
+                (None, None) => {
+                    self.relocate(&reference, value);
+                    continue;
+                }
+            };
+            let value = match reference.direction {
+                Direction::Any => value,
+                Direction::Forward if value >= 0 => value,
+                Direction::Backward if value <= 0 => -value,
+                Direction::Either if value < 0 => {
+                    self.set_op(reference.address, reference.section, Op::Subi);
+                    -value
+                }
+                Direction::Either => value,
+                Direction::Forward | Direction::Backward => {
+                    let Expression::Distance(label, _) = reference.expression else {
+                        unreachable!()
+                    };
+                    let kind = ErrorKind::WrongDirection {
+                        label: expression::written(&label).to_string(),
+                        distance: value,
+                    };
+                    self.errors.push(AssembleError::new(kind, reference.span));
+                    continue;
+                }
+            };
+            if reference.direction != Direction::Any {
+                // The direction is given by the instruction, so only the
+                // distance is encoded.
+                reference.field.range.min = 0;
+            }
+            match reference.relaxation {
+                Some(index) if !reference.field.range.contains(value) => {
+                    self.relaxed.insert(index);
+                }
+                _ => self.patch(&reference, value),
+            }
+        }
+    }
+
+    /// Report constants that cannot be evaluated, even when unused.
+    fn check_constants(&mut self) {
+        for constant in self.constants.values() {
+            let mut resolver = self.lookup(constant.address, constant.section);
+            if let Err(error) = constant.expression.relocate(&mut resolver) {
+                self.errors.push(error);
+            }
         }
-        for LabelReference {
-            identifier,
+    }
+
+    /// Shift and mask `value` into the field of the instruction or data
+    /// value at the reference address.
+    fn patch(&mut self, reference: &Reference, value: i64) {
+        let Reference {
+            operand,
             address,
-            length,
-            shift,
-        } in &self.absolute_references
-        {
-            if let Some(&target) = self.labels.get(identifier) {
-                let mask = 2_u32.pow(*length) - 1;
-                let u = target;
-                let u = u >> shift;
-                let u = u & mask;
-                // TODO: Don't panic, instead return Err(string)
-                let [a, b, c]: [u8; 3] = self.bytes[*address as usize..*address as usize + 3]
-                    .try_into()
-                    .expect(&format!(
-                        "LabelReference: {} @ {} + {}",
-                        identifier, address, length
-                    ));
-                let instruction = u32::from_be_bytes([0, a, b, c]);
-                let instruction = instruction | u;
-                let [_, a, b, c] = instruction.to_be_bytes();
-                self.bytes[*address as usize + 0] = a;
-                self.bytes[*address as usize + 1] = b;
-                self.bytes[*address as usize + 2] = c;
-            } else {
-                return Err(format!(
-                    "Unknown label: `{}` in\n{:#?}",
-                    identifier, self.labels
-                ));
-            }
-        }
-        for LabelReference {
-            identifier,
+            field,
+            truncates,
+            section,
+            span,
+            ..
+        } = reference;
+        if *truncates {
+            self.check_truncation(value, field.length, *span);
+        } else {
+            self.check_range(value, field.range, *span);
+        }
+        let mask = 2_u32.pow(field.length) - 1;
+        let u = value as u32;
+        let u = u >> field.shift;
+        let u = u & mask;
+        if !self.image_mut(*section).patch(*address, field.bytes, u) {
+            let kind = ErrorKind::ReferenceOutOfBounds(operand.clone());
+            self.errors.push(AssembleError::new(kind, *span));
+        }
+    }
+
+    /// Record the field of `reference` as a relocation, as its `value`
+    /// depends on addresses only known after linking.
+    fn relocate(&mut self, reference: &Reference, value: Value) {
+        let Reference {
+            expression,
             address,
-            length,
-            shift,
-        } in &self.relative_references
-        {
-            if let Some(&target) = self.labels.get(identifier) {
-                let mask = 2_u32.pow(*length) - 1;
-                // TODO: Error on negative addi or positive subi
-                // TODO: Maybe pseudo-instructions `jump`/`cjump` and maybe `letall`/`cletall`
-                let u = (target as i32 - (*address as i32 + 3)).abs() as u32;
-                let u = u >> shift;
-                let u = u & mask;
-                let [a, b, c]: [u8; 3] = self.bytes[*address as usize..*address as usize + 3]
-                    .try_into()
-                    .unwrap();
-                let instruction = u32::from_be_bytes([0, a, b, c]);
-                let instruction = instruction | u;
-                let [_, a, b, c] = instruction.to_be_bytes();
-                self.bytes[*address as usize + 0] = a;
-                self.bytes[*address as usize + 1] = b;
-                self.bytes[*address as usize + 2] = c;
-            } else {
-                return Err(format!(
-                    "Unknown label: `{}` in\n{:#?}",
-                    identifier, self.labels
-                ));
-            }
-        }
-        for LabelReference {
-            identifier,
+            field,
+            truncates,
+            direction,
+            section,
+            span,
+            ..
+        } = reference;
+        let here = section.base();
+        let terms: Vec<_> = value
+            .terms
+            .iter()
+            .map(|(name, &count)| (name, count))
+            .collect();
+        let (kind, symbol, addend) = match (terms.as_slice(), field.shift) {
+            _ if *direction != Direction::Any => {
+                self.errors.push(expression.not_relocatable());
+                return;
+            }
+            ([(symbol, 1)], 0) => (RelocationKind::Absolute, symbol, value.number),
+            ([(symbol, 1)], 12) => (RelocationKind::High, symbol, value.number),
+            ([(symbol, 1), (base, -1)] | [(base, -1), (symbol, 1)], 0) if *base == here => {
+                // The linker measures from the end of the instruction.
+                let addend = value.number + *address as i64 + 3;
+                (RelocationKind::Relative, symbol, addend)
+            }
+            ([(symbol, 1), (base, -1)] | [(base, -1), (symbol, 1)], 0) => {
+                let kind = RelocationKind::Delta(base.to_string());
+                (kind, symbol, value.number)
+            }
+            _ => {
+                self.errors.push(expression.not_relocatable());
+                return;
+            }
+        };
+        self.relocations.push(Relocation {
+            section: *section,
+            offset: *address,
+            kind,
+            symbol: symbol.to_string(),
+            addend,
+            length: field.length,
+            bytes: field.bytes,
+            min: field.range.min,
+            max: field.range.max,
+            truncates: *truncates,
+            span: *span,
+            file: None,
+        });
+    }
+
+    /// Replace the opcode of the instruction at `address`.
+    fn set_op(&mut self, address: u32, section: SectionKind, op: Op) {
+        let Some(bytes) = self.image_mut(section).get_mut(address, 3) else {
+            return;
+        };
+        let mut instruction =
+            Instruction::decode(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]));
+        instruction.op = op;
+        let [_, a, b, c] = instruction.encode().to_be_bytes();
+        bytes.copy_from_slice(&[a, b, c]);
+    }
+
+    /// Bytes of `section`, which are parked while statements place bytes
+    /// in the other section.
+    fn image_mut(&mut self, section: SectionKind) -> &mut Image {
+        match section == self.section {
+            true => &mut self.image,
+            false => &mut self.parked,
+        }
+    }
+
+    /// Values of labels and constants as seen from `address` in `section`.
+    fn lookup(&self, address: u32, section: SectionKind) -> Resolver<'_> {
+        Resolver {
+            labels: &self.labels,
+            constants: &self.constants,
             address,
-            length,
-            shift,
-        } in &self.delta_references
-        {
-            if let Some(&value) = self.labels.get(identifier) {
-                let mask = 2_u32.pow(*length) - 1;
-                let u = value;
-                let u = u >> shift;
-                let u = u & mask;
-                let [a, b, c]: [u8; 3] = self.bytes[*address as usize..*address as usize + 3]
-                    .try_into()
-                    .unwrap();
-                let instruction = u32::from_be_bytes([0, a, b, c]);
-                let instruction = instruction | u;
-                let [_, a, b, c] = instruction.to_be_bytes();
-                self.bytes[*address as usize + 0] = a;
-                self.bytes[*address as usize + 1] = b;
-                self.bytes[*address as usize + 2] = c;
-            } else {
-                return Err(format!(
-                    "Unknown label: `{}` in\n{:#?}",
-                    identifier, self.labels
-                ));
-            }
-        }
-        Ok(self.bytes.clone())
+            section,
+            label_sections: self.object.then_some(&self.label_sections),
+            externs: &self.externs,
+            evaluating: vec![],
+        }
+    }
+
+    /// Error unless `value` lies in `range`.
+    fn check_range(&mut self, value: i64, range: ValueRange, span: Span) -> bool {
+        let fits = range.contains(value);
+        if !fits {
+            let ValueRange { min, max } = range;
+            let kind = ErrorKind::OutOfRange { value, min, max };
+            self.errors.push(AssembleError::new(kind, span));
+        }
+        fits
+    }
+
+    /// Warn unless `value` fits in `bits` bits as an unsigned number.
+    fn check_truncation(&mut self, value: i64, bits: u32, span: Span) {
+        if !ValueRange::unsigned(bits).contains(value) {
+            let kind = WarningKind::Truncated { value, bits };
+            self.warnings.push(Warning::new(kind, span));
+        }
     }
 
     fn parse_data(&mut self, pair: Pair<Rule>) {
         let mut pairs = pair.into_inner();
-        let instruction = pairs.next().unwrap().as_str();
+        let instruction = pairs.next().unwrap().as_str().to_lowercase();
         let value = pairs.next().unwrap();
-        match instruction {
+        match instruction.as_str() {
             "data" => self.parse_data_value(value, 1),
             "data2" => self.parse_data_value(value, 2),
             "data3" => self.parse_data_value(value, 3),
@@ -154,27 +845,255 @@ impl Assembler {
     fn parse_data_value(&mut self, pair: Pair<Rule>, bytes: u32) {
         match pair.as_rule() {
             Rule::DataValues => self.parse_data_values(pair.into_inner(), bytes),
-            Rule::String => self.parse_data_string(pair.as_str()),
+            Rule::String => {
+                let bytes = self.parse_string(pair);
+                self.image.extend(bytes);
+            }
             _ => unreachable!(),
         }
     }
 
     fn parse_data_values(&mut self, pairs: Pairs<Rule>, bytes: u32) {
+        let field = Field {
+            bytes,
+            length: bytes * 8,
+            shift: 0,
+            range: ValueRange::bits(bytes * 8),
+        };
         for pair in pairs {
-            let value = self.parse_value(pair, bytes, 0);
+            let value = self.parse_value(pair, field) as u32;
             let [_, a, b, c] = value.to_be_bytes();
             match bytes {
-                1 => self.bytes.push(c),
-                2 => self.bytes.extend([b, c]),
-                3 => self.bytes.extend([a, b, c]),
+                1 => self.image.extend([c]),
+                2 => self.image.extend([b, c]),
+                3 => self.image.extend([a, b, c]),
                 _ => unreachable!(),
             }
         }
     }
 
-    fn parse_data_string(&mut self, string: &str) {
-        let string = &string[1..string.len() - 1];
-        self.bytes.extend(string.as_bytes());
+    /// Place strings with `.ascii`, `.asciz` or `.pstring`.
+    fn parse_text(&mut self, pair: Pair<Rule>) {
+        let mut pairs = pair.into_inner();
+        let directive = pairs.next().unwrap().as_str().to_lowercase();
+        for pair in pairs {
+            let span = pair.as_span().into();
+            let bytes = self.parse_string(pair);
+            match directive.as_str() {
+                ".ascii" => self.image.extend(bytes),
+                ".asciz" => {
+                    self.image.extend(bytes);
+                    self.image.extend([0]);
+                }
+                ".pstring" => {
+                    let length = bytes.len() as i64;
+                    self.check_range(length, ValueRange::unsigned(8), span);
+                    self.image.extend([length as u8]);
+                    self.image.extend(bytes);
+                }
+                _ => unreachable!("Text: {}", directive),
+            }
+        }
+    }
+
+    /// Give a character, or each character of a string in turn, the byte
+    /// that strings after `.charmap` place for it.
+    fn parse_charmap(&mut self, pair: Pair<Rule>) {
+        let mut pairs = pair.into_inner().skip(1);
+        let characters = pairs.next().unwrap();
+        let text = characters.as_str().to_string();
+        let span: Span = characters.as_span().into();
+        // Escaped bytes and code points outside Unicode are no characters.
+        let characters: Vec<_> = match characters.as_rule() {
+            Rule::String => {
+                let Some(units) = self.unescape(characters) else {
+                    return;
+                };
+                let characters = units.into_iter().map(|unit| match unit {
+                    Unit::Character(character) => Some(character),
+                    Unit::Byte(_) => None,
+                });
+                characters.collect()
+            }
+            _ => {
+                let Some(value) = self.parse_layout_value(characters) else {
+                    return;
+                };
+                vec![u32::try_from(value).ok().and_then(char::from_u32)]
+            }
+        };
+        if characters.contains(&None) {
+            let kind = ErrorKind::InvalidCharacter(text);
+            self.errors.push(AssembleError::new(kind, span));
+            return;
+        }
+        let pair = pairs.next().unwrap();
+        let span = syntax::expression_span(&pair).into();
+        let Some(first) = self.parse_layout_value(pair) else {
+            return;
+        };
+        for (index, character) in characters.into_iter().flatten().enumerate() {
+            let byte = first + index as i64;
+            if !self.check_range(byte, ValueRange::unsigned(8), span) {
+                return;
+            }
+            self.charmap.insert(character, byte as u8);
+        }
+    }
+
+    /// Bytes of the string in `pair`, with its escapes decoded and its
+    /// characters mapped by `.charmap`.
+    fn parse_string(&mut self, pair: Pair<Rule>) -> Vec<u8> {
+        match self.unescape(pair) {
+            Some(units) => self.charmap.encode(&units),
+            None => vec![],
+        }
+    }
+
+    /// Characters and escaped bytes of the string or character literal in
+    /// `pair`, or `None` when it has an escape that is not a character.
+    fn unescape(&mut self, pair: Pair<Rule>) -> Option<Vec<Unit>> {
+        let text = pair.as_str();
+        // Both kinds of literal are quoted.
+        let contents = &text[1..text.len() - 1];
+        let (offset, length) = match text::unescape(contents) {
+            Ok(units) => return Some(units),
+            Err(escape) => escape,
+        };
+        let (line, column) = pair.line_col();
+        let column = column + 1 + contents[..offset].chars().count();
+        let escape = &contents[offset..offset + length];
+        let span = Span {
+            start: Location { line, column },
+            end: Location {
+                line,
+                column: column + escape.chars().count(),
+            },
+        };
+        let kind = ErrorKind::InvalidCharacter(escape.to_string());
+        self.errors.push(AssembleError::new(kind, span));
+        None
+    }
+
+    /// Move the address of the next byte or place bytes with `.org`,
+    /// `.align`, `.fill` or `.space`.
+    fn parse_placement(&mut self, pair: Pair<Rule>) {
+        let statement_span = pair.as_span().into();
+        let mut pairs = pair.into_inner();
+        let directive = pairs.next().unwrap().as_str().to_lowercase();
+        let pair = pairs.next().unwrap();
+        let span = syntax::expression_span(&pair).into();
+        let Some(value) = self.parse_layout_value(pair) else {
+            return;
+        };
+        let addresses = ValueRange::unsigned(24);
+        match directive.as_str() {
+            ".org" if self.object => {
+                let error = AssembleError::new(ErrorKind::FixedAddress, statement_span);
+                self.errors.push(error);
+            }
+            ".org" => {
+                if self.check_range(value, addresses, span) {
+                    self.image.set_address(value as u32, statement_span);
+                }
+            }
+            ".align" => {
+                let range = ValueRange {
+                    min: 1,
+                    max: addresses.max,
+                };
+                if self.check_range(value, range, span) {
+                    let alignment = value as u32;
+                    let largest = self.alignments.entry(self.section).or_insert(1);
+                    *largest = (*largest).max(alignment);
+                    let padding = (alignment - self.image.address() % alignment) % alignment;
+                    if self.check_room(padding, statement_span) {
+                        self.image
+                            .extend(std::iter::repeat(0).take(padding as usize));
+                    }
+                }
+            }
+            ".space" => {
+                if self.check_range(value, addresses, span)
+                    && self.check_room(value as u32, statement_span)
+                {
+                    self.image.extend(std::iter::repeat(0).take(value as usize));
+                }
+            }
+            ".fill" => {
+                let pair = pairs.next().unwrap();
+                let byte_span = syntax::expression_span(&pair).into();
+                let Some(byte) = self.parse_layout_value(pair) else {
+                    return;
+                };
+                if self.check_range(value, addresses, span)
+                    && self.check_range(byte, ValueRange::bits(8), byte_span)
+                    && self.check_room(value as u32, statement_span)
+                {
+                    let byte = byte as u8;
+                    self.image
+                        .extend(std::iter::repeat(byte).take(value as usize));
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Whether `length` more bytes fit in the address space, reporting an
+    /// error at `span` when they do not. Checked before placing many bytes
+    /// at once, rather than after.
+    fn check_room(&mut self, length: u32, span: Span) -> bool {
+        let fits = self.image.fits(length as u64);
+        if !fits {
+            let error = AssembleError::new(ErrorKind::OutsideAddressSpace, span);
+            self.errors.push(error);
+        }
+        fits
+    }
+
+    /// Insert the bytes of a file, from an optional offset and for an
+    /// optional length.
+    fn parse_include_binary(&mut self, pair: Pair<Rule>) {
+        let span: Span = pair.as_span().into();
+        let mut pairs = pair.into_inner().skip(1);
+        let name = pairs.next().unwrap().as_str();
+        let name = &name[1..name.len() - 1];
+        let bytes = match resolver::read(self.resolver.as_deref(), name) {
+            Ok(bytes) => bytes,
+            Err(kind) => {
+                self.errors.push(AssembleError::new(kind, span));
+                return;
+            }
+        };
+        let offset = match pairs.next() {
+            Some(pair) => match self.parse_bound(pair, bytes.len()) {
+                Some(offset) => offset,
+                None => return,
+            },
+            None => 0,
+        };
+        let length = match pairs.next() {
+            Some(pair) => match self.parse_bound(pair, bytes.len() - offset) {
+                Some(length) => length,
+                None => return,
+            },
+            None => bytes.len() - offset,
+        };
+        self.image
+            .extend(bytes[offset..offset + length].iter().copied());
+    }
+
+    /// Parse an offset into or a length of a file, which must not exceed
+    /// `max`.
+    fn parse_bound(&mut self, pair: Pair<Rule>, max: usize) -> Option<usize> {
+        let span = syntax::expression_span(&pair).into();
+        let value = self.parse_layout_value(pair)?;
+        let range = ValueRange {
+            min: 0,
+            max: max as i64,
+        };
+        self.check_range(value, range, span)
+            .then_some(value as usize)
     }
 
     fn parse_label_definition(&mut self, pair: Pair<Rule>) {
@@ -182,169 +1101,480 @@ impl Assembler {
         match label.as_rule() {
             Rule::GlobalLabel => self.add_global_label(label),
             Rule::LocalLabel => self.add_local_label(label),
+            Rule::NumericLabel | Rule::AnonymousLabel => self.add_anonymous_label(label),
             _ => unreachable!(),
         }
     }
 
+    /// Define the next `:` or numeric label, which only nearby references
+    /// reach, so it stays out of the symbol table.
+    fn add_anonymous_label(&mut self, pair: Pair<Rule>) {
+        let digits = pair.as_str().trim_end_matches(':');
+        let count = self.anonymous.entry(digits.to_string()).or_default();
+        let identifier = anonymous_label(digits, *count as i64);
+        *count += 1;
+        self.labels.insert(identifier.clone(), self.image.address());
+        self.label_sections.insert(identifier, self.section);
+    }
+
     fn add_global_label(&mut self, pair: Pair<Rule>) {
+        self.flush_pending_lets();
         let identifier = pair.as_str();
+        if self.constants.contains_key(identifier) {
+            let kind = ErrorKind::DuplicateSymbol(identifier.to_string());
+            self.errors.push(AssembleError::new(kind, pair.as_span()));
+        }
         self.scope = identifier.to_string();
+        self.aliases.clear();
+        self.definitions
+            .push((identifier.to_string(), pair.as_span().into()));
         self.labels
-            .insert(identifier.to_string(), self.bytes.len() as u32);
+            .insert(identifier.to_string(), self.image.address());
+        self.label_sections
+            .insert(identifier.to_string(), self.section);
     }
 
     fn add_local_label(&mut self, pair: Pair<Rule>) {
+        let Some(&scope_address) = self.labels.get(&self.scope) else {
+            let kind = ErrorKind::MissingScope(pair.as_str().to_string());
+            self.errors.push(AssembleError::new(kind, pair.as_span()));
+            return;
+        };
         let identifier = pair.as_str();
-        let identifier = format!("{}{}", self.scope, identifier.to_string());
-        self.labels.insert(identifier, self.bytes.len() as u32);
+        let identifier = format!("{}{}", self.scope, identifier);
+        if self.constants.contains_key(&identifier) {
+            let kind = ErrorKind::DuplicateSymbol(identifier.clone());
+            self.errors.push(AssembleError::new(kind, pair.as_span()));
+        }
+        self.definitions
+            .push((identifier.clone(), pair.as_span().into()));
+        self.labels.insert(identifier.clone(), self.image.address());
+        self.label_sections.insert(identifier, self.section);
         let relative_identifier = format!("{}~{}", self.scope, pair.as_str());
-        let relative_length = self.bytes.len() as u32 - self.labels.get(&self.scope).unwrap();
+        // `.org` may place a local label before its scope.
+        let relative_length = self.image.address().wrapping_sub(scope_address);
         self.labels.insert(relative_identifier, relative_length);
     }
 
     fn parse_instruction(&mut self, pair: Pair<Rule>) {
-        let mut pairs = pair.into_inner();
+        self.instruction += 1;
+        let mut pairs = pair.clone().into_inner();
         let op = pairs.next().unwrap();
         match op.as_rule() {
-            Rule::OpI => self.parse_immediate(op, pairs),
-            Rule::OpL => self.parse_let(op, pairs),
-            Rule::OpR => self.parse_register_instruction(op, pairs),
+            Rule::Mnemonic => match Op::parse(op.as_str()) {
+                Some((op, conditional))
+                    if self.operands_match(format_operands(op.format()), pairs.clone()) =>
+                {
+                    match op.format() {
+                        Format::I => self.parse_immediate(op, conditional, pairs),
+                        Format::L => self.parse_let(op, conditional, pairs),
+                        Format::R => self.parse_register_instruction(op, conditional, pairs),
+                    }
+                }
+                // An unknown mnemonic, or operands that do not fit the format.
+                _ => self.diagnose(pair),
+            },
+            // The grammar takes any name for a register, as it may be an
+            // alias.
+            Rule::PseudoOpL
+                if !self.operands_match(&[Rule::Register, Rule::Expression], pairs.clone()) =>
+            {
+                self.diagnose(pair)
+            }
+            Rule::PseudoOpRR
+                if !self.operands_match(&[Rule::Register, Rule::Register], pairs.clone()) =>
+            {
+                self.diagnose(pair)
+            }
+            Rule::PseudoOpR if !self.operands_match(&[Rule::Register], pairs.clone()) => {
+                self.diagnose(pair)
+            }
+            Rule::PseudoOpL
+            | Rule::PseudoOpV
+            | Rule::PseudoOpLabel
+            | Rule::PseudoOpRR
+            | Rule::PseudoOpR
+            | Rule::PseudoOp => self.parse_pseudo_instruction(op, pairs),
             _ => unreachable!(),
         }
     }
 
-    fn parse_immediate(&mut self, op: Pair<Rule>, mut pairs: Pairs<Rule>) {
+    /// Whether `operands` are the registers and values `expected`, where
+    /// an alias stands for a register and is no value.
+    fn operands_match(&self, expected: &[Rule], operands: Pairs<Rule>) -> bool {
+        operands.clone().count() == expected.len()
+            && operands.zip(expected).all(|(pair, &rule)| {
+                let alias = self.alias(&pair).is_some();
+                match (pair.as_rule(), rule) {
+                    (Rule::Register, Rule::Register) => true,
+                    (Rule::Expression, Rule::Register) => alias,
+                    (Rule::Expression, Rule::Expression) => !alias,
+                    _ => false,
+                }
+            })
+    }
+
+    /// Register that the operand in `pair` names with an alias.
+    fn alias(&self, pair: &Pair<Rule>) -> Option<u32> {
+        self.aliases
+            .get(syntax::expression_span(pair).as_str())
+            .copied()
+    }
+
+    /// Describe why the instruction in `pair` does not fit its operands.
+    fn diagnose(&mut self, pair: Pair<Rule>) {
+        let (line, column) = pair.line_col();
+        let error = syntax::diagnose(pair.as_str(), Location { line, column }, &self.aliases);
+        self.errors.push(error);
+    }
+
+    fn parse_immediate(&mut self, op: Op, conditional: bool, mut pairs: Pairs<Rule>) {
         use Op::*;
-        let (op, conditional) = match op.as_str().to_lowercase().as_str() {
-            "shri" => (Shri, false),
-            "shli" => (Shli, false),
-            "slessi" => (Slessi, false),
-            "load" => (Load, false),
-            "load2" => (Load2, false),
-            "load3" => (Load3, false),
-            "store" => (Store, false),
-            "store2" => (Store2, false),
-            "store3" => (Store3, false),
-            "ori" => (Ori, false),
-            "nori" => (Nori, false),
-            "andi" => (Andi, false),
-            "xori" => (Xori, false),
-            "lessi" => (Lessi, false),
-            "addi" => (Addi, false),
-            "subi" => (Subi, false),
-            "muli" => (Muli, false),
-            "cshri" => (Shri, true),
-            "cshli" => (Shli, true),
-            "cslessi" => (Slessi, true),
-            "cload" => (Load, true),
-            "cload2" => (Load2, true),
-            "cload3" => (Load3, true),
-            "cstore" => (Store, true),
-            "cstore2" => (Store2, true),
-            "cstore3" => (Store3, true),
-            "cori" => (Ori, true),
-            "cnori" => (Nori, true),
-            "candi" => (Andi, true),
-            "cxori" => (Xori, true),
-            "clessi" => (Lessi, true),
-            "caddi" => (Addi, true),
-            "csubi" => (Subi, true),
-            "cmuli" => (Muli, true),
-            _ => unreachable!(),
-        };
-        let conditional = conditional as u32;
-        let conditional = conditional << 23;
-        let opcode = op as u32;
-        let opcode = opcode << 18;
         let r = self.parse_register(pairs.next().unwrap());
-        let r = r << 12;
         let s = self.parse_register(pairs.next().unwrap());
-        let s = s << 6;
-        let u = self.parse_value(pairs.next().unwrap(), 6, 0);
-        // TODO: Yell if too big.
-        let u = u & 0o77;
-        let instruction = conditional | opcode | r | s | u;
-        let [_, a, b, c] = instruction.to_be_bytes();
-        self.bytes.extend([a, b, c]);
+        let value = pairs.next().unwrap();
+        let operand = syntax::expression_span(&value).as_str().to_string();
+        let span = syntax::expression_span(&value).into();
+        let expression = self.parse_expression(value);
+        let relative = matches!(expression, Expression::Distance(..));
+        // An `addi` or `subi` that moves `pc` by the distance to `~label` can
+        // be relaxed into a far branch.
+        let branch = relative
+            && matches!(op, Addi | Subi)
+            && r == REGISTER_PROGRAM_COUNTER
+            && s == REGISTER_PROGRAM_COUNTER;
+        if branch && self.relaxed.contains(&self.instruction) {
+            let Expression::Distance(label, span) = expression else {
+                unreachable!()
+            };
+            self.emit_far_branch(conditional, label, span);
+            return;
+        }
+        let range = match op.is_offset() {
+            true => ValueRange::signed(6),
+            false => ValueRange::unsigned(6),
+        };
+        let field = Field {
+            bytes: 3,
+            length: 6,
+            shift: 0,
+            range,
+        };
+        let u = self.evaluate_or_defer(expression, operand, field, span) as u32;
+        if relative {
+            let instruction = self.instruction;
+            let reference = self.references.last_mut().unwrap();
+            reference.direction = match op {
+                Addi => Direction::Forward,
+                Subi => Direction::Backward,
+                _ => Direction::Any,
+            };
+            reference.relaxation = branch.then_some(instruction);
+        }
+        self.emit_immediate(op, conditional, r, s, u);
     }
 
-    fn parse_let(&mut self, op: Pair<Rule>, mut pairs: Pairs<Rule>) {
+    /// Branch to `label` by loading its address into `at`, like `jump`.
+    ///
+    /// Unlike the `addi` or `subi` it replaces, a far branch leaves the
+    /// condition untouched.
+    fn emit_far_branch(&mut self, conditional: bool, label: String, span: Span) {
+        use Op::*;
+        for (op, shift) in [(Let, 0), (Lethi, 12)] {
+            let field = Field {
+                bytes: 3,
+                length: 12,
+                shift,
+                range: ValueRange::bits(24),
+            };
+            let expression = Expression::Symbol(label.clone(), span);
+            self.evaluate_or_defer(expression, label.clone(), field, span);
+            self.emit_let_value(op, conditional, REGISTER_TEMPORARY, 0);
+        }
+        self.emit_immediate(
+            Shri,
+            conditional,
+            REGISTER_PROGRAM_COUNTER,
+            REGISTER_TEMPORARY,
+            0,
+        );
+    }
+
+    fn parse_let(&mut self, op: Op, conditional: bool, mut pairs: Pairs<Rule>) {
+        let register = self.parse_register(pairs.next().unwrap());
+        self.emit_let(op, conditional, register, pairs.next().unwrap());
+    }
+
+    /// Emit a `let` or `lethi` of the value in `pair`.
+    fn emit_let(&mut self, op: Op, conditional: bool, register: u32, pair: Pair<Rule>) {
         use Op::*;
-        let (op, conditional) = match op.as_str().to_lowercase().as_str() {
-            "let" => (Let, false),
-            "lethi" => (Lethi, false),
-            "clet" => (Let, true),
-            "clethi" => (Lethi, true),
-            _ => unreachable!(),
-        };
-        let conditional = conditional as u32;
-        let conditional = conditional << 23;
-        let opcode = op as u32;
-        let opcode = opcode << 18;
-        let r = self.parse_register(pairs.next().unwrap());
-        let r = r << 12;
         let shift = match op {
             Lethi => 12,
             _ => 0,
         };
-        let u = self.parse_value(pairs.next().unwrap(), 12, shift);
-        // TODO: Yell if the number is too big to fit?
+        let field = Field {
+            bytes: 3,
+            length: 12,
+            shift,
+            range: ValueRange::bits(24),
+        };
+        let operand: String = syntax::expression_span(&pair)
+            .as_str()
+            .split_whitespace()
+            .collect();
+        let span = syntax::expression_span(&pair).into();
+        let references = self.references.len();
+        let value = self.parse_value(pair, field);
+        let deferred = self.references.len() > references;
+        match op {
+            Let if deferred || !ValueRange::unsigned(12).contains(value) => {
+                let reference = deferred.then_some(references);
+                self.defer_let(PendingLet {
+                    register,
+                    operand,
+                    value,
+                    reference,
+                    span,
+                });
+            }
+            Lethi => self.pair_let(register, &operand),
+            _ => {}
+        }
+        self.emit_let_value(op, conditional, register, value as u32);
+    }
+
+    /// Emit a `let` or `lethi` of `address` in the current section, which
+    /// objects relocate.
+    fn emit_let_address(
+        &mut self,
+        op: Op,
+        conditional: bool,
+        register: u32,
+        address: u32,
+        span: Span,
+    ) {
+        if !self.object {
+            return self.emit_let_value(op, conditional, register, address);
+        }
+        let field = Field {
+            bytes: 3,
+            length: 12,
+            shift: match op {
+                Op::Lethi => 12,
+                _ => 0,
+            },
+            range: ValueRange::bits(24),
+        };
+        let base = self.section.base();
+        let expression = Expression::Binary(
+            BinaryOperator::Add,
+            Box::new(Expression::Symbol(base.to_string(), span)),
+            Box::new(Expression::Number(address as i64)),
+            span,
+        );
+        self.evaluate_or_defer(expression, base.to_string(), field, span);
+        self.emit_let_value(op, conditional, register, 0);
+    }
+
+    /// Emit a `let` or `lethi` of a value known while parsing.
+    fn emit_let_value(&mut self, op: Op, conditional: bool, register: u32, value: u32) {
         let u = match op {
-            Let => u & 0o77_77,
-            Lethi => (u >> 12) & 0o77_77,
+            Op::Let => value,
+            Op::Lethi => value >> 12,
             _ => unreachable!(),
         };
-        let instruction = conditional | opcode | r | u;
-        let [_, a, b, c] = instruction.to_be_bytes();
-        self.bytes.extend([a, b, c]);
+        self.emit_immediate(op, conditional, register, 0, u);
     }
 
-    fn parse_register_instruction(&mut self, op: Pair<Rule>, mut pairs: Pairs<Rule>) {
-        use Op::*;
-        let (op, conditional) = match op.as_str().to_lowercase().as_str() {
-            "ashr" => (Ashr, false),
-            "rol" => (Rol, false),
-            "shr" => (Shr, false),
-            "shl" => (Shl, false),
-            "sless" => (Sless, false),
-            "or" => (Or, false),
-            "nor" => (Nor, false),
-            "and" => (And, false),
-            "xor" => (Xor, false),
-            "less" => (Less, false),
-            "add" => (Add, false),
-            "sub" => (Sub, false),
-            "mul" => (Mul, false),
-            "cashr" => (Ashr, true),
-            "crol" => (Rol, true),
-            "cshr" => (Shr, true),
-            "cshl" => (Shl, true),
-            "csless" => (Sless, true),
-            "cor" => (Or, true),
-            "cnor" => (Nor, true),
-            "cand" => (And, true),
-            "cxor" => (Xor, true),
-            "cless" => (Less, true),
-            "cadd" => (Add, true),
-            "csub" => (Sub, true),
-            "cmul" => (Mul, true),
-            _ => unreachable!(),
+    fn emit_immediate(&mut self, op: Op, conditional: bool, r: u32, s: u32, u: u32) {
+        let instruction = Instruction {
+            conditional,
+            op,
+            r,
+            s,
+            u,
         };
-        let conditional = conditional as u32;
-        let conditional = conditional << 23;
-        let opcode = op as u32;
-        let opcode = opcode << 18;
+        self.emit(instruction.encode());
+    }
+
+    fn emit_register_instruction(&mut self, op: Op, conditional: bool, r: u32, s: u32, t: u32) {
+        self.emit_immediate(op, conditional, r, s, t);
+    }
+
+    fn emit(&mut self, instruction: u32) {
+        let [_, a, b, c] = instruction.to_be_bytes();
+        self.image.extend([a, b, c]);
+    }
+
+    /// Wait for a `lethi` to complete a `let` with a value too large for it.
+    fn defer_let(&mut self, pending: PendingLet) {
+        let index = self
+            .pending_lets
+            .iter()
+            .position(|other| other.register == pending.register);
+        if let Some(index) = index {
+            let replaced = self.pending_lets.remove(index);
+            self.truncate_let(replaced);
+        }
+        self.pending_lets.push(pending);
+    }
+
+    /// Complete a pending `let` of `operand` into `register`, if any.
+    fn pair_let(&mut self, register: u32, operand: &str) {
+        self.pending_lets
+            .retain(|pending| pending.register != register || pending.operand != operand);
+    }
+
+    /// Treat every pending `let` as intentionally loading only 12 bits.
+    fn flush_pending_lets(&mut self) {
+        for pending in std::mem::take(&mut self.pending_lets) {
+            self.truncate_let(pending);
+        }
+    }
+
+    fn truncate_let(&mut self, pending: PendingLet) {
+        match pending.reference {
+            Some(index) => self.references[index].truncates = true,
+            None => self.check_truncation(pending.value, 12, pending.span),
+        }
+    }
+
+    fn parse_register_instruction(&mut self, op: Op, conditional: bool, mut pairs: Pairs<Rule>) {
         let r = self.parse_register(pairs.next().unwrap());
-        let r = r << 12;
         let s = self.parse_register(pairs.next().unwrap());
-        let s = s << 6;
         let t = self.parse_register(pairs.next().unwrap());
-        let instruction = conditional | opcode | r | s | t;
-        let [_, a, b, c] = instruction.to_be_bytes();
-        self.bytes.extend([a, b, c]);
+        self.emit_register_instruction(op, conditional, r, s, t);
+    }
+
+    /// Expand a pseudo-instruction into the instructions it stands for.
+    ///
+    /// Conditional variants only use instructions that leave the condition
+    /// untouched until their last instruction, so either all or none of
+    /// the expansion takes effect.
+    fn parse_pseudo_instruction(&mut self, op: Pair<Rule>, mut pairs: Pairs<Rule>) {
+        use Op::*;
+        let mnemonic = op.as_str().to_lowercase();
+        let conditional = mnemonic.starts_with('c') && mnemonic != "call";
+        let mnemonic = match conditional {
+            true => &mnemonic[1..],
+            false => &mnemonic[..],
+        };
+        match mnemonic {
+            "letall" => {
+                let register = self.parse_register(pairs.next().unwrap());
+                let pair = pairs.next().unwrap();
+                self.emit_let(Let, conditional, register, pair.clone());
+                self.emit_let(Lethi, conditional, register, pair);
+            }
+            "jump" => {
+                let pair = pairs.next().unwrap();
+                self.emit_let(Let, conditional, REGISTER_TEMPORARY, pair.clone());
+                self.emit_let(Lethi, conditional, REGISTER_TEMPORARY, pair);
+                self.emit_immediate(
+                    Shri,
+                    conditional,
+                    REGISTER_PROGRAM_COUNTER,
+                    REGISTER_TEMPORARY,
+                    0,
+                );
+            }
+            "call" => {
+                let pair = pairs.next().unwrap();
+                self.emit_let(Let, conditional, REGISTER_TEMPORARY, pair.clone());
+                self.emit_let(Lethi, conditional, REGISTER_TEMPORARY, pair);
+                if conditional {
+                    // `addi` would clear the condition, so load the return
+                    // address after the two `let`s and the jump instead.
+                    let address = self.image.address() + 9;
+                    let span = op.as_span().into();
+                    self.emit_let_address(Let, conditional, REGISTER_LINK, address, span);
+                    self.emit_let_address(Lethi, conditional, REGISTER_LINK, address, span);
+                } else {
+                    self.emit_immediate(
+                        Addi,
+                        conditional,
+                        REGISTER_LINK,
+                        REGISTER_PROGRAM_COUNTER,
+                        3,
+                    );
+                }
+                self.emit_immediate(
+                    Shri,
+                    conditional,
+                    REGISTER_PROGRAM_COUNTER,
+                    REGISTER_TEMPORARY,
+                    0,
+                );
+            }
+            "branch" => {
+                let label = pairs.next().unwrap().into_inner().next().unwrap();
+                let span = label.as_span().into();
+                let label = self.label_identifier(label);
+                if self.relaxed.contains(&self.instruction) {
+                    self.emit_far_branch(conditional, label, span);
+                    return;
+                }
+                let field = Field {
+                    bytes: 3,
+                    length: 6,
+                    shift: 0,
+                    range: ValueRange::unsigned(6),
+                };
+                let expression = Expression::Distance(label.clone(), span);
+                self.evaluate_or_defer(expression, label, field, span);
+                let instruction = self.instruction;
+                let reference = self.references.last_mut().unwrap();
+                reference.direction = Direction::Either;
+                reference.relaxation = Some(instruction);
+                self.emit_immediate(
+                    Addi,
+                    conditional,
+                    REGISTER_PROGRAM_COUNTER,
+                    REGISTER_PROGRAM_COUNTER,
+                    0,
+                );
+            }
+            "ret" => {
+                self.emit_immediate(
+                    Shri,
+                    conditional,
+                    REGISTER_PROGRAM_COUNTER,
+                    REGISTER_LINK,
+                    0,
+                );
+            }
+            "nop" => self.emit_immediate(Shri, conditional, REGISTER_GLOBAL, REGISTER_GLOBAL, 0),
+            "mov" => {
+                let r = self.parse_register(pairs.next().unwrap());
+                let s = self.parse_register(pairs.next().unwrap());
+                self.emit_immediate(Shri, conditional, r, s, 0);
+            }
+            "push" => {
+                let register = self.parse_register(pairs.next().unwrap());
+                self.emit_immediate(
+                    Store3,
+                    conditional,
+                    REGISTER_GLOBAL,
+                    register,
+                    (-3_i32) as u32,
+                );
+                self.emit_immediate(Subi, conditional, REGISTER_GLOBAL, REGISTER_GLOBAL, 3);
+            }
+            "pop" => {
+                let register = self.parse_register(pairs.next().unwrap());
+                self.emit_immediate(Load3, conditional, register, REGISTER_GLOBAL, 0);
+                self.emit_immediate(Addi, conditional, REGISTER_GLOBAL, REGISTER_GLOBAL, 3);
+            }
+            _ => unreachable!("Pseudo-instruction: {}", mnemonic),
+        }
     }
 
+    /// Number of the register in `pair`, which is a register or, as the
+    /// operands are checked beforehand, an alias.
     fn parse_register(&mut self, pair: Pair<Rule>) -> u32 {
+        if pair.as_rule() != Rule::Register {
+            return self.alias(&pair).unwrap();
+        }
         match pair.as_str().to_lowercase().as_str() {
             "r0" | "sp" => 0x00,
             "r1" => 0x01,
@@ -406,147 +1636,634 @@ impl Assembler {
             "r39" => 0x39,
             "r3a" => 0x3A,
             "r3b" => 0x3B,
-            "r3c" => 0x3C,
-            "r3d" => 0x3D,
+            "r3c" | "lr" => 0x3C,
+            "r3d" | "at" => 0x3D,
             "r3e" | "ir" => 0x3E,
             "r3f" | "pc" => 0x3F,
             register => unreachable!("Register: {}", register),
         }
     }
 
-    fn parse_value(&mut self, pair: Pair<Rule>, length: u32, shift: u32) -> u32 {
-        match pair.as_rule() {
-            Rule::Number => self.parse_number(pair.into_inner().next().unwrap()),
-            Rule::SignedNumber => self.parse_signed_number(pair.into_inner().next().unwrap()),
-            Rule::LabelReference => {
-                self.parse_label_reference(pair.into_inner().next().unwrap(), length, shift);
+    /// Parse the expression in `pair`, evaluating it right away when it is
+    /// constant and patching it in after layout otherwise.
+    fn parse_value(&mut self, pair: Pair<Rule>, field: Field) -> i64 {
+        let operand = syntax::expression_span(&pair).as_str().to_string();
+        let span = syntax::expression_span(&pair).into();
+        let expression = self.parse_expression(pair);
+        self.evaluate_or_defer(expression, operand, field, span)
+    }
+
+    /// Evaluate the expression in `pair` right away, as it decides the
+    /// layout and only labels before it have an address.
+    fn parse_layout_value(&mut self, pair: Pair<Rule>) -> Option<i64> {
+        let expression = self.parse_expression(pair);
+        let mut resolver = self.lookup(self.image.address(), self.section);
+        match expression.evaluate(&mut resolver) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+
+    fn evaluate_or_defer(
+        &mut self,
+        expression: Expression,
+        operand: String,
+        field: Field,
+        span: Span,
+    ) -> i64 {
+        if !expression.is_constant() {
+            // Point at the label itself when there is nothing else.
+            let span = match &expression {
+                Expression::Symbol(_, span) | Expression::Distance(_, span) => *span,
+                _ => span,
+            };
+            self.references.push(Reference {
+                expression,
+                operand,
+                address: self.image.address(),
+                field,
+                truncates: false,
+                relaxation: None,
+                direction: Direction::Any,
+                section: self.section,
+                span,
+            });
+            return 0;
+        }
+        let mut resolver = self.lookup(0, self.section);
+        match expression.evaluate(&mut resolver) {
+            Ok(value) => {
+                self.check_range(value, field.range, span);
+                value
+            }
+            Err(error) => {
+                self.errors.push(error);
                 0
             }
-            _ => todo!("Value: {} ({:?})", pair.as_str(), pair.as_rule()),
         }
     }
 
-    fn parse_number(&mut self, pair: Pair<Rule>) -> u32 {
-        let string = pair.as_str().replace("_", "");
+    fn parse_expression(&mut self, pair: Pair<Rule>) -> Expression {
+        operators()
+            .map_primary(|primary| self.parse_primary(primary))
+            .map_prefix(|operator, operand| {
+                Expression::Unary(unary_operator(operator.as_rule()), Box::new(operand))
+            })
+            .map_infix(|left, operator, right| {
+                let span = operator.as_span().into();
+                let operator = binary_operator(operator.as_rule());
+                Expression::Binary(operator, Box::new(left), Box::new(right), span)
+            })
+            .parse(pair.into_inner())
+    }
+
+    fn parse_primary(&mut self, pair: Pair<Rule>) -> Expression {
         match pair.as_rule() {
-            Rule::Binary => u32::from_str_radix(&string[2..], 0b10).unwrap(),
-            Rule::Octal => u32::from_str_radix(&string[2..], 0o10).unwrap(),
-            Rule::Decimal => u32::from_str_radix(&string, 10).unwrap(),
-            Rule::Hexadecimal => u32::from_str_radix(&string[2..], 0x10).unwrap(),
-            _ => unreachable!("Number: {}", pair.as_str()),
+            Rule::Expression => self.parse_expression(pair),
+            Rule::SizeOf => {
+                let name = pair.clone().into_inner().next().unwrap().as_str();
+                Expression::Symbol(format!("sizeof({})", name), pair.as_span().into())
+            }
+            Rule::Function => {
+                let mut pairs = pair.into_inner();
+                let operator = match pairs.next().unwrap().as_str().to_lowercase().as_str() {
+                    "hi" => UnaryOperator::High,
+                    "lo" => UnaryOperator::Low,
+                    function => unreachable!("Function: {}", function),
+                };
+                let operand = self.parse_expression(pairs.next().unwrap());
+                Expression::Unary(operator, Box::new(operand))
+            }
+            Rule::CharacterLiteral => match self.unescape(pair).as_deref() {
+                Some(&[Unit::Character(character)]) => Expression::Number(character as i64),
+                Some(&[Unit::Byte(byte)]) => Expression::Number(byte as i64),
+                _ => Expression::Number(0),
+            },
+            Rule::Number => {
+                Expression::Number(self.parse_number(pair.into_inner().next().unwrap()))
+            }
+            Rule::LabelReference => self.parse_label_reference(pair.into_inner().next().unwrap()),
+            _ => unreachable!("Primary: {} ({:?})", pair.as_str(), pair.as_rule()),
         }
     }
 
-    fn parse_signed_number(&mut self, pair: Pair<Rule>) -> u32 {
-        match pair.as_rule() {
-            Rule::Binary => -i32::from_str_radix(&pair.as_str()[2..], 0b10).unwrap() as u32,
-            Rule::Octal => -i32::from_str_radix(&pair.as_str()[2..], 0o10).unwrap() as u32,
-            Rule::Decimal => -i32::from_str_radix(pair.as_str(), 10).unwrap() as u32,
-            Rule::Hexadecimal => -i32::from_str_radix(&pair.as_str()[2..], 0x10).unwrap() as u32,
+    fn parse_number(&mut self, pair: Pair<Rule>) -> i64 {
+        if pair.as_rule() == Rule::FixedPoint {
+            return self.parse_fixed_point(pair);
+        }
+        let string = pair.as_str().replace('_', "");
+        let result = match pair.as_rule() {
+            Rule::Binary => u32::from_str_radix(&string[2..], 0b10),
+            Rule::Octal => u32::from_str_radix(&string[2..], 0o10),
+            Rule::Decimal => string.parse(),
+            Rule::Hexadecimal => u32::from_str_radix(&string[2..], 0x10),
             _ => unreachable!("Number: {}", pair.as_str()),
+        };
+        match result {
+            Ok(number) => number as i64,
+            Err(error) => {
+                let number = pair.as_str().to_string();
+                let kind = match error.kind() {
+                    std::num::IntErrorKind::PosOverflow => ErrorKind::NumberTooLarge(number),
+                    _ => ErrorKind::InvalidNumber(number),
+                };
+                self.errors.push(AssembleError::new(kind, pair.as_span()));
+                0
+            }
+        }
+    }
+
+    /// Value of a fixed-point literal like `1.5q12` as an integer with as
+    /// many fraction bits as its `q`, or the `.fixed` before it, gives.
+    fn parse_fixed_point(&mut self, pair: Pair<Rule>) -> i64 {
+        let literal = pair.as_str().to_string();
+        let text = literal.replace('_', "").to_lowercase();
+        let (number, bits) = match text.split_once('q') {
+            Some((number, bits)) => (number, bits.parse().ok()),
+            None => (text.as_str(), self.fixed),
+        };
+        let error = |kind| AssembleError::new(kind, pair.as_span());
+        let Some(bits) = bits else {
+            self.errors
+                .push(error(ErrorKind::MissingFixedFormat(literal)));
+            return 0;
+        };
+        if bits > 24 {
+            self.errors.push(error(ErrorKind::InvalidNumber(literal)));
+            return 0;
+        }
+        // The digits without the point, over the power of ten the point
+        // stands for.
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let digits: Option<u128> = format!("{}{}", whole, fraction).parse().ok();
+        let numerator = digits.and_then(|digits| digits.checked_mul(1 << bits));
+        let denominator = 10_u128.checked_pow(fraction.len() as u32);
+        let Some((numerator, denominator)) = numerator.zip(denominator) else {
+            self.errors.push(error(ErrorKind::NumberTooLarge(literal)));
+            return 0;
+        };
+        let value = (numerator + denominator / 2) / denominator;
+        if value > u32::MAX as u128 {
+            self.errors.push(error(ErrorKind::NumberTooLarge(literal)));
+            return 0;
+        }
+        let value = value as i64;
+        if numerator % denominator != 0 {
+            let kind = WarningKind::Rounded {
+                literal,
+                value,
+                bits,
+            };
+            self.warnings.push(Warning::new(kind, pair.as_span()));
         }
+        value
     }
 
-    fn parse_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
+    /// Give the fixed-point literals after `.fixed` without a `q` suffix
+    /// its number of fraction bits.
+    fn parse_fixed_format(&mut self, pair: Pair<Rule>) {
+        let pair = pair.into_inner().nth(1).unwrap();
+        let span = syntax::expression_span(&pair).into();
+        let Some(bits) = self.parse_layout_value(pair) else {
+            return;
+        };
+        if self.check_range(bits, ValueRange { min: 0, max: 24 }, span) {
+            self.fixed = Some(bits as u32);
+        }
+    }
+
+    fn parse_label_reference(&mut self, pair: Pair<Rule>) -> Expression {
+        let span = pair.as_span().into();
         match pair.as_rule() {
-            Rule::RelativeLabelReference => self.parse_relative_label_reference(
-                pair.into_inner().next().unwrap(),
-                length,
-                shift,
-            ),
-            Rule::RelativeLabelOffset => self.parse_relative_label_offset(pair, length, shift),
-            Rule::AbsoluteLabelReference => self.parse_absolute_label_reference(
-                pair.into_inner().next().unwrap(),
-                length,
-                shift,
-            ),
+            Rule::RelativeLabelReference => {
+                let label = pair.into_inner().next().unwrap();
+                let span = label.as_span().into();
+                Expression::Distance(self.label_identifier(label), span)
+            }
+            Rule::RelativeLabelOffset => {
+                // `scope~.local` refers to both labels.
+                let (scope, local) = pair.as_str().split_once('~').unwrap();
+                self.referenced.insert(scope.to_string());
+                self.referenced.insert(format!("{}{}", scope, local));
+                Expression::Symbol(pair.as_str().to_string(), span)
+            }
+            Rule::AbsoluteLabelReference => {
+                let identifier = self.label_identifier(pair.into_inner().next().unwrap());
+                // A constant from `.set` keeps the value it has at this point.
+                match self.constants.get(&identifier) {
+                    Some(constant) if constant.redefinable => constant.expression.clone(),
+                    _ => Expression::Symbol(identifier, span),
+                }
+            }
             _ => unreachable!("{:?}", pair.as_rule()),
         }
     }
 
-    fn parse_relative_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        match pair.as_rule() {
-            Rule::ScopedLabel => self.parse_relative_global_label_reference(pair, length, shift),
-            Rule::LocalLabel => self.parse_relative_local_label_reference(pair, length, shift),
-            _ => unreachable!(),
+    /// Full identifier of a label, prefixing local labels with their scope.
+    /// The label is noted as referred to, for the lints.
+    fn label_identifier(&mut self, pair: Pair<Rule>) -> String {
+        let identifier = match pair.as_rule() {
+            Rule::ScopedLabel => pair.as_str().to_string(),
+            Rule::LocalLabel => format!("{}{}", self.scope, pair.as_str()),
+            Rule::AnonymousReference => self.anonymous_identifier(pair),
+            _ => unreachable!("{:?}", pair.as_rule()),
+        };
+        self.referenced
+            .insert(expression::referred(&identifier).to_string());
+        identifier
+    }
+
+    /// Identifier of a reference like `:-` or `1f`: its text followed by
+    /// the anonymous label it reaches, counting from the labels defined so
+    /// far.
+    fn anonymous_identifier(&self, pair: Pair<Rule>) -> String {
+        let text = pair.as_str();
+        let (digits, direction) = match text.strip_prefix(':') {
+            Some(direction) => ("", direction),
+            None => text.split_at(text.len() - 1),
+        };
+        let defined = self.anonymous.get(digits).copied().unwrap_or(0) as i64;
+        let skipped = direction.len() as i64;
+        let ordinal = match direction {
+            "b" => defined - 1,
+            "f" => defined,
+            _ if direction.starts_with('-') => defined - skipped,
+            _ => defined + skipped - 1,
+        };
+        format!("{} {}", text, anonymous_label(digits, ordinal))
+    }
+
+    /// Switch to the section that `.code` or `.data` names. Only objects
+    /// keep their sections apart.
+    fn parse_section(&mut self, pair: Pair<Rule>) {
+        let section = match pair.as_str().to_lowercase().as_str() {
+            ".code" => SectionKind::Code,
+            ".data" => SectionKind::Data,
+            directive => unreachable!("Section: {}", directive),
+        };
+        if self.object && section != self.section {
+            std::mem::swap(&mut self.image, &mut self.parked);
+            self.section = section;
         }
     }
 
-    fn parse_relative_label_offset(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        let identifier = pair.as_str().to_string();
-        let address = self.bytes.len() as u32;
-        self.delta_references.push(LabelReference {
-            identifier,
-            address,
-            length,
-            shift,
-        })
+    /// Name a register with `.alias`, or forget names with `.unalias`.
+    fn parse_alias(&mut self, pair: Pair<Rule>) {
+        let mut pairs = pair.into_inner();
+        let directive = pairs.next().unwrap().as_str().to_lowercase();
+        match directive.as_str() {
+            ".alias" => {
+                let name = pairs.next().unwrap();
+                let register = self.parse_register(pairs.next().unwrap());
+                let other = self
+                    .aliases
+                    .iter()
+                    .filter(|&(other, &named)| named == register && other != name.as_str())
+                    .map(|(other, _)| other)
+                    .min();
+                if let Some(other) = other {
+                    let kind = WarningKind::SharedRegister {
+                        alias: name.as_str().to_string(),
+                        other: other.clone(),
+                    };
+                    self.warnings.push(Warning::new(kind, name.as_span()));
+                }
+                self.aliases.insert(name.as_str().to_string(), register);
+            }
+            ".unalias" => {
+                for name in pairs {
+                    if self.aliases.remove(name.as_str()).is_none() {
+                        let kind = ErrorKind::UnknownAlias(name.as_str().to_string());
+                        self.errors.push(AssembleError::new(kind, name.as_span()));
+                    }
+                }
+            }
+            _ => unreachable!("Alias: {}", directive),
+        }
     }
 
-    fn parse_absolute_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        match pair.as_rule() {
-            Rule::ScopedLabel => self.parse_absolute_global_label_reference(pair, length, shift),
-            Rule::LocalLabel => self.parse_absolute_local_label_reference(pair, length, shift),
-            _ => unreachable!("{:?}", pair.as_rule()),
+    /// Declare names exported with `.global` or imported with `.extern`.
+    fn parse_linkage(&mut self, pair: Pair<Rule>) {
+        let mut pairs = pair.into_inner();
+        let directive = pairs.next().unwrap().as_str().to_lowercase();
+        let names = pairs.map(|pair| (pair.as_str().to_string(), pair.as_span().into()));
+        match directive.as_str() {
+            ".global" => self.globals.extend(names),
+            ".extern" => self.externs.extend(names),
+            _ => unreachable!("Linkage: {}", directive),
         }
     }
 
-    fn parse_relative_global_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        let identifier = pair.as_str().to_string();
-        let address = self.bytes.len() as u32;
-        self.relative_references.push(LabelReference {
-            identifier,
-            address,
-            length,
-            shift,
-        })
+    /// Start laying out a `.struct` or `.enum`, or end it and give a
+    /// structure its size.
+    fn parse_layout(&mut self, pair: Pair<Rule>) {
+        let span = pair.as_span().into();
+        let mut pairs = pair.into_inner();
+        let directive = pairs.next().unwrap().as_str().to_lowercase();
+        match directive.as_str() {
+            ".struct" | ".enum" => {
+                if let Some(layout) = &self.layout {
+                    let kind = ErrorKind::Syntax(format!(
+                        "`{}` cannot be inside `{}`",
+                        directive, layout.directive
+                    ));
+                    self.errors.push(AssembleError::new(kind, span));
+                    return;
+                }
+                self.layout = Some(Layout {
+                    directive,
+                    name: pairs.next().unwrap().as_str().to_string(),
+                    next: 0,
+                    span,
+                });
+            }
+            end => {
+                let start = end.replacen("end", "", 1);
+                let Some(layout) = self.layout.take_if(|layout| layout.directive == start) else {
+                    let kind = ErrorKind::Syntax(format!("`{}` without `{}`", end, start));
+                    self.errors.push(AssembleError::new(kind, span));
+                    return;
+                };
+                if layout.directive == ".struct" {
+                    let name = format!("sizeof({})", layout.name);
+                    self.define_layout_constant(name, layout.next, span);
+                }
+            }
+        }
     }
 
-    fn parse_absolute_global_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        let identifier = pair.as_str().to_string();
-        let address = self.bytes.len() as u32;
-        self.absolute_references.push(LabelReference {
-            identifier,
-            address,
-            length,
-            shift,
-        })
+    /// Define a field of the `.struct`, or a name of the `.enum`, being laid
+    /// out.
+    fn parse_layout_entry(&mut self, pair: Pair<Rule>) {
+        let span: Span = pair.as_span().into();
+        let layout = self.layout.as_ref().unwrap();
+        let (directive, name, next) = (layout.directive.as_str(), &layout.name, layout.next);
+        let mut pairs = pair.clone().into_inner();
+        let next = match (directive, pair.as_rule()) {
+            (".struct", Rule::LabelDefinition)
+                if pairs.peek().unwrap().as_rule() == Rule::GlobalLabel =>
+            {
+                let field = format!("{}.{}", name, pairs.next().unwrap().as_str());
+                self.define_layout_constant(field, next, span);
+                next
+            }
+            (".struct", Rule::Reserve) => {
+                let size = match pairs.next().unwrap().as_str().to_lowercase().as_str() {
+                    ".res2" => 2,
+                    ".res3" => 3,
+                    _ => 1,
+                };
+                let pair = pairs.next().unwrap();
+                let count_span = syntax::expression_span(&pair).into();
+                let Some(count) = self.parse_layout_value(pair) else {
+                    return;
+                };
+                if !self.check_range(count, ValueRange::unsigned(24), count_span) {
+                    return;
+                }
+                next + size * count
+            }
+            (".enum", Rule::Member) => {
+                let member = format!("{}.{}", name, pairs.next().unwrap().as_str());
+                let value = match pairs.next() {
+                    Some(pair) => match self.parse_layout_value(pair) {
+                        Some(value) => value,
+                        None => return,
+                    },
+                    None => next,
+                };
+                self.define_layout_constant(member, value, span);
+                value + 1
+            }
+            _ => {
+                let message = match directive {
+                    ".struct" => "only labels and `.res` can be inside `.struct`",
+                    _ => "only names can be inside `.enum`",
+                };
+                let kind = ErrorKind::Syntax(message.to_string());
+                self.errors.push(AssembleError::new(kind, span));
+                return;
+            }
+        };
+        self.layout.as_mut().unwrap().next = next;
     }
 
-    fn parse_relative_local_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        let identifier = pair.as_str();
-        let identifier = format!("{}{}", self.scope, identifier);
-        let address = self.bytes.len() as u32;
-        self.relative_references.push(LabelReference {
-            identifier,
-            address,
-            length,
-            shift,
-        })
+    fn define_layout_constant(&mut self, name: String, value: i64, span: Span) {
+        if self.constants.contains_key(&name) || self.labels.contains_key(&name) {
+            let kind = ErrorKind::DuplicateSymbol(name);
+            self.errors.push(AssembleError::new(kind, span));
+            return;
+        }
+        let constant = Constant {
+            expression: Expression::Number(value),
+            address: self.image.address(),
+            section: self.section,
+            redefinable: false,
+        };
+        self.constants.insert(name, constant);
     }
 
-    fn parse_absolute_local_label_reference(&mut self, pair: Pair<Rule>, length: u32, shift: u32) {
-        let identifier = pair.as_str();
-        let identifier = format!("{}{}", self.scope, identifier);
-        let address = self.bytes.len() as u32;
-        self.absolute_references.push(LabelReference {
-            identifier,
-            address,
-            length,
-            shift,
-        })
+    fn parse_constant(&mut self, pair: Pair<Rule>) {
+        let mut pairs = pair.into_inner();
+        let redefinable = pairs.next().unwrap().as_str().to_lowercase() == ".set";
+        let name = pairs.next().unwrap();
+        let expression = self.parse_expression(pairs.next().unwrap());
+        let identifier = name.as_str().to_string();
+        let redefines = match self.constants.get(&identifier) {
+            Some(constant) => !(redefinable && constant.redefinable),
+            None => self.labels.contains_key(&identifier),
+        };
+        if redefines {
+            let kind = ErrorKind::DuplicateSymbol(identifier);
+            self.errors.push(AssembleError::new(kind, name.as_span()));
+            return;
+        }
+        let constant = Constant {
+            expression,
+            address: self.image.address(),
+            section: self.section,
+            redefinable,
+        };
+        self.constants.insert(identifier, constant);
+    }
+}
+
+/// Precedence of the operators in expressions, as the grammar describes.
+fn operators() -> PrattParser<Rule> {
+    PrattParser::new()
+        .op(Operator::infix(Rule::BitOr, Assoc::Left))
+        .op(Operator::infix(Rule::BitXor, Assoc::Left))
+        .op(Operator::infix(Rule::BitAnd, Assoc::Left))
+        .op(Operator::infix(Rule::ShiftLeft, Assoc::Left)
+            | Operator::infix(Rule::ShiftRight, Assoc::Left))
+        .op(Operator::infix(Rule::Add, Assoc::Left) | Operator::infix(Rule::Subtract, Assoc::Left))
+        .op(Operator::infix(Rule::Multiply, Assoc::Left)
+            | Operator::infix(Rule::Divide, Assoc::Left)
+            | Operator::infix(Rule::Remainder, Assoc::Left))
+        .op(Operator::prefix(Rule::Negate) | Operator::prefix(Rule::BitNot))
+}
+
+fn unary_operator(rule: Rule) -> UnaryOperator {
+    match rule {
+        Rule::Negate => UnaryOperator::Negate,
+        Rule::BitNot => UnaryOperator::BitNot,
+        _ => unreachable!("Prefix: {:?}", rule),
+    }
+}
+
+fn binary_operator(rule: Rule) -> BinaryOperator {
+    match rule {
+        Rule::ShiftLeft => BinaryOperator::ShiftLeft,
+        Rule::ShiftRight => BinaryOperator::ShiftRight,
+        Rule::BitOr => BinaryOperator::BitOr,
+        Rule::BitXor => BinaryOperator::BitXor,
+        Rule::BitAnd => BinaryOperator::BitAnd,
+        Rule::Add => BinaryOperator::Add,
+        Rule::Subtract => BinaryOperator::Subtract,
+        Rule::Multiply => BinaryOperator::Multiply,
+        Rule::Divide => BinaryOperator::Divide,
+        Rule::Remainder => BinaryOperator::Remainder,
+        _ => unreachable!("Infix: {:?}", rule),
+    }
+}
+
+/// Registers and values that instructions of `format` take.
+fn format_operands(format: Format) -> &'static [Rule] {
+    match format {
+        Format::L => &[Rule::Register, Rule::Expression],
+        Format::I => &[Rule::Register, Rule::Register, Rule::Expression],
+        Format::R => &[Rule::Register, Rule::Register, Rule::Register],
+    }
+}
+
+/// Name of the `ordinal`th anonymous label with `digits`, which no written
+/// label can have.
+fn anonymous_label(digits: &str, ordinal: i64) -> String {
+    format!("{}:{}", digits, ordinal)
+}
+
+/// Looks up labels and constants as seen from the instruction at `address`.
+struct Resolver<'a> {
+    labels: &'a HashMap<String, u32>,
+    constants: &'a HashMap<String, Constant>,
+    address: u32,
+    section: SectionKind,
+    /// Section of each label of an object, whose labels are offsets into
+    /// their section, or `None` when assembling a program.
+    label_sections: Option<&'a HashMap<String, SectionKind>>,
+    externs: &'a [(String, Span)],
+    /// Constants being evaluated, to catch circular definitions.
+    evaluating: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// Address of the label `name`, or of the symbol an object imports as
+    /// `name`.
+    fn address(&self, name: &str) -> Option<Value> {
+        let name = expression::referred(name);
+        let Some(sections) = self.label_sections else {
+            return self
+                .labels
+                .get(name)
+                .map(|&address| Value::from(address as i64));
+        };
+        if let Some(&address) = self.labels.get(name) {
+            let value = match name.split_once('~') {
+                // A distance between labels, which depends on where their
+                // sections go when they lie in different ones.
+                Some((scope, local)) => {
+                    let local = format!("{}{}", scope, local);
+                    Value::address(sections[&local].base(), address as i64)
+                        .add(Value::address(sections[scope].base(), 0).scale(-1))
+                }
+                None => Value::address(sections[name].base(), address as i64),
+            };
+            return Some(value);
+        }
+        // Pseudo-instructions load addresses in their own section.
+        if SectionKind::ALL.iter().any(|kind| kind.base() == name) {
+            return Some(Value::address(name, 0));
+        }
+        self.externs
+            .iter()
+            .any(|(extern_name, _)| extern_name == name)
+            .then(|| Value::address(name, 0))
+    }
+}
+
+impl Symbols for Resolver<'_> {
+    fn value(&mut self, name: &str, span: Span) -> Result<Value, AssembleError> {
+        if let Some(value) = self.address(name) {
+            return Ok(value);
+        }
+        let Some(constant) = self.constants.get(name) else {
+            let kind = ErrorKind::UnknownLabel(expression::written(name).to_string());
+            return Err(AssembleError::new(kind, span));
+        };
+        if self.evaluating.iter().any(|evaluating| evaluating == name) {
+            let kind = ErrorKind::CircularConstant(name.to_string());
+            return Err(AssembleError::new(kind, span));
+        }
+        let address = std::mem::replace(&mut self.address, constant.address);
+        let section = std::mem::replace(&mut self.section, constant.section);
+        self.evaluating.push(name.to_string());
+        let value = constant.expression.relocate(self);
+        self.evaluating.pop();
+        self.address = address;
+        self.section = section;
+        value
+    }
+
+    fn distance(&mut self, name: &str, span: Span) -> Result<Value, AssembleError> {
+        let Some(target) = self.address(name) else {
+            let kind = ErrorKind::UnknownLabel(expression::written(name).to_string());
+            return Err(AssembleError::new(kind, span));
+        };
+        let end = self.address as i64 + 3;
+        let end = match self.label_sections {
+            Some(_) => Value::address(self.section.base(), end),
+            None => Value::from(end),
+        };
+        Ok(target.add(end.scale(-1)))
     }
 }
 
 impl Default for Assembler {
     fn default() -> Self {
         Self {
-            bytes: Default::default(),
+            image: Default::default(),
             labels: Default::default(),
+            constants: Default::default(),
             scope: Default::default(),
-            relative_references: Default::default(),
-            absolute_references: Default::default(),
-            delta_references: Default::default(),
+            references: Default::default(),
+            pending_lets: Default::default(),
+            errors: Default::default(),
+            warnings: Default::default(),
+            instruction: Default::default(),
+            relaxed: Default::default(),
+            resolver: Default::default(),
+            listing: Default::default(),
+            statements: Default::default(),
+            object: Default::default(),
+            section: SectionKind::Code,
+            parked: Default::default(),
+            alignments: Default::default(),
+            label_sections: Default::default(),
+            globals: Default::default(),
+            externs: Default::default(),
+            relocations: Default::default(),
+            defines: Default::default(),
+            charmap: Default::default(),
+            anonymous: Default::default(),
+            aliases: Default::default(),
+            layout: Default::default(),
+            fixed: Default::default(),
+            lints: Default::default(),
+            definitions: Default::default(),
+            referenced: Default::default(),
         }
     }
 }
@@ -0,0 +1,264 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    image::{Image, ADDRESS_SPACE},
+    object::RelocationKind,
+    AssembleError, AssembleErrors, Assembly, ErrorKind, Object, SectionKind, Span, Symbol,
+    SymbolKind, Warning, WarningKind,
+};
+
+/// Lays out relocatable objects into one ROM: the code of every object in
+/// the order they were added, then their data, each section at a multiple
+/// of its alignment.
+#[derive(Default)]
+pub struct Linker {
+    objects: Vec<(String, Object)>,
+    origin: u32,
+    data: Option<u32>,
+}
+
+/// Where the linker placed a section of an object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    pub object: String,
+    pub section: SectionKind,
+    pub address: u32,
+    pub length: u32,
+}
+
+/// Output of a successful link.
+pub struct Linked {
+    /// The ROM, with every label of every object at its final address.
+    pub assembly: Assembly,
+    /// Where each section went, in order of address.
+    pub placements: Vec<Placement>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the ROM at `address` instead of 0.
+    pub fn with_origin(mut self, address: u32) -> Self {
+        self.origin = address;
+        self
+    }
+
+    /// Place the data sections from `address` instead of right after the
+    /// code.
+    pub fn with_data_address(mut self, address: u32) -> Self {
+        self.data = Some(address);
+        self
+    }
+
+    /// Add `object`, named `name` in the link map and in diagnostics about
+    /// its main source.
+    pub fn with_object(mut self, name: impl Into<String>, object: Object) -> Self {
+        self.objects.push((name.into(), object));
+        self
+    }
+
+    pub fn link(self) -> Result<Linked, AssembleErrors> {
+        let mut errors = vec![];
+        let mut warnings = vec![];
+        let mut image = Image::default();
+        let mut placements = vec![];
+        // Address of each section of each object.
+        let mut bases: Vec<HashMap<SectionKind, u32>> = vec![HashMap::new(); self.objects.len()];
+        for address in [Some(self.origin), self.data].into_iter().flatten() {
+            if address as u64 >= ADDRESS_SPACE {
+                let error = AssembleError::new(ErrorKind::OutsideAddressSpace, Span::default());
+                return Err(AssembleErrors(vec![error]));
+            }
+        }
+        let mut address = self.origin;
+        for kind in SectionKind::ALL {
+            if let (SectionKind::Data, Some(data)) = (kind, self.data) {
+                address = data;
+            }
+            for (index, (name, object)) in self.objects.iter().enumerate() {
+                let Some(section) = object.section(kind) else {
+                    continue;
+                };
+                let alignment = section.alignment.max(1);
+                let length = section.bytes.len() as u32;
+                let placed = address
+                    .checked_next_multiple_of(alignment)
+                    .and_then(|start| Some((start, start.checked_add(length)?)))
+                    .filter(|&(_, end)| end as u64 <= ADDRESS_SPACE);
+                let Some((start, end)) = placed else {
+                    let mut error =
+                        AssembleError::new(ErrorKind::OutsideAddressSpace, Span::default());
+                    error.file = Some(name.clone());
+                    errors.push(error);
+                    return Err(AssembleErrors(errors));
+                };
+                address = start;
+                image.set_address(address, Span::default());
+                image.extend(section.bytes.iter().copied());
+                bases[index].insert(kind, address);
+                if section.bytes.is_empty() {
+                    continue;
+                }
+                placements.push(Placement {
+                    object: name.clone(),
+                    section: kind,
+                    address,
+                    length,
+                });
+                address = end;
+            }
+        }
+        errors.extend(image.overlaps());
+
+        let mut exports = HashMap::new();
+        let mut symbols = vec![];
+        for ((name, object), bases) in self.objects.iter().zip(&bases) {
+            for symbol in &object.symbols {
+                let value = match symbol.section.map(|section| bases.get(&section)) {
+                    Some(Some(base)) => base + symbol.value,
+                    Some(None) => {
+                        errors.push(undeclared_section(name, "symbol"));
+                        continue;
+                    }
+                    None => symbol.value,
+                };
+                if symbol.global && exports.insert(symbol.name.clone(), value).is_some() {
+                    let mut error = AssembleError::new(
+                        ErrorKind::DuplicateSymbol(symbol.name.clone()),
+                        Span::default(),
+                    );
+                    error.file = Some(name.clone());
+                    errors.push(error);
+                }
+                if symbol.section.is_some() && !symbol.name.contains('~') {
+                    let kind = match symbol.name.contains('.') {
+                        true => SymbolKind::Local,
+                        false => SymbolKind::Global,
+                    };
+                    symbols.push(Symbol {
+                        name: symbol.name.clone(),
+                        value,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        for ((name, object), bases) in self.objects.iter().zip(&bases) {
+            let resolve = |symbol: &str| match SectionKind::ALL
+                .into_iter()
+                .find(|kind| kind.base() == symbol)
+            {
+                Some(kind) => bases.get(&kind).map(|&base| base as i64),
+                None => exports.get(symbol).map(|&value| value as i64),
+            };
+            for relocation in &object.relocations {
+                let file = relocation.file.clone().or_else(|| Some(name.clone()));
+                let error = |kind| {
+                    let mut error = AssembleError::new(kind, relocation.span);
+                    error.file = file.clone();
+                    error
+                };
+                let Some(target) = resolve(&relocation.symbol) else {
+                    errors.push(error(ErrorKind::UndefinedExtern(relocation.symbol.clone())));
+                    continue;
+                };
+                let Some(base) = bases.get(&relocation.section) else {
+                    errors.push(undeclared_section(name, "relocation"));
+                    continue;
+                };
+                let address = base + relocation.offset;
+                let value = match &relocation.kind {
+                    RelocationKind::Absolute | RelocationKind::High => target + relocation.addend,
+                    RelocationKind::Relative => target + relocation.addend - (address as i64 + 3),
+                    RelocationKind::Delta(base) => {
+                        let Some(base) = resolve(base) else {
+                            errors.push(error(ErrorKind::UndefinedExtern(base.clone())));
+                            continue;
+                        };
+                        target - base + relocation.addend
+                    }
+                };
+                if relocation.truncates {
+                    if !(0..1 << relocation.length).contains(&value) {
+                        let kind = WarningKind::Truncated {
+                            value,
+                            bits: relocation.length,
+                        };
+                        let mut warning = Warning::new(kind, relocation.span);
+                        warning.file = file.clone();
+                        warnings.push(warning);
+                    }
+                } else if !(relocation.min..=relocation.max).contains(&value) {
+                    errors.push(error(ErrorKind::OutOfRange {
+                        value,
+                        min: relocation.min,
+                        max: relocation.max,
+                    }));
+                    continue;
+                }
+                let shift = match relocation.kind {
+                    RelocationKind::High => 12,
+                    _ => 0,
+                };
+                let mask = 2_u32.pow(relocation.length) - 1;
+                let u = (value as u32 >> shift) & mask;
+                if !image.patch(address, relocation.bytes, u) {
+                    errors.push(error(ErrorKind::ReferenceOutOfBounds(
+                        relocation.symbol.clone(),
+                    )));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(AssembleErrors(errors));
+        }
+        symbols.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+        placements.sort_by_key(|placement| placement.address);
+        let assembly = Assembly {
+            segments: image.segments(),
+            warnings,
+            listing: None,
+            symbols,
+            lines: vec![],
+        };
+        Ok(Linked {
+            assembly,
+            placements,
+        })
+    }
+}
+
+impl Linked {
+    /// Link map with the address, length, section and object of every
+    /// placed section, then the address of every label.
+    pub fn map(&self) -> String {
+        let mut map = String::new();
+        for placement in &self.placements {
+            writeln!(
+                map,
+                "{:06X} {:6} {} {}",
+                placement.address,
+                placement.length,
+                placement.section.name(),
+                placement.object
+            )
+            .unwrap();
+        }
+        writeln!(map).unwrap();
+        map.push_str(&self.assembly.sym());
+        map
+    }
+}
+
+/// Error for a `record` of the object `name` in a section the object does
+/// not have, which only objects built by hand contain.
+fn undeclared_section(name: &str, record: &str) -> AssembleError {
+    let kind = ErrorKind::InvalidObject(format!("{} of an undeclared section", record));
+    let mut error = AssembleError::new(kind, Span::default());
+    error.file = Some(name.to_string());
+    error
+}
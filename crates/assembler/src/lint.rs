@@ -0,0 +1,399 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use common::{
+    Format, Instruction, Op, REGISTER_GLOBAL, REGISTER_INTERRUPT, REGISTER_PROGRAM_COUNTER,
+};
+
+use crate::{image::Image, listing::Statement, syntax, Span, Warning, WarningKind};
+
+/// A mistake that still assembles, which [`crate::Assembler::with_lints`]
+/// looks for in the assembled program. A `; lint: allow(name)` comment on
+/// the line a lint points at, naming it, keeps it quiet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// A conditional instruction with no instruction that sets the
+    /// condition between it and the label before it.
+    UnsetCondition,
+    /// `ir` is written by code that the interrupt vector branches to for
+    /// interrupt 0, rather than by an interrupt handler.
+    InterruptWrite,
+    /// An interrupt handler gives `sp` a value that is not derived from
+    /// `sp`, and switching contexts copies it to the interrupted code.
+    GlobalWrite,
+    /// A label is defined again, which replaces its earlier address.
+    DuplicateLabel(String),
+    /// A label no expression refers to.
+    UnusedLabel(String),
+    /// An instruction after an unconditional write to `pc` that no label
+    /// or return address reaches.
+    UnreachableCode,
+}
+
+impl Lint {
+    /// Name that `; lint: allow(name)` refers to the lint by.
+    pub fn name(&self) -> &'static str {
+        use Lint::*;
+        match self {
+            UnsetCondition => "unset-condition",
+            InterruptWrite => "interrupt-write",
+            GlobalWrite => "global-write",
+            DuplicateLabel(_) => "duplicate-label",
+            UnusedLabel(_) => "unused-label",
+            UnreachableCode => "unreachable-code",
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Lint::*;
+        match self {
+            UnsetCondition => write!(
+                f,
+                "no instruction since the last label sets the condition this instruction runs on"
+            ),
+            InterruptWrite => write!(f, "`ir` is written outside an interrupt handler"),
+            GlobalWrite => write!(
+                f,
+                "`sp` is written in an interrupt handler and the interrupted code gets its value"
+            ),
+            DuplicateLabel(label) => write!(
+                f,
+                "label `{}` is defined again and its earlier address is lost",
+                label
+            ),
+            UnusedLabel(label) => write!(f, "label `{}` is never referred to", label),
+            UnreachableCode => write!(
+                f,
+                "instruction after an unconditional write to `pc` is never reached"
+            ),
+        }
+    }
+}
+
+/// An instruction word of the program and the statement that placed it.
+struct Word {
+    address: u32,
+    instruction: Instruction,
+    span: Span,
+}
+
+impl Word {
+    /// Register the instruction writes, which stores do not.
+    fn destination(&self) -> Option<u32> {
+        use Op::*;
+        match self.instruction.op {
+            Store | Store2 | Store3 => None,
+            _ => Some(self.instruction.r),
+        }
+    }
+
+    /// Whether the instruction always writes `pc`, so the word after it
+    /// only runs when something branches to it.
+    fn jumps(&self) -> bool {
+        !self.instruction.conditional && self.destination() == Some(REGISTER_PROGRAM_COUNTER)
+    }
+
+    /// Address the instruction loads into a register other than `pc` from
+    /// `pc`, like the return address of `call`.
+    fn return_address(&self) -> Option<u32> {
+        let Instruction { op, r, s, u, .. } = self.instruction;
+        (op == Op::Addi && s == REGISTER_PROGRAM_COUNTER && r != REGISTER_PROGRAM_COUNTER)
+            .then(|| self.address + 3 + u)
+    }
+
+    /// Whether the value written to `sp` is derived from `sp`, like that of
+    /// `push`, `pop` or a stack frame.
+    fn adjusts_stack(&self) -> bool {
+        let Instruction { op, s, u, .. } = self.instruction;
+        match op.format() {
+            Format::L => false,
+            Format::I => s == REGISTER_GLOBAL,
+            Format::R => s == REGISTER_GLOBAL || u == REGISTER_GLOBAL,
+        }
+    }
+}
+
+/// Looks for every [`Lint`] in the instructions and labels of a program.
+pub(crate) struct Linter<'a> {
+    /// Instruction words in order of address.
+    words: Vec<Word>,
+    /// Index of the word at each address.
+    indices: HashMap<u32, usize>,
+    /// Addresses that code may branch to: labels and return addresses.
+    entries: HashSet<u32>,
+    /// Address each word writes to `pc`, when it is known.
+    targets: Vec<Option<u32>>,
+    /// Labels in the order they are defined.
+    definitions: &'a [(String, Span)],
+    /// Labels that expressions refer to.
+    referenced: &'a HashSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Linter<'a> {
+    pub(crate) fn new(
+        statements: &[Statement],
+        image: &Image,
+        labels: &HashMap<String, u32>,
+        definitions: &'a [(String, Span)],
+        referenced: &'a HashSet<String>,
+    ) -> Self {
+        let mut words = vec![];
+        for statement in statements.iter().filter(|statement| statement.instruction) {
+            let bytes = image
+                .get(statement.address, statement.length as usize)
+                .unwrap_or_default();
+            for (index, word) in bytes.chunks_exact(3).enumerate() {
+                words.push(Word {
+                    address: statement.address + 3 * index as u32,
+                    instruction: Instruction::decode(u32::from_be_bytes([
+                        0, word[0], word[1], word[2],
+                    ])),
+                    span: statement.span,
+                });
+            }
+        }
+        words.sort_by_key(|word| word.address);
+        let indices = words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| (word.address, index))
+            .collect();
+        let entries = labels
+            .iter()
+            // `scope~.local` is a distance rather than an address.
+            .filter(|(name, _)| !name.contains('~'))
+            .map(|(_, &address)| address)
+            .chain(words.iter().filter_map(Word::return_address))
+            .collect();
+        let mut linter = Self {
+            words,
+            indices,
+            entries,
+            targets: vec![],
+            definitions,
+            referenced,
+            warnings: vec![],
+        };
+        linter.targets = linter.targets();
+        linter
+    }
+
+    /// Look for every lint and keep those that no comment allows, in
+    /// `source`, the expanded source the spans point into.
+    pub(crate) fn run(mut self, source: &str) -> Vec<Warning> {
+        self.check_conditions();
+        self.check_contexts();
+        self.check_labels();
+        self.check_reachability();
+        let lines: Vec<_> = source.split('\n').collect();
+        self.warnings
+            .into_iter()
+            .filter(|warning| {
+                let WarningKind::Lint(lint) = &warning.kind else {
+                    return true;
+                };
+                let line = lines.get(warning.span.start.line - 1).unwrap_or(&"");
+                !allowed(line, lint)
+            })
+            .collect()
+    }
+
+    fn warn(&mut self, lint: Lint, span: Span) {
+        let warning = Warning::new(WarningKind::Lint(lint), span);
+        // A pseudo-instruction places several words for one statement.
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Whether the word at `index` follows the word before it, so it runs
+    /// after it unless that word jumps.
+    fn follows(&self, index: usize) -> bool {
+        index > 0 && self.words[index - 1].address + 3 == self.words[index].address
+    }
+
+    /// Address each word writes to `pc`, following the values that `let`
+    /// and `lethi` load since the last label, like those of `jump`.
+    fn targets(&self) -> Vec<Option<u32>> {
+        use Op::*;
+        let mut known: HashMap<u32, u32> = HashMap::new();
+        let mut targets = vec![];
+        for (index, word) in self.words.iter().enumerate() {
+            if !self.follows(index) || self.entries.contains(&word.address) {
+                known.clear();
+            }
+            let Instruction { op, r, s, u, .. } = word.instruction;
+            let pc = REGISTER_PROGRAM_COUNTER;
+            let value = match op {
+                Let => Some(u),
+                Lethi => known.get(&r).map(|low| low & 0o77_77 | u << 12),
+                Addi if s == pc => Some(word.address + 3 + u),
+                Subi if s == pc => Some((word.address + 3).wrapping_sub(u)),
+                // Moves like `mov` and `ori pc, rB, 0`.
+                Shri | Shli | Ori | Xori | Addi | Subi if u == 0 => known.get(&s).copied(),
+                _ => None,
+            };
+            match word.destination() {
+                Some(register) if register == pc => targets.push(value),
+                Some(register) => {
+                    targets.push(None);
+                    match value {
+                        Some(value) => known.insert(register, value),
+                        None => known.remove(&register),
+                    };
+                }
+                None => targets.push(None),
+            }
+        }
+        targets
+    }
+
+    /// Indices of the words that run after the word at `index`.
+    fn successors(&self, index: usize) -> Vec<usize> {
+        let word = &self.words[index];
+        let mut successors = vec![];
+        if !word.jumps() && index + 1 < self.words.len() && self.follows(index + 1) {
+            successors.push(index + 1);
+        }
+        for address in [self.targets[index], word.return_address()] {
+            if let Some(&target) = address.and_then(|address| self.indices.get(&address)) {
+                successors.push(target);
+            }
+        }
+        successors
+    }
+
+    /// Indices of the words that run after any of `starts`.
+    fn reachable(&self, starts: Vec<usize>) -> HashSet<usize> {
+        let mut reached: HashSet<usize> = starts.iter().copied().collect();
+        let mut pending = starts;
+        while let Some(index) = pending.pop() {
+            for successor in self.successors(index) {
+                if reached.insert(successor) {
+                    pending.push(successor);
+                }
+            }
+        }
+        reached
+    }
+
+    fn check_conditions(&mut self) {
+        let mut set = false;
+        for index in 0..self.words.len() {
+            let word = &self.words[index];
+            if !self.follows(index) || self.entries.contains(&word.address) {
+                set = false;
+            }
+            if word.instruction.conditional && !set {
+                self.warn(Lint::UnsetCondition, word.span);
+            }
+            set |= self.words[index].instruction.op.sets_condition();
+        }
+    }
+
+    /// Branches of the interrupt vector, which compares `ir` with the
+    /// number of an interrupt and branches to its handler when they are
+    /// equal, as the interrupt number and the index of the handler.
+    fn dispatches(&self) -> Vec<(u32, usize)> {
+        let mut dispatches = vec![];
+        for (index, word) in self.words.iter().enumerate() {
+            let Instruction { op, s, u, .. } = word.instruction;
+            if !matches!(op, Op::Lessi | Op::Slessi) || s != REGISTER_INTERRUPT {
+                continue;
+            }
+            for next in index + 1..self.words.len() {
+                if !self.follows(next) {
+                    break;
+                }
+                let branch = &self.words[next];
+                if branch.instruction.conditional
+                    && branch.destination() == Some(REGISTER_PROGRAM_COUNTER)
+                {
+                    let target = self.targets[next].and_then(|target| self.indices.get(&target));
+                    if let Some(&target) = target {
+                        dispatches.push((u, target));
+                    }
+                    break;
+                }
+                if branch.instruction.op.sets_condition() {
+                    break;
+                }
+            }
+        }
+        dispatches
+    }
+
+    /// Look for writes to `ir` in the code of interrupt 0 and to `sp` in
+    /// the code of other interrupts, as far as the interrupt vector and the
+    /// branches with known targets tell them apart.
+    fn check_contexts(&mut self) {
+        let (main, handlers): (Vec<_>, Vec<_>) = self
+            .dispatches()
+            .into_iter()
+            .partition(|&(interrupt, _)| interrupt == 0);
+        let main = self.reachable(main.into_iter().map(|(_, index)| index).collect());
+        let handlers = self.reachable(handlers.into_iter().map(|(_, index)| index).collect());
+        for index in 0..self.words.len() {
+            let word = &self.words[index];
+            let span = word.span;
+            match word.destination() {
+                Some(REGISTER_INTERRUPT) if main.contains(&index) && !handlers.contains(&index) => {
+                    self.warn(Lint::InterruptWrite, span)
+                }
+                Some(REGISTER_GLOBAL)
+                    if handlers.contains(&index)
+                        && !main.contains(&index)
+                        && !word.adjusts_stack() =>
+                {
+                    self.warn(Lint::GlobalWrite, span)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_labels(&mut self) {
+        let mut defined = HashSet::new();
+        for (label, span) in self.definitions {
+            if !defined.insert(label) {
+                self.warn(Lint::DuplicateLabel(label.clone()), *span);
+            } else if !self.referenced.contains(label) {
+                self.warn(Lint::UnusedLabel(label.clone()), *span);
+            }
+        }
+    }
+
+    fn check_reachability(&mut self) {
+        for index in 1..self.words.len() {
+            let word = &self.words[index];
+            let previous = &self.words[index - 1];
+            if self.follows(index)
+                && previous.jumps()
+                && previous.span != word.span
+                && !self.entries.contains(&word.address)
+            {
+                self.warn(Lint::UnreachableCode, word.span);
+            }
+        }
+    }
+}
+
+/// Whether the comment of `line` is `; lint: allow(...)` naming `lint`.
+fn allowed(line: &str, lint: &Lint) -> bool {
+    let comment = &line[syntax::strip_comment(line).len()..];
+    let names = comment
+        .trim_start_matches(';')
+        .trim()
+        .strip_prefix("lint:")
+        .and_then(|rest| rest.trim().strip_prefix("allow("))
+        .and_then(|rest| rest.split_once(')'));
+    match names {
+        Some((names, _)) => names.split(',').any(|name| name.trim() == lint.name()),
+        None => false,
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{image::Image, preprocessor::SourceMap, Span};
+
+/// Bytes a statement placed, recorded for the listing and the lints.
+pub(crate) struct Statement {
+    /// Span in the expanded source.
+    pub(crate) span: Span,
+    pub(crate) address: u32,
+    pub(crate) length: u32,
+    pub(crate) instruction: bool,
+}
+
+/// List every line of the expanded `source` with its address, the bytes
+/// placed for it in hexadecimal and, for instructions, in octal split into
+/// the `c`, `op`, `r`, `s` and `u` fields, its text and the values of the
+/// symbols it refers to.
+pub(crate) fn write(
+    source: &str,
+    map: &SourceMap,
+    statements: &[Statement],
+    image: &Image,
+    symbols: &BTreeMap<usize, Vec<String>>,
+) -> String {
+    let lines: Vec<_> = source.split('\n').collect();
+    let locations: Vec<_> = (1..=lines.len()).map(|line| map.describe(line)).collect();
+    let width = locations.iter().map(String::len).max().unwrap_or(0).max(4);
+    let mut listing = String::new();
+    let fields = "c op r  s  u";
+    write_row(
+        &mut listing,
+        width,
+        "line",
+        "address",
+        "bytes",
+        fields,
+        "source",
+    );
+    let mut statements = statements.iter().peekable();
+    for (index, text) in lines.iter().enumerate() {
+        let line = index + 1;
+        let mut address = None;
+        let mut words = vec![];
+        while let Some(statement) =
+            statements.next_if(|statement| statement.span.start.line == line)
+        {
+            address.get_or_insert(statement.address);
+            let bytes = image
+                .get(statement.address, statement.length as usize)
+                .unwrap_or_default();
+            for word in bytes.chunks(3) {
+                words.push((word, statement.instruction));
+            }
+        }
+        let mut text = text.trim_end().to_string();
+        if let Some(symbols) = symbols.get(&line) {
+            write!(text, "  ; {}", symbols.join(", ")).unwrap();
+        }
+        let address = match address {
+            Some(address) => format!("{:06X}", address),
+            None => String::new(),
+        };
+        let location = &locations[index];
+        if words.is_empty() {
+            write_row(&mut listing, width, location, &address, "", "", &text);
+        }
+        for (index, (word, instruction)) in words.into_iter().enumerate() {
+            let hex: String = word.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let octal = match (instruction, word) {
+                (true, &[a, b, c]) => {
+                    let word = u32::from_be_bytes([0, a, b, c]);
+                    format!(
+                        "{:o} {:02o} {:02o} {:02o} {:02o}",
+                        word >> 23,
+                        word >> 18 & 0o37,
+                        word >> 12 & 0o77,
+                        word >> 6 & 0o77,
+                        word & 0o77
+                    )
+                }
+                _ => String::new(),
+            };
+            match index {
+                0 => write_row(&mut listing, width, location, &address, &hex, &octal, &text),
+                _ => write_row(&mut listing, width, "", "", &hex, &octal, ""),
+            }
+        }
+    }
+    listing
+}
+
+fn write_row(
+    listing: &mut String,
+    width: usize,
+    location: &str,
+    address: &str,
+    hex: &str,
+    octal: &str,
+    text: &str,
+) {
+    let row = format!(
+        "{:<width$}  {:<7}  {:<6}  {:<14}  {}",
+        location,
+        address,
+        hex,
+        octal,
+        text,
+        width = width
+    );
+    writeln!(listing, "{}", row.trim_end()).unwrap();
+}
@@ -0,0 +1,366 @@
+use std::fmt::Write;
+
+use crate::{AssembleError, ErrorKind, Location, Span, Warning};
+
+/// Kind of bytes a section holds. The linker places the code of every
+/// object before their data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SectionKind {
+    Code,
+    Data,
+}
+
+impl SectionKind {
+    pub(crate) const ALL: [SectionKind; 2] = [SectionKind::Code, SectionKind::Data];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SectionKind::Code => "code",
+            SectionKind::Data => "data",
+        }
+    }
+
+    /// Name that stands for the address of the section in relocations,
+    /// which cannot collide with a label.
+    pub fn base(self) -> &'static str {
+        match self {
+            SectionKind::Code => ".code",
+            SectionKind::Data => ".data",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// Bytes that the linker places together, at a multiple of `alignment`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub alignment: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// A label or `.equ` constant of an object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    /// Section the value is an offset into, or `None` for a constant.
+    pub section: Option<SectionKind>,
+    pub value: u32,
+    /// Whether other objects may refer to the symbol, as declared with
+    /// `.global`.
+    pub global: bool,
+}
+
+/// How the value patched in by a relocation is worked out from the address
+/// of its symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// The address plus the addend.
+    Absolute,
+    /// Bits 12 to 23 of the address plus the addend, for `lethi`.
+    High,
+    /// Distance from the end of the instruction to the address plus the
+    /// addend, for `~label`.
+    Relative,
+    /// Distance from the address of the named symbol to the address plus
+    /// the addend, for `label~.local` and differences of labels.
+    Delta(String),
+}
+
+/// A field whose value depends on addresses only known after linking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    pub section: SectionKind,
+    /// Offset into the section of the instruction or data holding the
+    /// field.
+    pub offset: u32,
+    pub kind: RelocationKind,
+    /// A symbol of another object, or a section of this one like `.code`.
+    pub symbol: String,
+    pub addend: i64,
+    /// Number of bits in the field.
+    pub length: u32,
+    /// Number of bytes from the offset that hold the field, as a
+    /// big-endian number: 3 for an instruction, 1 to 3 for data.
+    pub bytes: u32,
+    /// Values the field accepts.
+    pub min: i64,
+    pub max: i64,
+    /// Whether a value too large for the field is truncated with a warning
+    /// instead of rejected.
+    pub truncates: bool,
+    /// Operand the field was assembled from, for diagnostics.
+    pub span: Span,
+    /// Included file the operand lies in, or `None` for the main source.
+    pub file: Option<String>,
+}
+
+/// Output of assembling a source on its own, to be placed by the
+/// [`Linker`](crate::Linker) along with other objects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Object {
+    /// The code and data sections, in that order.
+    pub sections: Vec<Section>,
+    pub symbols: Vec<ObjectSymbol>,
+    /// Symbols declared with `.extern`, which other objects define.
+    pub externs: Vec<String>,
+    pub relocations: Vec<Relocation>,
+    /// Warnings of the assembly, which object files do not keep.
+    pub warnings: Vec<Warning>,
+}
+
+const MAGIC: &str = "kitty24 object";
+
+/// Bytes per `bytes` line of an object file.
+const BYTES_PER_LINE: usize = 24;
+
+impl Object {
+    pub fn section(&self, kind: SectionKind) -> Option<&Section> {
+        self.sections.iter().find(|section| section.kind == kind)
+    }
+
+    /// Object file with one record per line, read back by
+    /// [`Object::read`].
+    pub fn write(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "{}", MAGIC).unwrap();
+        for section in &self.sections {
+            let name = section.kind.name();
+            writeln!(text, "section {} {}", name, section.alignment).unwrap();
+            for (index, chunk) in section.bytes.chunks(BYTES_PER_LINE).enumerate() {
+                let hex: String = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                writeln!(
+                    text,
+                    "bytes {} {:06X} {}",
+                    name,
+                    index * BYTES_PER_LINE,
+                    hex
+                )
+                .unwrap();
+            }
+        }
+        for symbol in &self.symbols {
+            let section = symbol.section.map_or("-", SectionKind::name);
+            write!(
+                text,
+                "symbol {} {} {:06X}",
+                symbol.name, section, symbol.value
+            )
+            .unwrap();
+            if symbol.global {
+                write!(text, " global").unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+        for name in &self.externs {
+            writeln!(text, "extern {}", name).unwrap();
+        }
+        for relocation in &self.relocations {
+            let kind = match &relocation.kind {
+                RelocationKind::Absolute => "absolute".to_string(),
+                RelocationKind::High => "high".to_string(),
+                RelocationKind::Relative => "relative".to_string(),
+                RelocationKind::Delta(base) => format!("delta:{}", base),
+            };
+            let Span { start, end } = relocation.span;
+            write!(
+                text,
+                "relocation {} {:06X} {} {} {} {} {} {} {} {} {}:{}-{}:{}",
+                relocation.section.name(),
+                relocation.offset,
+                kind,
+                relocation.symbol,
+                relocation.addend,
+                relocation.length,
+                relocation.bytes,
+                relocation.min,
+                relocation.max,
+                match relocation.truncates {
+                    true => "truncates",
+                    false => "checked",
+                },
+                start.line,
+                start.column,
+                end.line,
+                end.column,
+            )
+            .unwrap();
+            if let Some(file) = &relocation.file {
+                write!(text, " {}", file).unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+        text
+    }
+
+    /// Read an object file written by [`Object::write`]. Errors span the
+    /// offending line.
+    pub fn read(text: &str) -> Result<Self, AssembleError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, MAGIC)) => {}
+            _ => {
+                return Err(invalid(
+                    1,
+                    text.lines().next().unwrap_or(""),
+                    "missing header",
+                ))
+            }
+        }
+        let mut object = Object {
+            sections: vec![],
+            symbols: vec![],
+            externs: vec![],
+            relocations: vec![],
+            warnings: vec![],
+        };
+        for (index, line) in lines {
+            let error = |reason: &str| invalid(index + 1, line, reason);
+            let mut fields = line.split(' ');
+            match fields.next() {
+                Some("section") => {
+                    let kind = section_kind(fields.next()).ok_or_else(|| error("bad section"))?;
+                    let alignment = number(fields.next()).ok_or_else(|| error("bad alignment"))?;
+                    object.sections.push(Section {
+                        kind,
+                        alignment,
+                        bytes: vec![],
+                    });
+                }
+                Some("bytes") => {
+                    let kind = section_kind(fields.next());
+                    let section = object
+                        .sections
+                        .iter_mut()
+                        .find(|section| Some(section.kind) == kind)
+                        .ok_or_else(|| error("bytes of an undeclared section"))?;
+                    let offset = hex(fields.next()).ok_or_else(|| error("bad offset"))?;
+                    if offset as usize != section.bytes.len() {
+                        return Err(error("bytes out of order"));
+                    }
+                    let hex = fields.next().unwrap_or("");
+                    let bytes = (0..hex.len())
+                        .step_by(2)
+                        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| error("bad bytes"))?;
+                    section.bytes.extend(bytes);
+                }
+                Some("symbol") => {
+                    let name = fields.next().ok_or_else(|| error("missing name"))?;
+                    let section = match fields.next() {
+                        Some("-") => None,
+                        name => Some(section_kind(name).ok_or_else(|| error("bad section"))?),
+                    };
+                    if section.is_some_and(|kind| object.section(kind).is_none()) {
+                        return Err(error("symbol of an undeclared section"));
+                    }
+                    let value = hex(fields.next()).ok_or_else(|| error("bad value"))?;
+                    let global = match fields.next() {
+                        Some("global") => true,
+                        None => false,
+                        Some(_) => return Err(error("bad symbol")),
+                    };
+                    object.symbols.push(ObjectSymbol {
+                        name: name.to_string(),
+                        section,
+                        value,
+                        global,
+                    });
+                }
+                Some("extern") => {
+                    let name = fields.next().ok_or_else(|| error("missing name"))?;
+                    object.externs.push(name.to_string());
+                }
+                Some("relocation") => {
+                    let relocation = relocation(fields).ok_or_else(|| error("bad relocation"))?;
+                    if object.section(relocation.section).is_none() {
+                        return Err(error("relocation of an undeclared section"));
+                    }
+                    object.relocations.push(relocation);
+                }
+                Some("") => {}
+                _ => return Err(error("unknown record")),
+            }
+        }
+        Ok(object)
+    }
+}
+
+fn relocation<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<Relocation> {
+    let section = section_kind(fields.next())?;
+    let offset = hex(fields.next())?;
+    let kind = match fields.next()? {
+        "absolute" => RelocationKind::Absolute,
+        "high" => RelocationKind::High,
+        "relative" => RelocationKind::Relative,
+        kind => RelocationKind::Delta(kind.strip_prefix("delta:")?.to_string()),
+    };
+    let symbol = fields.next()?.to_string();
+    let addend = fields.next()?.parse().ok()?;
+    let length = number(fields.next()).filter(|length| (1..=24).contains(length))?;
+    let bytes = number(fields.next()).filter(|bytes| (1..=3).contains(bytes))?;
+    let min = fields.next()?.parse().ok()?;
+    let max = fields.next()?.parse().ok()?;
+    let truncates = match fields.next()? {
+        "truncates" => true,
+        "checked" => false,
+        _ => return None,
+    };
+    let (start, end) = fields.next()?.split_once('-')?;
+    let span = Span {
+        start: location(start)?,
+        end: location(end)?,
+    };
+    // File names may contain spaces.
+    let file: Vec<_> = fields.collect();
+    let file = (!file.is_empty()).then(|| file.join(" "));
+    Some(Relocation {
+        section,
+        offset,
+        kind,
+        symbol,
+        addend,
+        length,
+        bytes,
+        min,
+        max,
+        truncates,
+        span,
+        file,
+    })
+}
+
+fn section_kind(name: Option<&str>) -> Option<SectionKind> {
+    SectionKind::parse(name?)
+}
+
+fn number(text: Option<&str>) -> Option<u32> {
+    text?.parse().ok()
+}
+
+fn hex(text: Option<&str>) -> Option<u32> {
+    u32::from_str_radix(text?, 16).ok()
+}
+
+fn location(text: &str) -> Option<Location> {
+    let (line, column) = text.split_once(':')?;
+    Some(Location {
+        line: line.parse().ok()?,
+        column: column.parse().ok()?,
+    })
+}
+
+fn invalid(line: usize, text: &str, reason: &str) -> AssembleError {
+    let span = Span {
+        start: Location { line, column: 1 },
+        end: Location {
+            line,
+            column: text.len() + 1,
+        },
+    };
+    AssembleError::new(ErrorKind::InvalidObject(reason.to_string()), span)
+}
@@ -0,0 +1,892 @@
+use std::{collections::HashMap, rc::Rc};
+
+use pest::{iterators::Pair, Parser};
+
+use crate::{
+    resolver::{self, SourceResolver},
+    syntax::strip_comment,
+    AssembleError, Assembler, ErrorKind, Expansion, KittyAssemblyParser, Location, Rule,
+    SectionKind, Span, Warning,
+};
+
+/// Deepest nesting of macro invocations, to stop macros that invoke
+/// themselves.
+const MAX_DEPTH: usize = 64;
+
+/// Most repetitions of a `.rept` block, as many as there are addresses.
+const MAX_REPEAT: i64 = 0o77_777_777;
+
+/// Source with every file included and every macro expanded, along with
+/// where each of its lines came from.
+pub(crate) struct Expanded {
+    pub(crate) source: String,
+    pub(crate) map: SourceMap,
+}
+
+struct Macro {
+    /// Included file the macro is defined in, or `None` for the main source.
+    file: Option<Rc<str>>,
+    parameters: Vec<String>,
+    /// Lines between `.macro` and `.endm` with their line numbers.
+    body: Vec<(usize, String)>,
+    /// Local labels defined in the body, which every expansion renames.
+    labels: Vec<String>,
+}
+
+/// Where a line of the expanded source came from.
+#[derive(Clone)]
+struct Origin {
+    /// Included file the line is in, or `None` for the main source.
+    file: Option<Rc<str>>,
+    line: usize,
+    /// Column in the original line of every byte of the expanded line, when
+    /// arguments were substituted into it.
+    columns: Option<Vec<usize>>,
+    expansions: Vec<Expansion>,
+}
+
+/// Maps locations in the expanded source back to the original source.
+#[derive(Default)]
+pub(crate) struct SourceMap {
+    origins: Vec<Origin>,
+}
+
+impl SourceMap {
+    pub(crate) fn map_error(&self, mut error: AssembleError) -> AssembleError {
+        error.file = self.file(error.span);
+        error.expansions = self.expansions(error.span);
+        error.span = self.original_span(error.span);
+        error
+    }
+
+    pub(crate) fn map_warning(&self, mut warning: Warning) -> Warning {
+        warning.file = self.file(warning.span);
+        warning.expansions = self.expansions(warning.span);
+        warning.span = self.original_span(warning.span);
+        warning
+    }
+
+    /// File and span in the original source of a span of the expanded
+    /// source.
+    pub(crate) fn map_span(&self, span: Span) -> (Option<String>, Span) {
+        (self.file(span), self.original_span(span))
+    }
+
+    /// Line in the original source of a line of the expanded source,
+    /// prefixed with its file when it lies in an included file.
+    pub(crate) fn describe(&self, line: usize) -> String {
+        match self.locate(line) {
+            (Some(file), line) => format!("{}:{}", file, line),
+            (None, line) => line.to_string(),
+        }
+    }
+
+    /// File and line in the original source of a line of the expanded
+    /// source.
+    pub(crate) fn locate(&self, line: usize) -> (Option<String>, usize) {
+        match self.origin(line) {
+            Some(origin) => (origin.file.as_deref().map(str::to_string), origin.line),
+            None => (None, line),
+        }
+    }
+
+    fn origin(&self, line: usize) -> Option<&Origin> {
+        self.origins.get(line.checked_sub(1)?)
+    }
+
+    fn file(&self, span: Span) -> Option<String> {
+        let origin = self.origin(span.start.line)?;
+        origin.file.as_deref().map(str::to_string)
+    }
+
+    fn expansions(&self, span: Span) -> Vec<Expansion> {
+        match self.origin(span.start.line) {
+            Some(origin) => origin.expansions.clone(),
+            None => vec![],
+        }
+    }
+
+    fn original_span(&self, span: Span) -> Span {
+        let start = self.map_location(span.start);
+        let end = match span.end.line == span.start.line {
+            true => Location {
+                line: start.line,
+                ..self.map_location(span.end)
+            },
+            false => self.map_location(span.end),
+        };
+        Span { start, end }
+    }
+
+    fn map_location(&self, location: Location) -> Location {
+        if self.origins.is_empty() {
+            return location;
+        }
+        let Some(origin) = self.origin(location.line) else {
+            // Past the last line, as at the end of the input.
+            let line = self.origins.last().map_or(1, |origin| origin.line + 1);
+            return Location { line, column: 1 };
+        };
+        let column = match &origin.columns {
+            None => location.column,
+            Some(columns) => match columns.get(location.column - 1) {
+                Some(&column) => column,
+                None => columns.last().map_or(1, |column| column + 1),
+            },
+        };
+        Location {
+            line: origin.line,
+            column,
+        }
+    }
+}
+
+impl Origin {
+    /// Column in the original line of a column of the expanded line.
+    fn column(&self, column: usize) -> usize {
+        match &self.columns {
+            Some(columns) => columns[column - 1],
+            None => column,
+        }
+    }
+
+    /// Span in the original source of the columns `start..end` of the
+    /// expanded line.
+    fn span(&self, start: usize, end: usize) -> Span {
+        let start = Location {
+            line: self.line,
+            column: self.column(start),
+        };
+        let end = Location {
+            line: self.line,
+            column: self.column(end - 1) + 1,
+        };
+        Span { start, end }
+    }
+}
+
+/// Directive that opens a block, to report it when the block is never
+/// closed.
+struct Opening {
+    directive: String,
+    span: Span,
+    file: Option<Rc<str>>,
+    expansions: Vec<Expansion>,
+}
+
+/// A `.if`, `.ifdef` or `.ifndef` block.
+struct Condition {
+    opening: Opening,
+    /// Whether the lines of the current branch are kept.
+    active: bool,
+    /// Whether no later branch is kept, because an earlier one was or the
+    /// whole block lies in a branch that is not.
+    taken: bool,
+    /// Whether the block reached its `.else`.
+    otherwise: bool,
+}
+
+/// A `.rept` block whose lines are being collected.
+struct Repeat {
+    opening: Opening,
+    count: usize,
+    lines: Vec<(String, Origin)>,
+    /// Number of `.rept` blocks open among the lines.
+    depth: usize,
+}
+
+#[derive(Default)]
+struct Preprocessor<'a> {
+    resolver: Option<&'a dyn SourceResolver>,
+    /// Files being included, innermost last, to detect cycles.
+    files: Vec<Rc<str>>,
+    macros: HashMap<String, Rc<Macro>>,
+    lines: Vec<String>,
+    origins: Vec<Origin>,
+    errors: Vec<AssembleError>,
+    /// Expansions and repetitions so far, which makes the local labels of
+    /// each unique.
+    expansions: usize,
+    /// Constants defined so far, which conditions and repeat counts may use.
+    constants: Assembler,
+    /// Conditional blocks open at this point, innermost last.
+    conditions: Vec<Condition>,
+    /// Number of conditional blocks open when the current file, expansion
+    /// or repetition started, which it cannot close.
+    floor: usize,
+    /// `.rept` blocks whose lines are being collected, innermost last.
+    repeats: Vec<Repeat>,
+}
+
+/// Include the files, keep the lines that conditions choose, repeat blocks
+/// and expand the macros in `source`.
+pub(crate) fn expand(
+    source: &str,
+    resolver: Option<&dyn SourceResolver>,
+    defines: &[(String, i64)],
+) -> Result<Expanded, Vec<AssembleError>> {
+    let mut constants = Assembler {
+        defines: defines.to_vec(),
+        ..Default::default()
+    };
+    constants.define_constants();
+    let mut preprocessor = Preprocessor {
+        resolver,
+        constants,
+        ..Default::default()
+    };
+    preprocessor.source(source, None, &[]);
+    if !preprocessor.errors.is_empty() {
+        return Err(preprocessor.errors);
+    }
+    Ok(Expanded {
+        source: preprocessor.lines.join("\n"),
+        map: SourceMap {
+            origins: preprocessor.origins,
+        },
+    })
+}
+
+impl Preprocessor<'_> {
+    /// Add the lines of `text`, from the included `file` or the main source,
+    /// to the output.
+    fn source(&mut self, text: &str, file: Option<Rc<str>>, expansions: &[Expansion]) {
+        let blocks = self.open_blocks();
+        let mut lines = text.split('\n').enumerate();
+        while let Some((index, line)) = lines.next() {
+            let number = index + 1;
+            let (column, word) = first_word(line);
+            let origin = Origin {
+                file: file.clone(),
+                line: number,
+                columns: None,
+                expansions: expansions.to_vec(),
+            };
+            match word.to_lowercase().as_str() {
+                ".macro" => self.define(line, origin, column, &mut lines),
+                ".endm" => {
+                    let kind = ErrorKind::Syntax("`.endm` without `.macro`".to_string());
+                    self.error(kind, origin.span(column, column + word.len()), &origin);
+                }
+                _ => self.process(line.to_string(), origin),
+            }
+        }
+        self.close_blocks(blocks);
+    }
+
+    /// Collect the body of the macro defined on `line`, up to its `.endm`.
+    fn define<'a>(
+        &mut self,
+        line: &str,
+        origin: Origin,
+        column: usize,
+        lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    ) {
+        let code = strip_comment(line);
+        let rest = &code[column - 1 + ".macro".len()..];
+        let name_column = column + ".macro".len() + rest.len() - rest.trim_start().len();
+        let (name, parameters) = match rest.trim().split_once(char::is_whitespace) {
+            Some((name, parameters)) => (name, split_arguments(parameters)),
+            None => (rest.trim(), vec![]),
+        };
+        let mut body = vec![];
+        let mut closed = false;
+        for (index, line) in lines.by_ref() {
+            let (column, word) = first_word(line);
+            match word.to_lowercase().as_str() {
+                ".endm" => {
+                    closed = true;
+                    break;
+                }
+                ".macro" => {
+                    let kind = ErrorKind::Syntax("macro definitions cannot be nested".to_string());
+                    let span = token_span(index + 1, column, word.len());
+                    self.push_error(kind, span, &origin.file, &origin.expansions);
+                }
+                _ => body.push((index + 1, line.to_string())),
+            }
+        }
+        if !self.active() {
+            return;
+        }
+        if !self.repeats.is_empty() {
+            let kind = ErrorKind::Syntax("macros cannot be defined inside `.rept`".to_string());
+            self.error(kind, origin.span(column, column + ".macro".len()), &origin);
+            return;
+        }
+        if name.is_empty() {
+            let kind = ErrorKind::Syntax("expected macro name".to_string());
+            self.error(kind, origin.span(column, column + ".macro".len()), &origin);
+            return;
+        }
+        let span = origin.span(name_column, name_column + name.len());
+        if !closed {
+            self.error(ErrorKind::UnclosedMacro(name.to_string()), span, &origin);
+            return;
+        }
+        if self.macros.contains_key(name) {
+            self.error(ErrorKind::DuplicateSymbol(name.to_string()), span, &origin);
+            return;
+        }
+        let labels = body
+            .iter()
+            .flat_map(|(_, line)| local_label_definitions(strip_comment(line)))
+            .collect();
+        let definition = Macro {
+            file: origin.file,
+            parameters,
+            body,
+            labels,
+        };
+        self.macros.insert(name.to_string(), Rc::new(definition));
+    }
+
+    /// Add `line` to the output, expanding it first if it invokes a macro,
+    /// unless it lies in a conditional branch that is not kept.
+    fn process(&mut self, line: String, origin: Origin) {
+        let (column, word) = first_word(&line);
+        let directive = word.to_lowercase();
+        if !self.repeats.is_empty() {
+            self.collect(line, origin, &directive);
+            return;
+        }
+        match directive.as_str() {
+            ".if" | ".ifdef" | ".ifndef" => {
+                self.open_condition(&directive, &line, column, origin);
+                return;
+            }
+            ".elif" | ".else" | ".endif" => {
+                self.continue_condition(&directive, &line, column, origin);
+                return;
+            }
+            _ if !self.active() => return,
+            ".rept" => {
+                self.open_repeat(&line, column, origin);
+                return;
+            }
+            ".endr" => {
+                let kind = ErrorKind::Syntax("`.endr` without `.rept`".to_string());
+                self.error(kind, origin.span(column, column + word.len()), &origin);
+                return;
+            }
+            ".include" => {
+                self.include(&line, column, origin);
+                return;
+            }
+            ".equ" | ".set" => self.define_constant(&line, column),
+            _ => {}
+        }
+        let Some(definition) = self.macros.get(word).cloned() else {
+            self.lines.push(line);
+            self.origins.push(origin);
+            return;
+        };
+        let code = strip_comment(&line).trim_end();
+        let invocation = Expansion {
+            name: word.to_string(),
+            span: origin.span(column, code.len() + 1),
+            file: origin.file.as_deref().map(str::to_string),
+        };
+        let arguments = split_arguments(&code[column - 1 + word.len()..]);
+        if arguments.len() != definition.parameters.len() {
+            let kind = ErrorKind::MacroArguments {
+                name: word.to_string(),
+                expected: definition.parameters.len(),
+                found: arguments.len(),
+            };
+            self.error(kind, invocation.span, &origin);
+            return;
+        }
+        if origin.expansions.len() >= MAX_DEPTH {
+            let kind = ErrorKind::MacroRecursion(word.to_string());
+            self.error(kind, invocation.span, &origin);
+            return;
+        }
+        self.expansions += 1;
+        let mut expansions = vec![invocation];
+        expansions.extend(origin.expansions);
+        let blocks = self.open_blocks();
+        for (number, line) in &definition.body {
+            let (line, columns) = substitute(
+                line,
+                &definition.parameters,
+                &arguments,
+                &definition.labels,
+                self.expansions,
+            );
+            let origin = Origin {
+                file: definition.file.clone(),
+                line: *number,
+                columns: Some(columns),
+                expansions: expansions.clone(),
+            };
+            self.process(line, origin);
+        }
+        self.close_blocks(blocks);
+    }
+
+    /// Whether lines are kept, as they are outside conditional branches
+    /// that are not.
+    fn active(&self) -> bool {
+        self.conditions
+            .last()
+            .is_none_or(|condition| condition.active)
+    }
+
+    /// Open the block of the `.if`, `.ifdef` or `.ifndef` on `line`.
+    fn open_condition(&mut self, directive: &str, line: &str, column: usize, origin: Origin) {
+        let span = origin.span(column, column + directive.len());
+        let enclosing = self.active();
+        let keep = enclosing && self.condition(directive, line, column, &origin);
+        self.conditions.push(Condition {
+            opening: Opening::new(directive, span, &origin),
+            active: keep,
+            taken: keep || !enclosing,
+            otherwise: false,
+        });
+    }
+
+    /// Move on to the branch of the innermost conditional block that the
+    /// `.elif` or `.else` on `line` starts, or close it on `.endif`.
+    fn continue_condition(&mut self, directive: &str, line: &str, column: usize, origin: Origin) {
+        let span = origin.span(column, column + directive.len());
+        if self.conditions.len() <= self.floor {
+            let kind = ErrorKind::Syntax(format!("`{}` without `.if`", directive));
+            self.error(kind, span, &origin);
+            return;
+        }
+        if directive != ".elif" {
+            self.expect_end(line, column + directive.len(), directive, &origin);
+        }
+        // The length was checked above.
+        let condition = self.conditions.last_mut().unwrap();
+        if condition.otherwise && directive != ".endif" {
+            let kind = ErrorKind::Syntax(format!("`{}` after `.else`", directive));
+            self.error(kind, span, &origin);
+            return;
+        }
+        match directive {
+            ".else" => {
+                condition.active = !condition.taken;
+                condition.taken = true;
+                condition.otherwise = true;
+            }
+            ".elif" if condition.taken => condition.active = false,
+            ".elif" => {
+                let keep = self.condition(directive, line, column, &origin);
+                let condition = self.conditions.last_mut().unwrap();
+                condition.active = keep;
+                condition.taken = keep;
+            }
+            _ => {
+                self.conditions.pop();
+            }
+        }
+    }
+
+    /// Whether the condition of the `.if`, `.elif`, `.ifdef` or `.ifndef`
+    /// on `line` holds.
+    fn condition(&mut self, directive: &str, line: &str, column: usize, origin: &Origin) -> bool {
+        match directive {
+            ".ifdef" | ".ifndef" => {
+                let message = "expected constant name";
+                let Some(pair) = self.parse_directive(Rule::Defined, line, column, origin, message)
+                else {
+                    return false;
+                };
+                // The pair is a `Defined` and it has a `GlobalLabel` inside.
+                let name = pair.into_inner().nth(1).unwrap().as_str();
+                self.constants.constants.contains_key(name) == (directive == ".ifdef")
+            }
+            _ => {
+                let message = "expected condition";
+                let Some(pair) =
+                    self.parse_directive(Rule::Condition, line, column, origin, message)
+                else {
+                    return false;
+                };
+                let value = pair.into_inner().nth(1).unwrap();
+                self.evaluate(value, column, origin)
+                    .is_some_and(|value| value != 0)
+            }
+        }
+    }
+
+    /// Start collecting the lines of the `.rept` block on `line`.
+    fn open_repeat(&mut self, line: &str, column: usize, origin: Origin) {
+        let span = origin.span(column, column + ".rept".len());
+        let message = "expected repeat count";
+        let count = match self.parse_directive(Rule::Repeat, line, column, &origin, message) {
+            Some(pair) => {
+                let value = pair.into_inner().nth(1).unwrap();
+                let start = column + value.as_span().start();
+                let value_span = origin.span(start, start + value.as_str().len());
+                match self.evaluate(value, column, &origin) {
+                    Some(count) if !(0..=MAX_REPEAT).contains(&count) => {
+                        let kind = ErrorKind::OutOfRange {
+                            value: count,
+                            min: 0,
+                            max: MAX_REPEAT,
+                        };
+                        self.error(kind, value_span, &origin);
+                        0
+                    }
+                    Some(count) => count as usize,
+                    None => 0,
+                }
+            }
+            // Still collect the lines, so they are not kept on their own.
+            None => 0,
+        };
+        self.repeats.push(Repeat {
+            opening: Opening::new(".rept", span, &origin),
+            count,
+            lines: vec![],
+            depth: 0,
+        });
+    }
+
+    /// Add `line` to the innermost `.rept` block, repeating the block when
+    /// `line` closes it.
+    fn collect(&mut self, line: String, origin: Origin, directive: &str) {
+        // Only called while a block is open.
+        let repeat = self.repeats.last_mut().unwrap();
+        match directive {
+            ".rept" => repeat.depth += 1,
+            ".endr" if repeat.depth > 0 => repeat.depth -= 1,
+            ".endr" => {
+                let repeat = self.repeats.pop().unwrap();
+                self.expect_end(&line, first_word(&line).0 + ".endr".len(), ".endr", &origin);
+                self.repeat(repeat);
+                return;
+            }
+            _ => {}
+        }
+        repeat.lines.push((line, origin));
+    }
+
+    /// Process the lines of `repeat` once per repetition, giving the local
+    /// labels defined in them a name unique to each.
+    fn repeat(&mut self, repeat: Repeat) {
+        let labels: Vec<_> = repeat
+            .lines
+            .iter()
+            .flat_map(|(line, _)| local_label_definitions(strip_comment(line)))
+            .collect();
+        for _ in 0..repeat.count {
+            self.expansions += 1;
+            let blocks = self.open_blocks();
+            for (line, origin) in &repeat.lines {
+                let (line, columns) = substitute(line, &[], &[], &labels, self.expansions);
+                let columns = columns.into_iter().map(|column| origin.column(column));
+                let origin = Origin {
+                    columns: Some(columns.collect()),
+                    ..origin.clone()
+                };
+                self.process(line, origin);
+            }
+            self.close_blocks(blocks);
+        }
+    }
+
+    /// Start a file, macro expansion or repetition, whose conditional and
+    /// `.rept` blocks must close within it. Returns what to restore when it
+    /// ends.
+    fn open_blocks(&mut self) -> (usize, usize) {
+        let floor = std::mem::replace(&mut self.floor, self.conditions.len());
+        (floor, self.repeats.len())
+    }
+
+    /// Report the blocks left open at the end of a file, macro expansion or
+    /// repetition, and close them.
+    fn close_blocks(&mut self, (floor, repeats): (usize, usize)) {
+        let conditions = self.conditions.split_off(self.floor);
+        let repeats = self.repeats.split_off(repeats);
+        let openings = conditions
+            .into_iter()
+            .map(|condition| condition.opening)
+            .chain(repeats.into_iter().map(|repeat| repeat.opening));
+        for opening in openings {
+            let kind = ErrorKind::UnclosedBlock(opening.directive);
+            self.push_error(kind, opening.span, &opening.file, &opening.expansions);
+        }
+        self.floor = floor;
+    }
+
+    /// Value of the expression in `pair`, parsed from `column` of the line of
+    /// `origin`, which may only use constants defined before it.
+    fn evaluate(&mut self, pair: Pair<Rule>, column: usize, origin: &Origin) -> Option<i64> {
+        let start = column + pair.as_span().start();
+        let span = origin.span(start, start + pair.as_str().len());
+        let expression = self.constants.parse_expression(pair);
+        let mut resolver = self.constants.lookup(0, SectionKind::Code);
+        let result = expression.evaluate(&mut resolver);
+        let parsed = std::mem::take(&mut self.constants.errors)
+            .into_iter()
+            .next();
+        let error = match (parsed, result) {
+            (None, Ok(value)) => return Some(value),
+            (Some(error), _) | (None, Err(error)) => error,
+        };
+        // The spans of the expression lie in the directive, or in the line
+        // of a constant it uses, so point at the whole expression.
+        let kind = match error.kind {
+            ErrorKind::UnknownLabel(name) => ErrorKind::NotConstant(name),
+            kind => kind,
+        };
+        self.error(kind, span, origin);
+        None
+    }
+
+    /// Define the constant of the `.equ` or `.set` on `line` for the
+    /// conditions after it. The assembler reports any error in it.
+    fn define_constant(&mut self, line: &str, column: usize) {
+        let code = strip_comment(line).trim_end();
+        if let Ok(mut pairs) = KittyAssemblyParser::parse(Rule::Constant, &code[column - 1..]) {
+            // The parse was successful; there is a `Constant`.
+            self.constants.parse_constant(pairs.next().unwrap());
+            self.constants.errors.clear();
+        }
+    }
+
+    /// Parse `rule` from the directive at `column` of `line` to the end of
+    /// its code, or report a syntax error with `message`.
+    fn parse_directive<'a>(
+        &mut self,
+        rule: Rule,
+        line: &'a str,
+        column: usize,
+        origin: &Origin,
+        message: &str,
+    ) -> Option<Pair<'a, Rule>> {
+        let code = strip_comment(line).trim_end();
+        match KittyAssemblyParser::parse(rule, &code[column - 1..]) {
+            Ok(mut pairs) if pairs.as_str().len() == code.len() + 1 - column => pairs.next(),
+            _ => {
+                let kind = ErrorKind::Syntax(message.to_string());
+                self.error(kind, origin.span(column, code.len() + 1), origin);
+                None
+            }
+        }
+    }
+
+    /// Report anything after `directive`, which ends before `column` of
+    /// `line`.
+    fn expect_end(&mut self, line: &str, column: usize, directive: &str, origin: &Origin) {
+        let code = strip_comment(line).trim_end();
+        let rest = &code[column - 1..];
+        if !rest.is_empty() {
+            let start = column + rest.len() - rest.trim_start().len();
+            let kind = ErrorKind::Syntax(format!("`{}` takes no operands", directive));
+            self.error(kind, origin.span(start, code.len() + 1), origin);
+        }
+    }
+
+    /// Add the lines of the file named on the `.include` line `line`.
+    fn include(&mut self, line: &str, column: usize, origin: Origin) {
+        let code = strip_comment(line).trim_end();
+        let span = origin.span(column, code.len() + 1);
+        let message = "expected file name in quotes";
+        let Some(pair) = self.parse_directive(Rule::Include, line, column, &origin, message) else {
+            return;
+        };
+        // The pair is an `Include` and it has a `String` inside.
+        let string = pair.into_inner().nth(1).unwrap();
+        let string = string.as_str();
+        let name = string[1..string.len() - 1].to_string();
+        if self.files.iter().any(|file| **file == name) {
+            self.error(ErrorKind::IncludeCycle(name), span, &origin);
+            return;
+        }
+        let bytes = match resolver::read(self.resolver, &name) {
+            Ok(bytes) => bytes,
+            Err(kind) => {
+                self.error(kind, span, &origin);
+                return;
+            }
+        };
+        let Ok(text) = String::from_utf8(bytes) else {
+            let kind = ErrorKind::UnreadableFile {
+                name,
+                reason: "not valid UTF-8".to_string(),
+            };
+            self.error(kind, span, &origin);
+            return;
+        };
+        let file: Rc<str> = name.into();
+        self.files.push(file.clone());
+        self.source(&text, Some(file), &origin.expansions);
+        self.files.pop();
+    }
+
+    fn error(&mut self, kind: ErrorKind, span: Span, origin: &Origin) {
+        self.push_error(kind, span, &origin.file, &origin.expansions);
+    }
+
+    fn push_error(
+        &mut self,
+        kind: ErrorKind,
+        span: Span,
+        file: &Option<Rc<str>>,
+        expansions: &[Expansion],
+    ) {
+        let mut error = AssembleError::new(kind, span);
+        error.file = file.as_deref().map(str::to_string);
+        error.expansions = expansions.to_vec();
+        self.errors.push(error);
+    }
+}
+
+/// Column and text of the first word on a line, ignoring comments.
+pub(crate) fn first_word(line: &str) -> (usize, &str) {
+    let code = strip_comment(line);
+    let trimmed = code.trim_start();
+    let column = code.len() - trimmed.len() + 1;
+    let length = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    (column, &trimmed[..length])
+}
+
+/// Split macro parameters or arguments on the commas outside parentheses
+/// and quotes.
+pub(crate) fn split_arguments(text: &str) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return vec![];
+    }
+    let mut arguments = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, character) in text.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quote.is_some() => escaped = true,
+            '"' | '\'' if quote == Some(character) => quote = None,
+            '"' | '\'' if quote.is_none() => quote = Some(character),
+            _ if quote.is_some() => {}
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(text[start..index].trim().to_string());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(text[start..].trim().to_string());
+    arguments
+}
+
+fn is_identifier_character(character: char) -> bool {
+    !character.is_whitespace() && !".~:;\"',()+-*/%<>&|^=\\".contains(character)
+}
+
+/// Local labels defined on a line, like `.loop` in `.loop:`.
+fn local_label_definitions(line: &str) -> Vec<String> {
+    let mut labels = vec![];
+    for (index, _) in line.match_indices('.') {
+        let name = &line[index + 1..];
+        let length = name
+            .find(|character| !is_identifier_character(character))
+            .unwrap_or(name.len());
+        let preceded = line[..index].chars().next_back();
+        if length > 0
+            && name[length..].starts_with(':')
+            && !preceded.is_some_and(is_identifier_character)
+        {
+            labels.push(line[index..index + 1 + length].to_string());
+        }
+    }
+    labels
+}
+
+/// Substitute `\parameter` with its argument, `\@` with the number of the
+/// expansion and give the local `labels` of a macro or `.rept` block a name
+/// unique to the expansion. Returns the line with the column every byte
+/// came from.
+fn substitute(
+    line: &str,
+    parameters: &[String],
+    arguments: &[String],
+    labels: &[String],
+    expansion: usize,
+) -> (String, Vec<usize>) {
+    let mut output = String::new();
+    let mut columns = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    let mut index = 0;
+    let code_length = strip_comment(line).len();
+    while index < line.len() {
+        let rest = &line[index..];
+        let character = rest.chars().next().unwrap();
+        let column = index + 1;
+        let identifier_length = |text: &str| {
+            text.find(|character| !is_identifier_character(character))
+                .unwrap_or(text.len())
+        };
+        // Leave string and character literals and comments as they are.
+        let literal = quote.is_some() || index >= code_length;
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quote.is_some() => escaped = true,
+            '"' | '\'' if quote == Some(character) => quote = None,
+            '"' | '\'' if quote.is_none() && !literal => quote = Some(character),
+            _ => {}
+        }
+        let replacement = match character {
+            _ if literal => None,
+            '\\' if rest[1..].starts_with('@') => Some((2, expansion.to_string())),
+            '\\' => {
+                let name = &rest[1..1 + identifier_length(&rest[1..])];
+                parameters
+                    .iter()
+                    .position(|parameter| parameter == name)
+                    .map(|position| (1 + name.len(), arguments[position].clone()))
+            }
+            '.' => {
+                let length = 1 + identifier_length(&rest[1..]);
+                let label = &rest[..length];
+                let previous = line[..index].chars().next_back();
+                let scoped = previous.is_some_and(is_identifier_character)
+                    || previous == Some('~')
+                        && line[..index - 1]
+                            .chars()
+                            .next_back()
+                            .is_some_and(is_identifier_character);
+                (!scoped && labels.iter().any(|defined| defined == label))
+                    .then(|| (length, format!("{}@{}", label, expansion)))
+            }
+            _ => None,
+        };
+        let (length, text) = replacement.unwrap_or_else(|| {
+            let length = character.len_utf8();
+            (length, rest[..length].to_string())
+        });
+        columns.extend(std::iter::repeat(column).take(text.len()));
+        output.push_str(&text);
+        index += length;
+    }
+    (output, columns)
+}
+
+impl Opening {
+    fn new(directive: &str, span: Span, origin: &Origin) -> Self {
+        Self {
+            directive: directive.to_string(),
+            span,
+            file: origin.file.clone(),
+            expansions: origin.expansions.clone(),
+        }
+    }
+}
+
+fn token_span(line: usize, column: usize, length: usize) -> Span {
+    let start = Location { line, column };
+    let end = Location {
+        line,
+        column: column + length,
+    };
+    Span { start, end }
+}
@@ -0,0 +1,68 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use crate::ErrorKind;
+
+/// Reads the files that `.include` and `.incbin` name.
+pub trait SourceResolver {
+    /// Contents of the file `name`, as written in the directive.
+    fn read(&self, name: &str) -> io::Result<Vec<u8>>;
+}
+
+/// Reads files from the filesystem, relative to a directory.
+pub struct FileResolver {
+    root: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl SourceResolver for FileResolver {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.root.join(name))
+    }
+}
+
+/// Serves files from memory, for targets without a filesystem and for tests.
+#[derive(Default)]
+pub struct MemoryResolver {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(name.into(), contents.into());
+    }
+}
+
+impl SourceResolver for MemoryResolver {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        match self.files.get(name) {
+            Some(contents) => Ok(contents.clone()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such file")),
+        }
+    }
+}
+
+/// Read `name` through `resolver`, describing why it cannot be read.
+pub(crate) fn read(
+    resolver: Option<&dyn SourceResolver>,
+    name: &str,
+) -> Result<Vec<u8>, ErrorKind> {
+    let unreadable = |reason: String| ErrorKind::UnreadableFile {
+        name: name.to_string(),
+        reason,
+    };
+    match resolver {
+        Some(resolver) => resolver
+            .read(name)
+            .map_err(|error| unreadable(error.to_string())),
+        None => Err(unreadable("no resolver was given for files".to_string())),
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use common::{Format, Op};
+use pest::{error::LineColLocation, iterators::Pair, Parser};
+
+use crate::{AssembleError, ErrorKind, KittyAssemblyParser, Location, Rule, Span};
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register,
+    Value,
+    Label,
+    Name,
+}
+
+impl Operand {
+    fn matches(self, text: &str, aliases: &HashMap<String, u32>) -> bool {
+        let alias = aliases.contains_key(text);
+        match self {
+            Operand::Register => matches(Rule::Register, text) || alias,
+            // Registers would otherwise be taken for label names.
+            Operand::Value => {
+                matches(Rule::Expression, text) && !matches(Rule::Register, text) && !alias
+            }
+            Operand::Label => [Rule::RelativeLabelReference, Rule::AbsoluteLabelReference]
+                .into_iter()
+                .any(|rule| matches(rule, text)),
+            Operand::Name => matches(Rule::GlobalLabel, text),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Operand::Register => "register",
+            Operand::Value => "expression",
+            Operand::Label => "label",
+            Operand::Name => "name",
+        }
+    }
+}
+
+/// Whether `rule` matches the whole of `text`.
+fn matches(rule: Rule, text: &str) -> bool {
+    match KittyAssemblyParser::parse(rule, text) {
+        Ok(pairs) => pairs.as_str().len() == text.len(),
+        Err(_) => false,
+    }
+}
+
+/// Describe why a line starting at `start` is not a valid statement,
+/// spanning the offending token where possible. `aliases` are the register
+/// aliases in scope.
+pub(crate) fn diagnose(
+    line: &str,
+    start: Location,
+    aliases: &HashMap<String, u32>,
+) -> AssembleError {
+    let code = strip_comment(line).trim_end();
+    let mnemonic_length = code
+        .find(|character: char| character.is_whitespace())
+        .unwrap_or(code.len());
+    let mnemonic = &code[..mnemonic_length];
+    let operands = if let Some((op, _)) = Op::parse(mnemonic) {
+        match op.format() {
+            Format::L => vec![Operand::Register, Operand::Value],
+            Format::I => vec![Operand::Register, Operand::Register, Operand::Value],
+            Format::R => vec![Operand::Register, Operand::Register, Operand::Register],
+        }
+    } else if matches(Rule::PseudoOpL, mnemonic) {
+        vec![Operand::Register, Operand::Value]
+    } else if matches(Rule::PseudoOpV, mnemonic) {
+        vec![Operand::Value]
+    } else if matches(Rule::PseudoOpLabel, mnemonic) {
+        vec![Operand::Label]
+    } else if matches(Rule::PseudoOpRR, mnemonic) {
+        vec![Operand::Register, Operand::Register]
+    } else if matches(Rule::PseudoOpR, mnemonic) {
+        vec![Operand::Register]
+    } else if matches(Rule::PseudoOp, mnemonic) {
+        vec![]
+    } else if matches(Rule::ConstantDirective, mnemonic) {
+        vec![Operand::Name, Operand::Value]
+    } else if matches(Rule::PlacementDirective, mnemonic)
+        || matches(Rule::FixedFormatDirective, mnemonic)
+    {
+        vec![Operand::Value]
+    } else if matches(Rule::FillDirective, mnemonic) {
+        vec![Operand::Value, Operand::Value]
+    } else if matches(Rule::SectionDirective, mnemonic) {
+        vec![]
+    } else if matches(Rule::LinkageDirective, mnemonic) || matches(Rule::UnaliasDirective, mnemonic)
+    {
+        // Any number of names, but at least one.
+        let names = strip_comment(&code[mnemonic_length..]).split(',').count();
+        vec![Operand::Name; names]
+    } else if matches(Rule::DataInstruction, mnemonic)
+        || matches(Rule::TextDirective, mnemonic)
+        || matches(Rule::CharmapDirective, mnemonic)
+        || matches(Rule::AliasDirective, mnemonic)
+        || matches(Rule::LayoutDirective, mnemonic)
+        || matches(Rule::EndLayoutDirective, mnemonic)
+        || matches(Rule::ReserveDirective, mnemonic)
+        || matches(Rule::IncludeBinaryDirective, mnemonic)
+        || code.contains(':')
+    {
+        return pest_error(line, start);
+    } else {
+        let kind = ErrorKind::Syntax(format!("unknown instruction `{}`", mnemonic));
+        return AssembleError::new(kind, token_span(start, 0, mnemonic.len()));
+    };
+
+    // Split the operands on commas, keeping track of their columns.
+    let mut found = vec![];
+    let mut offset = mnemonic_length;
+    for operand in code[mnemonic_length..].split(',') {
+        let trimmed = operand.trim();
+        let leading = operand.len() - operand.trim_start().len();
+        found.push((offset + leading, trimmed));
+        offset += operand.len() + 1;
+    }
+    if found.len() == 1 && found[0].1.is_empty() {
+        found.clear();
+    }
+
+    if found.len() < operands.len() {
+        let kind = ErrorKind::Syntax(format!(
+            "`{}` expects {} operands, found {}",
+            mnemonic,
+            operands.len(),
+            found.len()
+        ));
+        return AssembleError::new(kind, token_span(start, code.len(), 1));
+    }
+    if found.len() > operands.len() {
+        let (column, _) = found[operands.len()];
+        let kind = ErrorKind::Syntax(format!(
+            "`{}` expects {} operands, found {}",
+            mnemonic,
+            operands.len(),
+            found.len()
+        ));
+        return AssembleError::new(kind, token_span(start, column, code.len() - column));
+    }
+    for (operand, (column, text)) in operands.into_iter().zip(found) {
+        if !operand.matches(text, aliases) {
+            let kind = ErrorKind::Syntax(match text {
+                "" => format!("expected {}", operand.describe()),
+                _ => format!("expected {}, found `{}`", operand.describe(), text),
+            });
+            return AssembleError::new(kind, token_span(start, column, text.len().max(1)));
+        }
+    }
+    pest_error(line, start)
+}
+
+/// Parse `line` on its own and report where pest gave up.
+fn pest_error(line: &str, start: Location) -> AssembleError {
+    match KittyAssemblyParser::parse(Rule::Line, line) {
+        Err(error) => from_pest(error, start),
+        Ok(_) => {
+            let kind = ErrorKind::Syntax(format!("unexpected `{}`", line));
+            AssembleError::new(kind, token_span(start, 0, line.len()))
+        }
+    }
+}
+
+/// Convert a pest error into an error spanning the offending token,
+/// offset by `start`, the position of the parsed text within the source.
+pub(crate) fn from_pest(error: pest::error::Error<Rule>, start: Location) -> AssembleError {
+    let message = error.variant.message().to_string();
+    let (line, column) = match error.line_col {
+        LineColLocation::Pos(position) => position,
+        LineColLocation::Span(start, _) => start,
+    };
+    let token = error
+        .line()
+        .chars()
+        .skip(column - 1)
+        .take_while(|character| !character.is_whitespace() && *character != ',')
+        .count()
+        .max(1);
+    let column = match line {
+        1 => start.column + column - 1,
+        _ => column,
+    };
+    let line = start.line + line - 1;
+    let start = Location { line, column };
+    let end = Location {
+        line,
+        column: column + token,
+    };
+    AssembleError::new(ErrorKind::Syntax(message), Span { start, end })
+}
+
+fn token_span(start: Location, offset: usize, length: usize) -> Span {
+    let column = start.column + offset;
+    let start = Location {
+        line: start.line,
+        column,
+    };
+    let end = Location {
+        line: start.line,
+        column: column + length,
+    };
+    Span { start, end }
+}
+
+/// Span of the expression in `pair`, which the grammar lets run on
+/// through the whitespace and comments after it, up to its last token or
+/// closing parenthesis.
+pub(crate) fn expression_span<'i>(pair: &Pair<'i, Rule>) -> pest::Span<'i> {
+    let span = pair.as_span();
+    let Some(last) = pair.clone().into_inner().flatten().last() else {
+        return span;
+    };
+    let start = last.as_span().end();
+    // Closing parentheses are no tokens of their own.
+    let rest = &pair.get_input()[start..span.end()];
+    let mut end = start;
+    let mut comment = false;
+    for (index, character) in rest.char_indices() {
+        match character {
+            '\n' => comment = false,
+            _ if comment => {}
+            ';' => comment = true,
+            ')' => end = start + index + 1,
+            _ => {}
+        }
+    }
+    pest::Span::new(pair.get_input(), span.start(), end).unwrap()
+}
+
+/// Remove a trailing `;` comment, ignoring semicolons within strings and
+/// character literals.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quote.is_some() => escaped = true,
+            '"' | '\'' if quote == Some(character) => quote = None,
+            '"' | '\'' if quote.is_none() => quote = Some(character),
+            ';' if quote.is_none() => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+/// A character of a string or character literal, or a byte given by a
+/// `\xNN` escape, which no character map changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Unit {
+    Character(char),
+    Byte(u8),
+}
+
+/// Decode the escapes in `text`, the contents of a string or character
+/// literal as the grammar accepts them. A `\u{...}` escape that is not a
+/// character fails with its offset and length.
+pub(crate) fn unescape(text: &str) -> Result<Vec<Unit>, (usize, usize)> {
+    let mut units = vec![];
+    let mut characters = text.char_indices();
+    while let Some((index, character)) = characters.next() {
+        if character != '\\' {
+            units.push(Unit::Character(character));
+            continue;
+        }
+        // The grammar only accepts complete escapes.
+        let unit = match characters.next().unwrap().1 {
+            'n' => Unit::Character('\n'),
+            't' => Unit::Character('\t'),
+            '0' => Unit::Character('\0'),
+            'x' => {
+                characters.nth(1);
+                Unit::Byte(u8::from_str_radix(&text[index + 2..index + 4], 16).unwrap())
+            }
+            'u' => {
+                let (end, _) = characters.find(|&(_, character)| character == '}').unwrap();
+                u32::from_str_radix(&text[index + 3..end], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(Unit::Character)
+                    .ok_or((index, end + 1 - index))?
+            }
+            character => Unit::Character(character),
+        };
+        units.push(unit);
+    }
+    Ok(units)
+}
+
+/// Bytes that `.charmap` gives characters in the strings after it.
+#[derive(Default)]
+pub(crate) struct Charmap {
+    bytes: HashMap<char, u8>,
+}
+
+impl Charmap {
+    pub(crate) fn insert(&mut self, character: char, byte: u8) {
+        self.bytes.insert(character, byte);
+    }
+
+    /// Bytes of `units`, with mapped characters as their byte, other
+    /// characters in UTF-8 and escaped bytes as they are.
+    pub(crate) fn encode(&self, units: &[Unit]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for unit in units {
+            match unit {
+                Unit::Byte(byte) => bytes.push(*byte),
+                Unit::Character(character) => match self.bytes.get(character) {
+                    Some(&byte) => bytes.push(byte),
+                    None => {
+                        let mut buffer = [0; 4];
+                        bytes.extend(character.encode_utf8(&mut buffer).bytes());
+                    }
+                },
+            }
+        }
+        bytes
+    }
+}
@@ -0,0 +1,56 @@
+/// What programs do with an IO register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// A memory-mapped IO register, which the assembler predefines as
+/// `IO.NAME`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoRegister {
+    pub name: &'static str,
+    pub address: u32,
+    /// Number of bytes, read and written as a big-endian number.
+    pub width: u32,
+    pub access: Access,
+    pub description: &'static str,
+}
+
+/// Define a constant for the address of each register and `REGISTERS`
+/// from one row per register, so the virtual machine and the assembler
+/// agree on them.
+macro_rules! registers {
+    ($($name:ident = $address:literal, $width:literal, $access:ident, $description:literal;)*) => {
+        $(
+            #[doc = $description]
+            pub const $name: u32 = $address;
+        )*
+
+        /// Every IO register, in order of address.
+        pub const REGISTERS: &[IoRegister] = &[$(
+            IoRegister {
+                name: stringify!($name),
+                address: $address,
+                width: $width,
+                access: Access::$access,
+                description: $description,
+            },
+        )*];
+    };
+}
+
+registers! {
+    COMPOSITE_SRC_ADDR = 0xF90000, 3, Write, "Address of the image to composite.";
+    COMPOSITE_SRC_WIDTH = 0xF90003, 3, Write, "Width of the image to composite, in pixels.";
+    COMPOSITE_SRC_HEIGHT = 0xF90006, 3, Write, "Height of the image to composite, in pixels.";
+    COMPOSITE_SRC_STRIDE = 0xF90009, 3, Write, "Bytes from one row of the image to composite to the next.";
+    COMPOSITE_DST_P0 = 0xF90010, 3, Write, "Index of the framebuffer pixel the top left corner of the image goes to.";
+    COMPOSITE_DST_P1 = 0xF90013, 3, Write, "Index of the framebuffer pixel the top right corner of the image goes to.";
+    COMPOSITE_DST_P2 = 0xF90016, 3, Write, "Index of the framebuffer pixel the bottom right corner of the image goes to.";
+    COMPOSITE_DST_P3 = 0xF90019, 3, Write, "Index of the framebuffer pixel the bottom left corner of the image goes to.";
+    COMPOSITE_MODE = 0xF9001C, 3, Write, "Composites the image onto the framebuffer in the mode written to it.";
+    AUDIO_NOTE = 0xFA0003, 2, Write, "MIDI note of the square wave, in semitones and 256ths of one.";
+    FRAMEBUFFER = 0xFB0000, 4, ReadWrite, "First of the 320 by 180 pixels, row by row, as red, green, blue and alpha bytes.";
+}
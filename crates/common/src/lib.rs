@@ -1,84 +1,194 @@
+pub mod io;
+
 pub const REGISTER_COUNT: usize = 0x40;
 
 pub const REGISTER_PROGRAM_COUNTER: u32 = 0x3F;
 pub const REGISTER_GLOBAL: u32 = 0x0;
 pub const REGISTER_INTERRUPT: u32 = 0x3E;
+/// Return address written by the `call` pseudo-instruction.
+pub const REGISTER_LINK: u32 = 0x3C;
+/// Scratch register clobbered by pseudo-instructions.
+pub const REGISTER_TEMPORARY: u32 = 0x3D;
 
 pub const INTERRUPT_VBLANK: u32 = 0x0000_04;
 
-#[derive(Clone, Copy)]
-pub enum Op {
-    Let = 0o00,
-    Lethi = 0o01,
-    Shri = 0o02,
-    Shli = 0o03,
-    Slessi = 0o04,
-    Load = 0o05,
-    Load2 = 0o06,
-    Load3 = 0o07,
-    Ashr = 0o10,
-    Rol = 0o11,
-    Shr = 0o12,
-    Shl = 0o13,
-    Sless = 0o14,
-    Store = 0o15,
-    Store2 = 0o16,
-    Store3 = 0o17,
-    Ori = 0o20,
-    Nori = 0o21,
-    Andi = 0o22,
-    Xori = 0o23,
-    Lessi = 0o24,
-    Addi = 0o25,
-    Subi = 0o26,
-    Muli = 0o27,
-    Or = 0o30,
-    Nor = 0o31,
-    And = 0o32,
-    Xor = 0o33,
-    Less = 0o34,
-    Add = 0o35,
-    Sub = 0o36,
-    Mul = 0o37,
+/// How the 18 bits after the opcode are split into operands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Register `r` and a 12-bit immediate.
+    L,
+    /// Registers `r` and `s` and a 6-bit immediate.
+    I,
+    /// Registers `r`, `s` and `t`.
+    R,
+}
+
+/// Define `Op` from one row per opcode, so the opcode, mnemonic and format
+/// of an instruction are only written once.
+macro_rules! ops {
+    ($($op:ident = $opcode:literal, $mnemonic:literal, $format:ident;)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Op {
+            $($op = $opcode,)*
+        }
+
+        impl Op {
+            /// Every op, in order of opcode.
+            pub const ALL: [Op; 32] = [$(Op::$op,)*];
+
+            pub fn mnemonic(self) -> &'static str {
+                match self {
+                    $(Op::$op => $mnemonic,)*
+                }
+            }
+
+            pub fn format(self) -> Format {
+                match self {
+                    $(Op::$op => Format::$format,)*
+                }
+            }
+        }
+    };
 }
 
-impl From<u32> for Op {
-    fn from(value: u32) -> Self {
+ops! {
+    Let = 0o00, "let", L;
+    Lethi = 0o01, "lethi", L;
+    Shri = 0o02, "shri", I;
+    Shli = 0o03, "shli", I;
+    Slessi = 0o04, "slessi", I;
+    Load = 0o05, "load", I;
+    Load2 = 0o06, "load2", I;
+    Load3 = 0o07, "load3", I;
+    Ashr = 0o10, "ashr", R;
+    Rol = 0o11, "rol", R;
+    Shr = 0o12, "shr", R;
+    Shl = 0o13, "shl", R;
+    Sless = 0o14, "sless", R;
+    Store = 0o15, "store", I;
+    Store2 = 0o16, "store2", I;
+    Store3 = 0o17, "store3", I;
+    Ori = 0o20, "ori", I;
+    Nori = 0o21, "nori", I;
+    Andi = 0o22, "andi", I;
+    Xori = 0o23, "xori", I;
+    Lessi = 0o24, "lessi", I;
+    Addi = 0o25, "addi", I;
+    Subi = 0o26, "subi", I;
+    Muli = 0o27, "muli", I;
+    Or = 0o30, "or", R;
+    Nor = 0o31, "nor", R;
+    And = 0o32, "and", R;
+    Xor = 0o33, "xor", R;
+    Less = 0o34, "less", R;
+    Add = 0o35, "add", R;
+    Sub = 0o36, "sub", R;
+    Mul = 0o37, "mul", R;
+}
+
+impl Op {
+    /// The op named `mnemonic`, ignoring case, and whether it is the
+    /// conditional variant prefixed with `c`.
+    pub fn parse(mnemonic: &str) -> Option<(Op, bool)> {
+        let mnemonic = mnemonic.to_lowercase();
+        let find = |mnemonic: &str| Op::ALL.into_iter().find(|op| op.mnemonic() == mnemonic);
+        match find(&mnemonic) {
+            Some(op) => Some((op, false)),
+            None => find(mnemonic.strip_prefix('c')?).map(|op| (op, true)),
+        }
+    }
+
+    /// Whether the immediate is an offset, sign extended from 6 bits.
+    pub fn is_offset(self) -> bool {
         use Op::*;
-        match value {
-            0o00 => Let,
-            0o01 => Lethi,
-            0o02 => Shri,
-            0o03 => Shli,
-            0o04 => Slessi,
-            0o05 => Load,
-            0o06 => Load2,
-            0o07 => Load3,
-            0o10 => Ashr,
-            0o11 => Rol,
-            0o12 => Shr,
-            0o13 => Shl,
-            0o14 => Sless,
-            0o15 => Store,
-            0o16 => Store2,
-            0o17 => Store3,
-            0o20 => Ori,
-            0o21 => Nori,
-            0o22 => Andi,
-            0o23 => Xori,
-            0o24 => Lessi,
-            0o25 => Addi,
-            0o26 => Subi,
-            0o27 => Muli,
-            0o30 => Or,
-            0o31 => Nor,
-            0o32 => And,
-            0o33 => Xor,
-            0o34 => Less,
-            0o35 => Add,
-            0o36 => Sub,
-            0o37 => Mul,
-            _ => panic!("{}: Impossible operation.", value),
+        matches!(self, Load | Load2 | Load3 | Store | Store2 | Store3)
+    }
+
+    /// Whether the instruction sets the condition that conditional
+    /// instructions run on.
+    pub fn sets_condition(self) -> bool {
+        use Op::*;
+        matches!(
+            self,
+            Slessi
+                | Sless
+                | Ori
+                | Nori
+                | Andi
+                | Xori
+                | Lessi
+                | Addi
+                | Subi
+                | Muli
+                | Or
+                | Nor
+                | And
+                | Xor
+                | Less
+                | Add
+                | Sub
+                | Mul
+        )
+    }
+}
+
+/// An instruction word split into its fields: `c(1) op(5) r(6) s(6) u(6)`,
+/// where `L` instructions use `s` and `u` as one 12-bit immediate and `R`
+/// instructions name a third register with `u`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    /// Only execute when the condition is set.
+    pub conditional: bool,
+    pub op: Op,
+    pub r: u32,
+    /// Always 0 for `L` instructions.
+    pub s: u32,
+    /// The immediate, or register `t` of `R` instructions.
+    pub u: u32,
+}
+
+impl Instruction {
+    /// Split `word` into fields. Bits above the lowest 24 are ignored.
+    pub fn decode(word: u32) -> Self {
+        let op = Op::ALL[(word >> 18 & 0o37) as usize];
+        let (s, u) = match op.format() {
+            Format::L => (0, word & 0o77_77),
+            Format::I | Format::R => (word >> 6 & 0o77, word & 0o77),
+        };
+        Self {
+            conditional: word >> 23 & 1 != 0,
+            op,
+            r: word >> 12 & 0o77,
+            s,
+            u,
+        }
+    }
+
+    /// Join the fields into a word, dropping the bits that do not fit them.
+    pub fn encode(self) -> u32 {
+        let conditional = (self.conditional as u32) << 23;
+        let opcode = (self.op as u32) << 18;
+        let r = (self.r & 0o77) << 12;
+        let operands = match self.op.format() {
+            Format::L => self.u & 0o77_77,
+            Format::I | Format::R => (self.s & 0o77) << 6 | self.u & 0o77,
+        };
+        conditional | opcode | r | operands
+    }
+
+    /// Mnemonic, with a `c` prefix for the conditional variant.
+    pub fn mnemonic(self) -> String {
+        match self.conditional {
+            true => format!("c{}", self.op.mnemonic()),
+            false => self.op.mnemonic().to_string(),
+        }
+    }
+
+    /// The immediate, sign extended for offsets.
+    pub fn immediate(self) -> i32 {
+        match self.op.is_offset() {
+            true => (self.u as i32) << 26 >> 26,
+            false => self.u as i32,
         }
     }
 }
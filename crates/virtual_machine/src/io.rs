@@ -1,10 +0,0 @@
-pub const FRAMEBUFFER: usize = 0xFB0000;
-pub const COMPOSITE_SRC_ADDR: usize = 0xF90000;
-pub const COMPOSITE_SRC_WIDTH: usize = 0xF90003;
-pub const COMPOSITE_SRC_HEIGHT: usize = 0xF90006;
-pub const COMPOSITE_SRC_STRIDE: usize = 0xF90009;
-pub const COMPOSITE_DST_P0: usize = 0xF90010;
-pub const COMPOSITE_DST_P1: usize = 0xF90013;
-pub const COMPOSITE_DST_P2: usize = 0xF90016;
-pub const COMPOSITE_DST_P3: usize = 0xF90019;
-pub const COMPOSITE_MODE: usize = 0xF9001C;
\ No newline at end of file
@@ -1,13 +1,10 @@
 use std::f32::consts::TAU;
 
 mod cpu;
-mod io;
 
 use common::*;
+use common::io::*;
 use cpu::*;
-use io::*;
-
-use crate::io::COMPOSITE_MODE;
 
 const BITS: usize = 24;
 const MASK: usize = 2_usize.pow(BITS as u32) - 1;
@@ -55,6 +52,13 @@ impl VirtualMachine {
         }
     }
 
+    /// Copy `bytes` into memory from `address`, wrapping around the end.
+    pub fn load(&mut self, address: u32, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.ram[(address as usize + offset) & MASK] = *byte;
+        }
+    }
+
     pub fn error(message: String) -> Self {
         let error_message = message.as_bytes().to_vec();
         let ram = vec![0; MEMORY_SIZE];
@@ -80,7 +84,7 @@ impl VirtualMachine {
                 self.step(CYCLES_PER_PIXEL);
                 // This is a video cycle, update the pixel.
                 let color_index = (x + y * WIDTH) * 4;
-                let ram_index = FRAMEBUFFER + color_index;
+                let ram_index = FRAMEBUFFER as usize + color_index;
                 self.video[color_index + 0] = self.ram[ram_index + 0];
                 self.video[color_index + 1] = self.ram[ram_index + 1];
                 self.video[color_index + 2] = self.ram[ram_index + 2];
@@ -149,25 +153,12 @@ impl VirtualMachine {
                 self.ram[program_counter as usize + 1],
                 self.ram[program_counter as usize + 2],
             ]);
-            let c = instruction & 0b1_00000_000000_000000_000000;
-            let c = c != 0;
-            if !c || self.cpu.condition() {
-                let op = instruction & 0b0_11111_000000_000000_000000;
-                let op = op >> 18;
-                let op: Op = op.into();
-                use Op::*;
-                match op {
-                    Shri | Shli | Slessi | Load | Load2 | Load3 | Store | Store2 | Store3 | Ori
-                    | Nori | Andi | Xori | Lessi | Addi | Subi | Muli => {
-                        self.i(op, instruction);
-                    }
-                    Let | Lethi => {
-                        self.l(op, instruction);
-                    }
-                    Ashr | Rol | Shr | Shl | Sless | Or | Nor | And | Xor | Less | Add | Sub
-                    | Mul => {
-                        self.r(op, instruction);
-                    }
+            let instruction = Instruction::decode(instruction);
+            if !instruction.conditional || self.cpu.condition() {
+                match instruction.op.format() {
+                    Format::I => self.i(instruction),
+                    Format::L => self.l(instruction),
+                    Format::R => self.r(instruction),
                 }
             }
         }
@@ -176,7 +167,8 @@ impl VirtualMachine {
     /// Sample audio channels for output buffer.
     fn sample(&mut self, cycle: usize) {
         let sample_index = cycle / CYCLES_PER_SAMPLE;
-        let midi = self.ram[0xFA0003] as f32 + self.ram[0xFA0004] as f32 / 256.0;
+        let note = AUDIO_NOTE as usize;
+        let midi = self.ram[note] as f32 + self.ram[note + 1] as f32 / 256.0;
         let frequency = 2.0_f32.powf((midi - 69.0) / 12.0) * 440.0;
         let increment = frequency * INCREMENT;
         self.sin_phase += increment;
@@ -188,53 +180,53 @@ impl VirtualMachine {
     fn composite(&mut self, mode: u32) {
         let source_address = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_SRC_ADDR + 0],
-            self.ram[COMPOSITE_SRC_ADDR + 1],
-            self.ram[COMPOSITE_SRC_ADDR + 2],
+            self.ram[COMPOSITE_SRC_ADDR as usize + 0],
+            self.ram[COMPOSITE_SRC_ADDR as usize + 1],
+            self.ram[COMPOSITE_SRC_ADDR as usize + 2],
         ]);
         let source_width = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_SRC_WIDTH + 0],
-            self.ram[COMPOSITE_SRC_WIDTH + 1],
-            self.ram[COMPOSITE_SRC_WIDTH + 2],
+            self.ram[COMPOSITE_SRC_WIDTH as usize + 0],
+            self.ram[COMPOSITE_SRC_WIDTH as usize + 1],
+            self.ram[COMPOSITE_SRC_WIDTH as usize + 2],
         ]);
         let source_height = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_SRC_HEIGHT + 0],
-            self.ram[COMPOSITE_SRC_HEIGHT + 1],
-            self.ram[COMPOSITE_SRC_HEIGHT + 2],
+            self.ram[COMPOSITE_SRC_HEIGHT as usize + 0],
+            self.ram[COMPOSITE_SRC_HEIGHT as usize + 1],
+            self.ram[COMPOSITE_SRC_HEIGHT as usize + 2],
         ]);
         let source_stride = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_SRC_STRIDE + 0],
-            self.ram[COMPOSITE_SRC_STRIDE + 1],
-            self.ram[COMPOSITE_SRC_STRIDE + 2],
+            self.ram[COMPOSITE_SRC_STRIDE as usize + 0],
+            self.ram[COMPOSITE_SRC_STRIDE as usize + 1],
+            self.ram[COMPOSITE_SRC_STRIDE as usize + 2],
         ]);
         let destination_p0 = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_DST_P0 + 0],
-            self.ram[COMPOSITE_DST_P0 + 1],
-            self.ram[COMPOSITE_DST_P0 + 2],
+            self.ram[COMPOSITE_DST_P0 as usize + 0],
+            self.ram[COMPOSITE_DST_P0 as usize + 1],
+            self.ram[COMPOSITE_DST_P0 as usize + 2],
         ]);
         let destination_p1 = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_DST_P1 + 0],
-            self.ram[COMPOSITE_DST_P1 + 1],
-            self.ram[COMPOSITE_DST_P1 + 2],
+            self.ram[COMPOSITE_DST_P1 as usize + 0],
+            self.ram[COMPOSITE_DST_P1 as usize + 1],
+            self.ram[COMPOSITE_DST_P1 as usize + 2],
         ]);
         let destination_p2 = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_DST_P2 + 0],
-            self.ram[COMPOSITE_DST_P2 + 1],
-            self.ram[COMPOSITE_DST_P2 + 2],
+            self.ram[COMPOSITE_DST_P2 as usize + 0],
+            self.ram[COMPOSITE_DST_P2 as usize + 1],
+            self.ram[COMPOSITE_DST_P2 as usize + 2],
         ]);
         let destination_p3 = u32::from_be_bytes([
             0,
-            self.ram[COMPOSITE_DST_P3 + 0],
-            self.ram[COMPOSITE_DST_P3 + 1],
-            self.ram[COMPOSITE_DST_P3 + 2],
+            self.ram[COMPOSITE_DST_P3 as usize + 0],
+            self.ram[COMPOSITE_DST_P3 as usize + 1],
+            self.ram[COMPOSITE_DST_P3 as usize + 2],
         ]);
-        let destination_address = FRAMEBUFFER;
+        let destination_address = FRAMEBUFFER as usize;
         // Assuming points go clockwise top-left to bottom-left
         let x0 = destination_p0 % WIDTH as u32;
         let y0 = destination_p0 / WIDTH as u32;
@@ -251,13 +243,9 @@ impl VirtualMachine {
     }
 
     /// Execute immediate instruction.
-    fn i(&mut self, op: Op, instruction: u32) {
-        let r = instruction & 0o77_00_00;
-        let r = r >> 12;
-        let s = instruction & 0o00_77_00;
-        let s = s >> 6;
+    fn i(&mut self, instruction: Instruction) {
+        let Instruction { op, r, s, u, .. } = instruction;
         let s = self.cpu[s];
-        let u = instruction & 0o00_00_77;
         use Op::*;
         match op {
             Shri => {
@@ -272,14 +260,14 @@ impl VirtualMachine {
                 self.cpu.set_condition(s == u);
             }
             Load => {
-                let i = (u << 2) as i8 as i32 >> 2;
+                let i = instruction.immediate();
                 let address = s as i32 + i;
                 // TODO: Add overflow/underflow test.
                 let value = self.ram[address as usize] as u32;
                 self.cpu.set(r, value)
             }
             Load2 => {
-                let i = (u << 2) as i8 as i32 >> 2;
+                let i = instruction.immediate();
                 let address = s as i32 + i;
                 // TODO: Add overflow/underflow test.
                 let value = u32::from_be_bytes([
@@ -291,7 +279,7 @@ impl VirtualMachine {
                 self.cpu.set(r, value)
             }
             Load3 => {
-                let i = (u << 2) as i8 as i32 >> 2;
+                let i = instruction.immediate();
                 let address = s as i32 + i;
                 // TODO: Add overflow/underflow test.
                 let value = u32::from_be_bytes([
@@ -303,14 +291,14 @@ impl VirtualMachine {
                 self.cpu.set(r, value)
             }
             Store => {
-                let i = (u << 2) as i8 as i32 >> 2;
+                let i = instruction.immediate();
                 let address = self.cpu[r] as i32 + i;
                 let address = address as usize;
                 // TODO: Add overflow/underflow test.
                 self.ram[address] = s as u8;
             }
             Store2 => {
-                let i = (u << 2) as i8 as i32 >> 2;
+                let i = instruction.immediate();
                 let address = self.cpu[r] as i32 + i;
                 // TODO: Add overflow/underflow test.
                 let [_, _, a, b] = s.to_be_bytes();
@@ -318,7 +306,7 @@ impl VirtualMachine {
                 self.ram[address as usize + 1] = b;
             }
             Store3 => {
-                let i = (u << 2) as i8 as i32 >> 2;
+                let i = instruction.immediate();
                 let address = self.cpu[r] as i32 + i;
                 let address = address as usize;
                 // TODO: Add overflow/underflow test.
@@ -326,9 +314,8 @@ impl VirtualMachine {
                 self.ram[address + 0] = a;
                 self.ram[address + 1] = b;
                 self.ram[address + 2] = c;
-                match address {
-                    COMPOSITE_MODE => self.composite(s),
-                    _ => {}
+                if address == COMPOSITE_MODE as usize {
+                    self.composite(s);
                 }
             }
             Ori => {
@@ -371,10 +358,8 @@ impl VirtualMachine {
     }
 
     /// Execute let instruction.
-    fn l(&mut self, op: Op, instruction: u32) {
-        let r = instruction & 0o77_00_00;
-        let r = r >> 12;
-        let u = instruction & 0o00_77_77;
+    fn l(&mut self, instruction: Instruction) {
+        let Instruction { op, r, u, .. } = instruction;
         match op {
             Op::Let => self.cpu.set(r, u),
             Op::Lethi => {
@@ -386,13 +371,9 @@ impl VirtualMachine {
         }
     }
 
-    fn r(&mut self, op: Op, instruction: u32) {
-        let r = instruction & 0o77_00_00;
-        let r = r >> 12;
-        let s = instruction & 0o00_77_00;
-        let s = s >> 6;
+    fn r(&mut self, instruction: Instruction) {
+        let Instruction { op, r, s, u: t, .. } = instruction;
         let s = self.cpu[s];
-        let t = instruction & 0o00_00_77;
         let t = self.cpu[t];
         use Op::*;
         match op {
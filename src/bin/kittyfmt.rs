@@ -0,0 +1,77 @@
+use std::{
+    env, fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use assembler::format;
+
+/// Format the kittyasm files named on the command line in place, or
+/// standard input to standard output when none are. With `--check`, only
+/// name the files that are not formatted and fail when there are any.
+fn main() -> ExitCode {
+    let mut check = false;
+    let mut files = vec![];
+    for argument in env::args().skip(1) {
+        match argument.as_str() {
+            "--check" => check = true,
+            _ => files.push(argument),
+        }
+    }
+
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(error) = io::stdin().read_to_string(&mut source) {
+            eprintln!("cannot read standard input: {}", error);
+            return ExitCode::FAILURE;
+        }
+        return match format(&source) {
+            Ok(formatted) if check && formatted != source => {
+                println!("<stdin>");
+                ExitCode::FAILURE
+            }
+            Ok(_) if check => ExitCode::SUCCESS,
+            Ok(formatted) => {
+                print!("{}", formatted);
+                ExitCode::SUCCESS
+            }
+            Err(errors) => {
+                eprintln!("{}", errors);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("cannot read `{}`: {}", file, error);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let formatted = match format(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}:{}", file, error);
+                }
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            status = ExitCode::FAILURE;
+        } else if let Err(error) = fs::write(&file, formatted) {
+            eprintln!("cannot write `{}`: {}", file, error);
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
@@ -7,7 +7,7 @@ fn virtual_machine() -> *mut VirtualMachine {
     let assembly = include_str!("boot.kittyasm");
     let rom = match Assembler::assemble(assembly) {
         Ok(rom) => rom,
-        Err(error) => return Box::into_raw(Box::new(VirtualMachine::error(error))),
+        Err(error) => return Box::into_raw(Box::new(VirtualMachine::error(error.to_string()))),
     };
     Box::into_raw(Box::new(VirtualMachine::new(rom)))
 }
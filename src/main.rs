@@ -1,11 +1,16 @@
-use assembler::Assembler;
+use assembler::{Assembler, FileResolver};
 use virtual_machine::VirtualMachine;
 
 fn main() {
     let assembly = include_str!("boot.kittyasm");
-    match Assembler::assemble(assembly) {
-        Ok(rom) => {
-            let mut virtual_machine = VirtualMachine::new(rom);
+    // Files included by the boot program are next to it.
+    let resolver = FileResolver::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+    match Assembler::default().with_resolver(resolver).build(assembly) {
+        Ok(assembly) => {
+            let mut virtual_machine = VirtualMachine::new(vec![]);
+            for segment in &assembly.segments {
+                virtual_machine.load(segment.address, &segment.bytes);
+            }
             for _ in 0..60 {
                 virtual_machine.run();
             }
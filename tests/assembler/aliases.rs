@@ -0,0 +1,180 @@
+mod names {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn stand_for_registers() {
+        let [_, r1, r2, r3, ..] = run_virtual_machine(
+            r"
+            main:
+                .alias  x = r1
+                .alias  y = r2
+                .alias  sum = r3
+                let     x, 17
+                addi    y, x, 3
+                add     sum, x, y
+        ",
+        );
+        assert_eq!([r1, r2, r3], [17, 20, 37]);
+    }
+
+    #[test]
+    fn assemble_to_same_code_as_registers() {
+        let aliased = Assembler::assemble(
+            r"
+            .alias  count = r4
+            .alias  total = r5
+            letall  count, 0x123456
+            mov     total, count
+            push    total
+        ",
+        )
+        .unwrap();
+        let written = Assembler::assemble("letall r4, 0x123456\nmov r5, r4\npush r5").unwrap();
+        assert_eq!(aliased, written);
+    }
+}
+
+mod scopes {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn end_at_next_global_label() {
+        let errors = Assembler::assemble(
+            r"
+            first:
+                .alias  x = r1
+                let     x, 1
+            second:
+                let     x, 2
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::Syntax("expected register, found `x`".to_string())
+        );
+    }
+
+    #[test]
+    fn local_labels_keep_them() {
+        let bytes = Assembler::assemble(
+            r"
+            main:
+                .alias  x = r1
+                .loop:
+                    addi    x, x, 1
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("addi r1, r1, 1").unwrap());
+    }
+
+    #[test]
+    fn unalias_ends_them() {
+        let errors = Assembler::assemble(
+            r"
+            .alias      x = r1
+            .unalias    x
+            let         x, 1
+        ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected register, found `x`".to_string())
+        );
+    }
+
+    #[test]
+    fn names_may_be_given_again() {
+        let bytes = Assembler::assemble(
+            r"
+            .alias      x = r1
+            .alias      x = r2
+            let         x, 1
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("let r2, 1").unwrap());
+    }
+}
+
+mod diagnostics {
+    use assembler::{Assembler, ErrorKind, WarningKind};
+
+    #[test]
+    fn warn_about_two_names_for_a_register() {
+        let assembly = Assembler::default()
+            .build(
+                r"
+                main:
+                    .alias  x = r1
+                    .alias  y = r1
+            ",
+            )
+            .unwrap();
+        let [warning] = &assembly.warnings[..] else {
+            panic!("{:?}", assembly.warnings)
+        };
+        assert_eq!(
+            warning.kind,
+            WarningKind::SharedRegister {
+                alias: "y".to_string(),
+                other: "x".to_string()
+            }
+        );
+        assert_eq!(warning.span.start.line, 4);
+    }
+
+    #[test]
+    fn names_in_other_scopes_may_share_a_register() {
+        let assembly = Assembler::default()
+            .build(
+                r"
+                first:
+                    .alias  x = r1
+                second:
+                    .alias  y = r1
+                    .unalias y
+                    .alias  z = r1
+            ",
+            )
+            .unwrap();
+        assert_eq!(assembly.warnings, []);
+    }
+
+    #[test]
+    fn unknown_alias_errors() {
+        let errors = Assembler::assemble(".unalias x").unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::UnknownAlias("x".to_string()));
+    }
+
+    #[test]
+    fn register_names_cannot_be_aliases() {
+        let errors = Assembler::assemble(".alias r2 = r1").unwrap_err();
+        assert!(matches!(errors.0[0].kind, ErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn aliases_are_not_values() {
+        let errors = Assembler::assemble(".alias x = r1\nlet r2, x").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected expression, found `x`".to_string())
+        );
+    }
+
+    #[test]
+    fn pseudo_instructions_check_registers() {
+        let errors = Assembler::assemble("mov r1, nowhere").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected register, found `nowhere`".to_string())
+        );
+    }
+}
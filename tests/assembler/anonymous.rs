@@ -0,0 +1,182 @@
+mod colons {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn jumps_backward() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            :
+                addi    r1, r1, 1
+                lessi   r0, r1, 24
+                caddi   pc, pc, 3
+                let     pc, :-
+        ",
+        );
+        assert_eq!(r1, 24);
+    }
+
+    #[test]
+    fn jumps_forward() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            let     pc, :+
+            let     r1, 34
+            :
+        ",
+        );
+        assert_eq!(r1, 17);
+    }
+
+    #[test]
+    fn extra_signs_skip_labels() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     pc, :++
+            :   let     r1, 1
+            :   let     r2, 2
+                let     pc, :+
+            :   let     pc, :--
+            :
+        ",
+        );
+        assert_eq!([r1, r2], [0, 2]);
+    }
+
+    #[test]
+    fn relative_references() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            :
+                addi    r1, r1, 1
+                lessi   r0, r1, 24
+                caddi   pc, pc, ~:+
+                subi    pc, pc, ~:-
+            :
+        ",
+        );
+        assert_eq!(r1, 24);
+    }
+}
+
+mod numbers {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn loops_backward() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            1:
+                addi    r1, r1, 1
+                lessi   r0, r1, 24
+                caddi   pc, pc, ~1f
+                subi    pc, pc, ~1b
+            1:
+        ",
+        );
+        assert_eq!(r1, 24);
+    }
+
+    #[test]
+    fn numbers_are_counted_apart() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     pc, 2f
+            1:  let     r1, 1
+            2:  let     r2, 2
+                let     pc, 1f
+            1:
+        ",
+        );
+        assert_eq!([r1, r2], [0, 2]);
+    }
+
+    #[test]
+    fn branch_reaches_both_directions() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            1:
+                addi    r1, r1, 1
+                lessi   r0, r1, 24
+                cbranch 2f
+                branch  1b
+            2:
+        ",
+        );
+        assert_eq!(r1, 24);
+    }
+
+    #[test]
+    fn binary_numbers_are_not_references() {
+        let [_, r1, ..] = run_virtual_machine("let r1, 0b11");
+        assert_eq!(r1, 3);
+    }
+
+    #[test]
+    fn macros_can_repeat_them() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            .macro count_to limit
+                1:
+                    addi    r1, r1, 1
+                    lessi   r0, r1, \limit
+                    caddi   pc, pc, 3
+                    subi    pc, pc, ~1b
+            .endm
+            count_to 5
+            count_to 9
+        ",
+        );
+        assert_eq!(r1, 9);
+    }
+}
+
+mod symbols {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn stay_out_of_the_symbol_table() {
+        let assembly = Assembler::default()
+            .build("main:\n:\n    nop\n1:\n    let pc, :-\n    let pc, 1b")
+            .unwrap();
+        let names: Vec<_> = assembly.symbols.iter().map(|symbol| &symbol.name).collect();
+        assert_eq!(names, ["main"]);
+    }
+
+    #[test]
+    fn do_not_change_the_scope() {
+        let bytes = Assembler::assemble("main:\n.loop:\n1:\n    let pc, .loop").unwrap();
+        assert_eq!(bytes, Assembler::assemble("let pc, 0").unwrap());
+    }
+
+    #[test]
+    fn missing_label_errors() {
+        let errors = Assembler::assemble(":\n    let pc, :+").unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::UnknownLabel(":+".to_string()));
+    }
+
+    #[test]
+    fn missing_numeric_label_errors() {
+        let errors = Assembler::assemble("let pc, 1b\n1:").unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::UnknownLabel("1b".to_string()));
+    }
+
+    #[test]
+    fn wrong_direction_names_the_reference() {
+        let errors = Assembler::assemble(":\n    addi pc, pc, ~:-").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::WrongDirection {
+                label: ":-".to_string(),
+                distance: -3
+            }
+        );
+    }
+}
@@ -0,0 +1,319 @@
+mod statements {
+    use assembler::{
+        ast::{parse, Expression, Operand, StatementKind},
+        Location, Span,
+    };
+
+    #[test]
+    fn labels_and_instructions() {
+        let program = parse("main:\n    addi r1, r1, 1\n.loop: nop\n1:\n:").unwrap();
+        let kinds: Vec<_> = program.statements.iter().map(|s| s.kind.clone()).collect();
+        let [main, addi, local, nop, numeric, anonymous] = &kinds[..] else {
+            panic!("{:?}", kinds)
+        };
+        assert_eq!(main, &StatementKind::Label("main".to_string()));
+        let StatementKind::Instruction { mnemonic, operands } = addi else {
+            panic!("{:?}", addi)
+        };
+        assert_eq!(mnemonic, "addi");
+        assert!(
+            matches!(&operands[..], [Operand::Register(..), Operand::Register(..), Operand::Expression(Expression::Number(number, _))] if number == "1")
+        );
+        assert_eq!(local, &StatementKind::Label(".loop".to_string()));
+        assert!(matches!(nop, StatementKind::Instruction { operands, .. } if operands.is_empty()));
+        assert_eq!(numeric, &StatementKind::Label("1".to_string()));
+        assert_eq!(anonymous, &StatementKind::Label(String::new()));
+    }
+
+    #[test]
+    fn spans_point_into_the_source() {
+        let program = parse("main:\n    let  r1, size * 2").unwrap();
+        let statement = &program.statements[1];
+        assert_eq!(
+            statement.span,
+            Span {
+                start: Location { line: 2, column: 5 },
+                end: Location {
+                    line: 2,
+                    column: 22
+                }
+            }
+        );
+        let StatementKind::Instruction { operands, .. } = &statement.kind else {
+            panic!("{:?}", statement.kind)
+        };
+        assert_eq!(
+            operands[1].span().start,
+            Location {
+                line: 2,
+                column: 14
+            }
+        );
+        let Operand::Expression(Expression::Binary(_, left, ..)) = &operands[1] else {
+            panic!("{:?}", operands[1])
+        };
+        assert_eq!(
+            **left,
+            Expression::Label(
+                "size".to_string(),
+                Span {
+                    start: Location {
+                        line: 2,
+                        column: 14
+                    },
+                    end: Location {
+                        line: 2,
+                        column: 18
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn directives_name_their_operands() {
+        let program = parse(".equ SIZE, 4\n.global main, SIZE\ndata \"hi\"").unwrap();
+        let operands: Vec<Vec<String>> = program
+            .statements
+            .iter()
+            .map(|statement| match &statement.kind {
+                StatementKind::Directive { operands, .. } => operands
+                    .iter()
+                    .map(|operand| match operand {
+                        Operand::Name(name, _) => format!("name {}", name),
+                        Operand::String(text, _) => format!("string {}", text),
+                        Operand::Expression(expression) => format!("expression {}", expression),
+                        Operand::Register(register, _) => format!("register {}", register),
+                    })
+                    .collect(),
+                kind => panic!("{:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            operands,
+            [
+                vec!["name SIZE", "expression 4"],
+                vec!["name main", "name SIZE"],
+                vec!["string hi"]
+            ]
+        );
+    }
+
+    #[test]
+    fn aliases_and_enum_names() {
+        let program = parse(".alias x = r1\n.enum Color\nred\ngreen = 4\n.endenum").unwrap();
+        assert_eq!(
+            program.statements[0].kind,
+            StatementKind::Alias {
+                name: "x".to_string(),
+                register: "r1".to_string()
+            }
+        );
+        assert!(matches!(
+            &program.statements[2].kind,
+            StatementKind::Member { name, value: None } if name == "red"
+        ));
+        assert!(matches!(
+            &program.statements[3].kind,
+            StatementKind::Member { name, value: Some(Expression::Number(value, _)) }
+                if name == "green" && value == "4"
+        ));
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let program = parse("; header\nnop ; after ';' and \";\"\n").unwrap();
+        let comments: Vec<_> = program
+            .statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Comment(text) => Some((text.as_str(), statement.span.start)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                (" header", Location { line: 1, column: 1 }),
+                (" after ';' and \";\"", Location { line: 2, column: 5 })
+            ]
+        );
+    }
+
+    #[test]
+    fn semicolon_characters_are_not_comments() {
+        let program = parse("data ';' ; semicolon").unwrap();
+        let printed: Vec<_> = program.statements.iter().map(|s| s.to_string()).collect();
+        assert_eq!(printed, ["data    ';'", "; semicolon"]);
+    }
+}
+
+mod preprocessor_lines {
+    use assembler::ast::{parse, StatementKind};
+
+    #[test]
+    fn macros_and_invocations() {
+        let program = parse(
+            r"
+            .macro  add_to target, amount
+                addi    \target, \target, \amount
+                store\width rA, \target, 0
+            .endm
+            add_to  r1, (1, 2)
+        ",
+        )
+        .unwrap();
+        let kinds: Vec<_> = program.statements.iter().map(|s| &s.kind).collect();
+        assert_eq!(
+            kinds[0],
+            &StatementKind::Macro {
+                name: "add_to".to_string(),
+                parameters: vec!["target".to_string(), "amount".to_string()]
+            }
+        );
+        // Parameters read as labels where a label may stand.
+        assert_eq!(
+            program.statements[1].to_string(),
+            r"addi    \target, \target, \amount"
+        );
+        assert_eq!(
+            kinds[2],
+            &StatementKind::Verbatim(r"store\width rA, \target, 0".to_string())
+        );
+        assert!(matches!(kinds[3], StatementKind::Directive { name, .. } if name == ".endm"));
+        assert_eq!(
+            kinds[4],
+            &StatementKind::Invocation {
+                name: "add_to".to_string(),
+                arguments: vec!["r1".to_string(), "(1, 2)".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn conditions_repeats_and_includes() {
+        let program = parse(
+            r#"
+            .include "missing.kittyasm"
+            .ifdef  DEBUG
+            .rept   2
+                nop
+            .endr
+            .elif   1 + 1
+            .else
+            .endif
+        "#,
+        )
+        .unwrap();
+        let names: Vec<_> = program
+            .statements
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ".include \"missing.kittyasm\"",
+                ".ifdef  DEBUG",
+                ".rept   2",
+                "nop",
+                ".endr",
+                ".elif   1 + 1",
+                ".else",
+                ".endif"
+            ]
+        );
+    }
+}
+
+mod printing {
+    use assembler::{ast::parse, Assembler, FileResolver};
+
+    fn print(source: &str) -> String {
+        parse(source).unwrap().to_string()
+    }
+
+    #[test]
+    fn canonical_layout() {
+        let printed = print(
+            "; Counts.\nmain:  let r1,17   ; start\n.loop:addi r1,r1,1\n\n\n\n   data 1,2,\n 3\n1: :\nlast: .alias x=r2",
+        );
+        assert_eq!(
+            printed,
+            r"; Counts.
+main:
+    let     r1, 17 ; start
+    .loop:
+    addi    r1, r1, 1
+
+    data    1, 2, 3
+    1:
+    :
+last:
+    .alias  x = r2
+"
+        );
+    }
+
+    #[test]
+    fn fewest_parentheses() {
+        assert_eq!(
+            print("data (1 + 2) * 3, 1 - (2 - 3), (1 - 2) - 3, -(a + 1), ~(b), hi((c))"),
+            "    data    (1 + 2) * 3, 1 - (2 - 3), 1 - 2 - 3, -(a + 1), ~(b), hi(c)\n"
+        );
+        assert_eq!(
+            print("data 1 | 2 ^ 3 & 4 << 5 + 6 % 7, (1 | 2) & 3, ~~.x, sizeof(P), 'a'"),
+            "    data    1 | 2 ^ 3 & 4 << 5 + 6 % 7, (1 | 2) & 3, ~~.x, sizeof(P), 'a'\n"
+        );
+    }
+
+    #[test]
+    fn printing_is_stable() {
+        let source = include_str!("../../src/boot.kittyasm");
+        let printed = print(source);
+        assert_eq!(print(&printed), printed);
+    }
+
+    #[test]
+    fn printed_source_assembles_the_same() {
+        let resolver = || FileResolver::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+        let source = include_str!("../../src/boot.kittyasm");
+        let original = Assembler::default()
+            .with_resolver(resolver())
+            .build(source)
+            .ok()
+            .unwrap();
+        let printed = Assembler::default()
+            .with_resolver(resolver())
+            .build(&print(source))
+            .ok()
+            .unwrap();
+        assert_eq!(printed.bytes(), original.bytes());
+    }
+}
+
+mod errors {
+    use assembler::{ast::parse, Assembler, ErrorKind};
+
+    #[test]
+    fn match_the_assembler() {
+        let source = "main:\n    let r1, 1 + ,\n    data \"x\", 1";
+        let errors = parse(source).unwrap_err();
+        assert_eq!(errors, Assembler::assemble(source).unwrap_err());
+        assert_eq!(errors.0.len(), 2);
+    }
+
+    #[test]
+    fn preprocessor_operands() {
+        let errors = parse(".if\n.endif 1\n.macro").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            messages,
+            [
+                &ErrorKind::Syntax("expected condition".to_string()),
+                &ErrorKind::Syntax("`.endif` takes no operands".to_string()),
+                &ErrorKind::Syntax("expected macro name".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+mod direction {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn backward_addi_errors() {
+        let errors = Assembler::assemble(
+            r"
+            back:
+                addi    pc, pc, ~back
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::WrongDirection {
+                label: "back".to_string(),
+                distance: -3
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "3:34: `back` is 3 bytes backward; use `subi` or `branch`"
+        );
+    }
+
+    #[test]
+    fn forward_subi_errors() {
+        let errors = Assembler::assemble(
+            r"
+            subi    pc, pc, ~ahead
+            nop
+            ahead:
+        ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::WrongDirection {
+                label: "ahead".to_string(),
+                distance: 3
+            }
+        );
+    }
+
+    #[test]
+    fn subi_to_next_instruction_assembles() {
+        assert!(Assembler::assemble("subi pc, pc, ~next\nnext:").is_ok());
+    }
+
+    #[test]
+    fn other_instructions_take_forward_distance() {
+        let bytes = Assembler::assemble(
+            r"
+                ori     r1, r0, ~ahead
+                nop
+            ahead:
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("ori r1, r0, 3\nnop").unwrap());
+    }
+
+    #[test]
+    fn other_instructions_reject_backward_distance() {
+        let errors = Assembler::assemble("back:\n    ori r1, r0, ~back").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: -3,
+                min: 0,
+                max: 63
+            }
+        );
+    }
+}
+
+mod branch {
+    use assembler::{Assembler, ErrorKind};
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn forward_is_addi() {
+        let branch = Assembler::assemble("branch ahead\nnop\nahead:").unwrap();
+        let addi = Assembler::assemble("addi pc, pc, ~ahead\nnop\nahead:").unwrap();
+        assert_eq!(branch, addi);
+    }
+
+    #[test]
+    fn backward_is_subi() {
+        let branch = Assembler::assemble("back:\nnop\nbranch back").unwrap();
+        let subi = Assembler::assemble("back:\nnop\nsubi pc, pc, ~back").unwrap();
+        assert_eq!(branch, subi);
+    }
+
+    #[test]
+    fn conditional_backward_is_csubi() {
+        let branch = Assembler::assemble("back:\ncbranch back").unwrap();
+        let subi = Assembler::assemble("back:\ncsubi pc, pc, ~back").unwrap();
+        assert_eq!(branch, subi);
+    }
+
+    #[test]
+    fn accepts_relative_reference() {
+        let branch = Assembler::assemble("branch ~ahead\nahead:").unwrap();
+        let addi = Assembler::assemble("addi pc, pc, ~ahead\nahead:").unwrap();
+        assert_eq!(branch, addi);
+    }
+
+    #[test]
+    fn missing_label_errors() {
+        let errors = Assembler::assemble("branch").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`branch` expects 1 operands, found 0".to_string())
+        );
+    }
+
+    #[test]
+    fn far_backward_is_relaxed() {
+        let source = "back:\n".to_string() + &"data3 0\n".repeat(22) + "branch back";
+        assert_eq!(Assembler::assemble(&source).unwrap().len(), 66 + 9);
+    }
+
+    #[test]
+    fn loops() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     r1, 3
+            loop:
+                addi    r2, r2, 1
+                subi    r1, r1, 1
+                xori    r3, r1, 0
+                cbranch .done
+                branch  loop
+            .done:
+        ",
+        );
+        assert_eq!(r1, 0);
+        assert_eq!(r2, 3);
+    }
+
+    #[test]
+    fn loops_over_far_distances() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            &(r"
+            let     r1, 3
+            loop:
+                addi    r2, r2, 1
+                subi    r1, r1, 1
+                xori    r3, r1, 0
+                cbranch done
+                branch  .skip
+            "
+            .to_string()
+                + &"data3 0\n".repeat(30)
+                + r"
+            .skip:
+                branch  loop
+            done:
+        "),
+        );
+        assert_eq!(r1, 0);
+        assert_eq!(r2, 3);
+    }
+}
@@ -0,0 +1,384 @@
+mod conditions {
+    use assembler::{Assembler, MemoryResolver};
+
+    fn build(assembler: Assembler, source: &str) -> Vec<u8> {
+        match assembler.build(source) {
+            Ok(assembly) => assembly.bytes(),
+            Err(errors) => panic!("{}", errors),
+        }
+    }
+
+    #[test]
+    fn if_keeps_lines_when_not_zero() {
+        let source = ".equ DEBUG, 1\n.if DEBUG\ndata 1\n.endif\n.if DEBUG - 1\ndata 2\n.endif";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1]);
+    }
+
+    #[test]
+    fn first_branch_that_holds_is_kept() {
+        let source = r"
+            .equ LEVEL, 2
+            .if LEVEL & 1
+                data 1
+            .elif LEVEL & 2
+                data 2
+            .elif LEVEL
+                data 3
+            .else
+                data 4
+            .endif
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [2]);
+    }
+
+    #[test]
+    fn else_is_kept_when_nothing_holds() {
+        let source = ".if 0\ndata 1\n.elif 0\ndata 2\n.else\ndata 3\n.endif";
+        assert_eq!(Assembler::assemble(source).unwrap(), [3]);
+    }
+
+    #[test]
+    fn conditions_nest() {
+        let source = r"
+            .if 1
+                .if 0
+                    data 1
+                .else
+                    data 2
+                .endif
+            .else
+                .if 1
+                    data 3
+                .endif
+            .endif
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [2]);
+    }
+
+    #[test]
+    fn ifdef_tests_for_constants() {
+        let source = r"
+            .equ VERBOSE, 0
+            .ifdef VERBOSE
+                data 1
+            .endif
+            .ifndef VERBOSE
+                data 2
+            .endif
+            .ifdef QUIET
+                data 3
+            .endif
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1]);
+    }
+
+    #[test]
+    fn set_constants_have_their_value_at_the_condition() {
+        let source = ".set MODE, 0\n.set MODE, 1\n.if MODE\ndata 1\n.endif\n.set MODE, 0";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1]);
+    }
+
+    #[test]
+    fn defines_choose_the_variant() {
+        let source = r"
+            .ifdef DEBUG
+                let r1, DEBUG
+            .else
+                nop
+            .endif
+            ";
+        let debug = build(Assembler::default().with_define("DEBUG", 7), source);
+        let release = build(Assembler::default(), source);
+        assert_eq!(debug, Assembler::assemble("let r1, 7").unwrap());
+        assert_eq!(release, Assembler::assemble("nop").unwrap());
+    }
+
+    #[test]
+    fn defaults_may_be_given_in_source() {
+        let source = ".ifndef LEVEL\n.equ LEVEL, 1\n.endif\nlet r1, LEVEL";
+        let default = build(Assembler::default(), source);
+        assert_eq!(default, Assembler::assemble("let r1, 1").unwrap());
+        let assembler = Assembler::default().with_define("LEVEL", 3);
+        assert_eq!(
+            build(assembler, source),
+            Assembler::assemble("let r1, 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn skipped_lines_are_not_assembled() {
+        let source = r"
+            .if 0
+                this is not kittyasm
+                undefined_macro r1, r2
+                .include 'missing.kittyasm'
+            .endif
+            data 1
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1]);
+    }
+
+    #[test]
+    fn skipped_macros_are_not_defined() {
+        let source = r"
+            .if 0
+            .macro emit
+                data 1
+            .endm
+            .else
+            .macro emit
+                data 2
+            .endm
+            .endif
+                emit
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [2]);
+    }
+
+    #[test]
+    fn conditions_in_macros_use_arguments() {
+        let source = r"
+            .macro emit value
+            .if \value
+                data \value
+            .endif
+            .endm
+                emit 1
+                emit 0
+                emit 3
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1, 3]);
+    }
+
+    #[test]
+    fn include_guards_stop_second_inclusion() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert(
+            "once.kittyasm",
+            ".ifndef ONCE\n.equ ONCE, 1\ndata 1\n.endif",
+        );
+        let assembler = Assembler::default().with_resolver(resolver);
+        let source = ".include \"once.kittyasm\"\n.include \"once.kittyasm\"";
+        assert_eq!(build(assembler, source), [1]);
+    }
+}
+
+mod repeats {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn rept_repeats_lines() {
+        let source = ".rept 3\ndata 1, 2\n.endr";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1, 2, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn count_is_a_constant_expression() {
+        let source = ".equ PIXELS, 2\n.rept PIXELS * 2 - 1\ndata 0\n.endr";
+        assert_eq!(Assembler::assemble(source).unwrap(), [0, 0, 0]);
+    }
+
+    #[test]
+    fn zero_repetitions_keep_nothing() {
+        let source = ".rept 0\nnot kittyasm\n.endr\ndata 1";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1]);
+    }
+
+    #[test]
+    fn set_counts_repetitions() {
+        let source = ".set INDEX, 0\n.rept 4\ndata INDEX\n.set INDEX, INDEX + 1\n.endr";
+        assert_eq!(Assembler::assemble(source).unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn repeats_nest() {
+        let source = ".rept 2\ndata 1\n.rept 2\ndata 2\n.endr\n.endr";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1, 2, 2, 1, 2, 2]);
+    }
+
+    #[test]
+    fn conditions_inside_repeat_see_each_value() {
+        let source = r"
+            .set INDEX, 0
+            .rept 4
+            .if INDEX & 1
+                data INDEX
+            .endif
+            .set INDEX, INDEX + 1
+            .endr
+            ";
+        assert_eq!(Assembler::assemble(source).unwrap(), [1, 3]);
+    }
+
+    #[test]
+    fn local_labels_are_unique_per_repetition() {
+        let registers = run_virtual_machine(
+            r"
+                let r1, 0
+            .rept 3
+                addi r1, r1, 1
+                addi pc, pc, ~.over
+                addi r1, r1, 10
+            .over:
+            .endr
+            ",
+        );
+        assert_eq!(registers[1], 3);
+    }
+
+    #[test]
+    fn unrolled_loop_stores_every_pixel() {
+        let registers = run_virtual_machine(
+            r"
+                let r1, 0x100
+                let r2, 0xABCDEF
+                lethi r2, 0xABCDEF
+            .rept 4
+                store3 r1, r2, 0
+                addi r1, r1, 4
+            .endr
+                let r1, 0x10C
+                load3 r3, r1, 0
+            ",
+        );
+        assert_eq!(registers[3], 0xABCDEF);
+    }
+
+    #[test]
+    fn macros_expand_in_repetitions() {
+        let source = ".macro emit value\ndata \\value\n.endm\n.rept 2\nemit 5\n.endr";
+        assert_eq!(Assembler::assemble(source).unwrap(), [5, 5]);
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn labels_are_not_constant() {
+        let errors = Assembler::assemble("main:\n.if main\n.endif").unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::NotConstant("main".to_string()));
+        assert_eq!(errors.0[0].span.start, Location { line: 2, column: 5 });
+    }
+
+    #[test]
+    fn constants_defined_later_are_not_known() {
+        let errors = Assembler::assemble(".if LATER\n.endif\n.equ LATER, 1").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::NotConstant("LATER".to_string())
+        );
+    }
+
+    #[test]
+    fn unclosed_if_errors() {
+        let errors = Assembler::assemble("nop\n    .if 1\nnop").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnclosedBlock(".if".to_string())
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 2, column: 5 });
+        assert_eq!(
+            errors.to_string().lines().next(),
+            Some("2:5: `.if` is missing `.endif`")
+        );
+    }
+
+    #[test]
+    fn unclosed_rept_errors() {
+        let errors = Assembler::assemble(".rept 2\nnop").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnclosedBlock(".rept".to_string())
+        );
+    }
+
+    #[test]
+    fn endif_without_if_errors() {
+        let errors = Assembler::assemble("nop\n.endif").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.endif` without `.if`".to_string())
+        );
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+
+    #[test]
+    fn endr_without_rept_errors() {
+        let errors = Assembler::assemble(".endr").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.endr` without `.rept`".to_string())
+        );
+    }
+
+    #[test]
+    fn else_after_else_errors() {
+        let errors = Assembler::assemble(".if 1\n.else\n.else\n.endif").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.else` after `.else`".to_string())
+        );
+        assert_eq!(errors.0[0].span.start.line, 3);
+    }
+
+    #[test]
+    fn blocks_do_not_span_macros() {
+        let source = ".macro open\n.if 1\n.endm\nopen\n.endif";
+        let errors = Assembler::assemble(source).unwrap_err();
+        let kinds: Vec<_> = errors.0.iter().map(|error| error.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                ErrorKind::UnclosedBlock(".if".to_string()),
+                ErrorKind::Syntax("`.endif` without `.if`".to_string()),
+            ]
+        );
+        assert_eq!(errors.0[0].span.start.line, 2);
+        assert_eq!(errors.0[0].expansions[0].name, "open");
+    }
+
+    #[test]
+    fn missing_condition_errors() {
+        let errors = Assembler::assemble(".if\n.endif").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected condition".to_string())
+        );
+    }
+
+    #[test]
+    fn endif_takes_no_operands() {
+        let errors = Assembler::assemble(".if 1\n.endif 1").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.endif` takes no operands".to_string())
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 2, column: 8 });
+    }
+
+    #[test]
+    fn negative_count_errors() {
+        let errors = Assembler::assemble(".rept -1\nnop\n.endr").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: -1,
+                min: 0,
+                max: 0o77_777_777
+            }
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 7 });
+    }
+
+    #[test]
+    fn macros_cannot_be_defined_in_repeats() {
+        let errors = Assembler::assemble(".rept 1\n.macro twice\n.endm\n.endr").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("macros cannot be defined inside `.rept`".to_string())
+        );
+    }
+}
@@ -92,3 +92,107 @@ mod data3 {
         assert_eq!(r1, 7_000_000)
     }
 }
+
+mod labels {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn pointer_table_holds_addresses() {
+        let bytes = Assembler::assemble(
+            r"
+            table:
+                data3   first, second
+            first:
+                data    1
+            second:
+                data    2
+            ",
+        )
+        .unwrap();
+        assert_eq!(bytes, [0, 0, 6, 0, 0, 7, 1, 2]);
+    }
+
+    #[test]
+    fn jump_table_reaches_handlers() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+                let     r2, table
+                lethi   r2, table
+                load3   r3, r2, 3
+                or      pc, r3, r0
+            table:
+                data3   first, second
+            first:
+                let     r1, 1
+                subi    pc, pc, ~first
+            second:
+                let     r1, 2
+                subi    pc, pc, ~second
+            ",
+        );
+        assert_eq!(r1, 2);
+    }
+
+    #[test]
+    fn offsets_fit_in_data2() {
+        let bytes = Assembler::assemble(
+            r"
+            table:
+                data2   end - table, end - start
+            start:
+                data    0xFF
+            end:
+            ",
+        )
+        .unwrap();
+        assert_eq!(bytes, [0, 5, 0, 1, 0xFF]);
+    }
+
+    #[test]
+    fn neighbouring_bytes_are_kept() {
+        let bytes = Assembler::assemble("data 0xAA\ndata2 end\ndata 0xBB\nend:").unwrap();
+        assert_eq!(bytes, [0xAA, 0, 4, 0xBB]);
+    }
+
+    #[test]
+    fn reference_may_end_the_output() {
+        let bytes = Assembler::assemble("start:\ndata 1\ndata3 start").unwrap();
+        assert_eq!(bytes, [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn constants_resolve_after_layout() {
+        let bytes = Assembler::assemble("data2 SIZE\n.equ SIZE, 0x1234").unwrap();
+        assert_eq!(bytes, [0x12, 0x34]);
+    }
+
+    #[test]
+    fn address_too_large_for_data_errors() {
+        let errors = Assembler::assemble("data 0, far\n.space 300\nfar:").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 302,
+                min: -128,
+                max: 255
+            }
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 9 });
+        assert_eq!(errors.0[0].span.end, Location { line: 1, column: 12 });
+    }
+
+    #[test]
+    fn address_too_large_for_data2_errors() {
+        let errors = Assembler::assemble(".org 0x10000\nhere:\ndata2 here").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 0x10000,
+                min: -32768,
+                max: 65535
+            }
+        );
+    }
+}
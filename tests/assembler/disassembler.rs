@@ -0,0 +1,142 @@
+mod decode {
+    use assembler::{Assembler, Disassembler};
+
+    fn disassemble(source: &str) -> Vec<String> {
+        let bytes = Assembler::assemble(source).unwrap();
+        Disassembler::new()
+            .disassemble(0, &bytes)
+            .lines()
+            .map(|line| line.split(';').next().unwrap().trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn decode_each_format() {
+        assert_eq!(
+            disassemble("let r1, 0x123\nlethi r1, 0xABC000\naddi r2, r1, 5\nadd r3, r2, r1"),
+            [
+                "let r1, 0x123",
+                "lethi r1, 0xABC000",
+                "addi r2, r1, 5",
+                "add r3, r2, r1"
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_conditional_bit() {
+        assert_eq!(
+            disassemble("csubi r1, r1, 1\ncxor r1, r2, r3"),
+            ["csubi r1, r1, 1", "cxor r1, r2, r3"]
+        );
+    }
+
+    #[test]
+    fn decode_register_aliases() {
+        assert_eq!(
+            disassemble("or sp, ir, pc\nor lr, at, r3b"),
+            ["or sp, ir, pc", "or lr, at, r3b"]
+        );
+    }
+
+    #[test]
+    fn decode_signed_offsets() {
+        assert_eq!(
+            disassemble("load r1, sp, -1\nstore3 r1, sp, 31"),
+            ["load r1, sp, -1", "store3 r1, sp, 31"]
+        );
+    }
+
+    #[test]
+    fn decode_leftover_bytes_as_data() {
+        assert_eq!(
+            disassemble("nop\ndata 1, 2"),
+            ["shri sp, sp, 0", "data 1, 2"]
+        );
+    }
+
+    #[test]
+    fn follow_with_address() {
+        let output = Disassembler::new().disassemble(0x10, &[0, 0, 0]);
+        assert_eq!(
+            output,
+            ".org 0x000010\n    let sp, 0x000           ; 000010\n"
+        );
+    }
+}
+
+mod symbols {
+    use assembler::{Assembler, Disassembler};
+
+    #[test]
+    fn define_labels_and_name_branch_targets() {
+        let source = "main:\n    nop\n.loop:\n    subi pc, pc, ~.loop";
+        let assembly = Assembler::default().build(source).unwrap();
+        let output = Disassembler::new()
+            .with_symbols(&assembly.symbols)
+            .disassemble(0, &assembly.bytes());
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "main:");
+        assert_eq!(lines[2], ".loop:");
+        assert!(lines[3].ends_with("; 000003 -> main.loop"), "{}", output);
+    }
+
+    #[test]
+    fn note_labels_inside_instructions() {
+        let assembly = Assembler::default()
+            .build("data 1\ninside:\ndata 2, 3")
+            .unwrap();
+        let output = Disassembler::new()
+            .with_symbols(&assembly.symbols)
+            .disassemble(0, &assembly.bytes());
+        assert!(
+            output.starts_with("; inside is at 000000+1\n"),
+            "{}",
+            output
+        );
+    }
+}
+
+mod round_trip {
+    use assembler::{Assembler, Disassembler};
+
+    fn assert_round_trip(bytes: &[u8]) {
+        let source = Disassembler::new().disassemble(0, bytes);
+        let reassembled = Assembler::assemble(&source).unwrap_or_else(|errors| {
+            panic!("{}\n{}", errors, source);
+        });
+        assert_eq!(reassembled, bytes, "{}", source);
+    }
+
+    #[test]
+    fn every_op_reassembles() {
+        let mut bytes = vec![];
+        for conditional in 0..2 {
+            for op in 0..32 {
+                for fields in [0, 0o01_02_03, 0o77_77_77, 0o40_40_40, 0o75_37_41] {
+                    let word: u32 = conditional << 23 | op << 18 | fields;
+                    bytes.extend(&word.to_be_bytes()[1..]);
+                }
+            }
+        }
+        assert_round_trip(&bytes);
+    }
+
+    #[test]
+    fn boot_program_reassembles() {
+        let bytes = Assembler::assemble(include_str!("../../src/boot.kittyasm")).unwrap();
+        assert_round_trip(&bytes);
+    }
+
+    #[test]
+    fn labelled_program_reassembles() {
+        let source = "main:\n    let r1, 3\n.loop:\n    subi r1, r1, 1\n    or at, r1, r1\n    \
+                      csubi pc, pc, ~.loop\nend:\n    data 7";
+        let assembly = Assembler::default().build(source).unwrap();
+        let bytes = assembly.bytes();
+        let output = Disassembler::new()
+            .with_symbols(&assembly.symbols)
+            .disassemble(0, &bytes);
+        assert_eq!(Assembler::assemble(&output).unwrap(), bytes, "{}", output);
+    }
+}
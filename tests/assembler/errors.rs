@@ -0,0 +1,213 @@
+mod syntax {
+    use assembler::{Assembler, ErrorKind, Location, Span};
+
+    #[test]
+    fn unknown_instruction_spans_mnemonic() {
+        let errors = Assembler::assemble(
+            r"
+            main:
+                frobnicate  r1, r2, r3
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::Syntax("unknown instruction `frobnicate`".to_string())
+        );
+        assert_eq!(
+            error.span,
+            Span {
+                start: Location {
+                    line: 3,
+                    column: 17
+                },
+                end: Location {
+                    line: 3,
+                    column: 27
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_register_spans_operand() {
+        let errors = Assembler::assemble(
+            r"
+            addi    r1, r99, 3
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::Syntax("expected register, found `r99`".to_string())
+        );
+        assert_eq!(
+            error.span.start,
+            Location {
+                line: 2,
+                column: 25
+            }
+        );
+        assert_eq!(
+            error.span.end,
+            Location {
+                line: 2,
+                column: 28
+            }
+        );
+    }
+
+    #[test]
+    fn register_is_not_an_immediate() {
+        let errors = Assembler::assemble("addi r1, r2, r3").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected expression, found `r3`".to_string())
+        );
+        assert_eq!(
+            errors.0[0].span.start,
+            Location {
+                line: 1,
+                column: 14
+            }
+        );
+    }
+
+    #[test]
+    fn missing_operand_is_reported() {
+        let errors = Assembler::assemble(
+            r"
+            addi    r1, r2
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::Syntax("`addi` expects 3 operands, found 2".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_every_error_in_one_pass() {
+        let errors = Assembler::assemble(
+            r"
+            foo     r1
+            addi    r1, r2, 3
+            bar     r2
+            let     pc, nowhere
+        ",
+        )
+        .unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|error| error.span.start.line).collect();
+        assert_eq!(lines, [2, 4, 5]);
+    }
+
+    #[test]
+    fn comment_at_end_of_input_is_accepted() {
+        assert!(Assembler::assemble("let r1, 1 ; no newline").is_ok());
+    }
+}
+
+mod numbers {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn too_large_number_errors() {
+        let errors = Assembler::assemble(
+            r"
+            let     r1, 0x1_0000_0000
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::NumberTooLarge("0x1_0000_0000".to_string())
+        );
+        assert_eq!(
+            error.span.start,
+            Location {
+                line: 2,
+                column: 25
+            }
+        );
+    }
+
+    #[test]
+    fn too_large_negative_number_errors() {
+        let errors = Assembler::assemble(
+            r"
+            let     r1, -99_999_999_999
+        ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::NumberTooLarge("99_999_999_999".to_string())
+        );
+    }
+}
+
+mod labels {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn unknown_label_spans_reference() {
+        let errors = Assembler::assemble(
+            r"
+            let     pc, nowhere
+        ",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::UnknownLabel("nowhere".to_string()));
+        assert_eq!(
+            error.span.start,
+            Location {
+                line: 2,
+                column: 25
+            }
+        );
+        assert_eq!(
+            error.span.end,
+            Location {
+                line: 2,
+                column: 32
+            }
+        );
+    }
+
+    #[test]
+    fn local_label_without_scope_errors() {
+        let errors = Assembler::assemble(
+            r"
+            .loop:
+                subi    pc, pc, ~.loop
+        ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::MissingScope(".loop".to_string())
+        );
+    }
+
+    #[test]
+    fn errors_display_with_location() {
+        let errors = Assembler::assemble("let pc, nowhere").unwrap_err();
+        assert_eq!(errors.to_string(), "1:9: unknown label `nowhere`");
+    }
+}
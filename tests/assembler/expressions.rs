@@ -0,0 +1,270 @@
+mod operators {
+    use assembler::Assembler;
+
+    fn assert_evaluates(expression: &str, value: u32) {
+        let bytes = Assembler::assemble(&format!("data3 {}", expression)).unwrap();
+        let expected = Assembler::assemble(&format!("data3 {}", value)).unwrap();
+        assert_eq!(bytes, expected, "{}", expression);
+    }
+
+    #[test]
+    fn evaluate_arithmetic() {
+        assert_evaluates("1 + 2", 3);
+        assert_evaluates("7 - 2", 5);
+        assert_evaluates("6 * 7", 42);
+        assert_evaluates("17 / 5", 3);
+        assert_evaluates("17 % 5", 2);
+    }
+
+    #[test]
+    fn evaluate_bitwise() {
+        assert_evaluates("0b1100 | 0b1010", 0b1110);
+        assert_evaluates("0b1100 & 0b1010", 0b1000);
+        assert_evaluates("0b1100 ^ 0b1010", 0b0110);
+        assert_evaluates("1 << 12", 4096);
+        assert_evaluates("0x1000 >> 4", 0x100);
+        assert_evaluates("~0 & 0xFF", 0xFF);
+    }
+
+    #[test]
+    fn bind_like_c() {
+        assert_evaluates("1 + 2 * 3", 7);
+        assert_evaluates("1 << 2 + 1", 8);
+        assert_evaluates("1 | 2 & 3", 3);
+        assert_evaluates("6 - 3 - 2", 1);
+        assert_evaluates("-2 + 5", 3);
+    }
+
+    #[test]
+    fn group_with_parentheses() {
+        assert_evaluates("(1 + 2) * 3", 9);
+        assert_evaluates("~(1 << 4) & 0xFF", 0xEF);
+    }
+
+    #[test]
+    fn select_high_and_low_bits() {
+        assert_evaluates("hi(0xABCDEF)", 0xABC);
+        assert_evaluates("lo(0xABCDEF)", 0xDEF);
+    }
+
+    #[test]
+    fn evaluate_character_literals() {
+        assert_evaluates("'A'", 65);
+        assert_evaluates("'a' - 'A'", 32);
+        assert_evaluates(r"'\n'", 10);
+        assert_evaluates(r"'\''", 39);
+        assert_evaluates(r"'\x7F'", 0x7F);
+        assert_evaluates(r"'\u{263A}'", 0x263A);
+    }
+}
+
+mod labels {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn subtract_into_lengths() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, notes.end - notes
+            jump    notes.end
+            notes:
+                data    1, 2, 3, 4, 5
+            .end:
+        ",
+        );
+        assert_eq!(r1, 5);
+    }
+
+    #[test]
+    fn offset_into_tables() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            letall  r2, table + 2
+            load    r1, r2, 0
+            jump    table.end
+            table:
+                data    10, 20, 30
+            .end:
+        ",
+        );
+        assert_eq!(r1, 30);
+    }
+
+    #[test]
+    fn split_with_high_and_low() {
+        let source =
+            "let r1, lo(far)\nlethi r1, far\n".to_string() + &"data3 0\n".repeat(2000) + "far:";
+        let assembly = Assembler::default().build(&source).unwrap();
+        assert!(assembly.warnings.is_empty());
+    }
+
+    #[test]
+    fn relative_reference_in_expression_is_signed() {
+        let bytes = Assembler::assemble(
+            r"
+            back:
+                ori     r1, r0, ~back + 5
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("ori r1, r0, 2").unwrap());
+    }
+
+    #[test]
+    fn tilde_before_parenthesis_negates_bits() {
+        let bytes = Assembler::assemble("let r1, ~(here) & 0xFF\nhere:").unwrap();
+        assert_eq!(bytes, Assembler::assemble("let r1, 0xFC").unwrap());
+    }
+}
+
+mod constants {
+    use assembler::{Assembler, ErrorKind};
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn equ_defines_constant() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            .equ    WIDTH, 320
+            .equ    HEIGHT, 180
+            letall  r1, WIDTH * HEIGHT
+        ",
+        );
+        assert_eq!(r1, 57600);
+    }
+
+    #[test]
+    fn equ_is_usable_before_definition() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, SIZE
+            .equ    SIZE, notes.end - notes
+            jump    notes.end
+            notes:
+                data    1, 2, 3
+            .end:
+        ",
+        );
+        assert_eq!(r1, 3);
+    }
+
+    #[test]
+    fn set_takes_value_at_point_of_use() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            .set    counter, 1
+            let     r1, counter
+            .set    counter, counter + 1
+            let     r2, counter
+        ",
+        );
+        assert_eq!(r1, 1);
+        assert_eq!(r2, 2);
+    }
+
+    #[test]
+    fn immediate_accepts_constant() {
+        let bytes = Assembler::assemble(".equ STEP, 3\naddi r1, r1, STEP").unwrap();
+        assert_eq!(bytes, Assembler::assemble("addi r1, r1, 3").unwrap());
+    }
+
+    #[test]
+    fn out_of_range_constant_errors() {
+        let errors = Assembler::assemble(".equ STEP, 64\naddi r1, r1, STEP").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 64,
+                min: 0,
+                max: 63
+            }
+        );
+    }
+
+    #[test]
+    fn redefined_equ_errors() {
+        let errors = Assembler::assemble(".equ SIZE, 1\n.equ SIZE, 2").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::DuplicateSymbol("SIZE".to_string())
+        );
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+
+    #[test]
+    fn set_of_equ_errors() {
+        let errors = Assembler::assemble(".equ SIZE, 1\n.set SIZE, 2").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::DuplicateSymbol("SIZE".to_string())
+        );
+    }
+
+    #[test]
+    fn label_named_as_constant_errors() {
+        let errors = Assembler::assemble(".equ main, 1\nmain:").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::DuplicateSymbol("main".to_string())
+        );
+    }
+
+    #[test]
+    fn circular_constants_error() {
+        let errors = Assembler::assemble(".equ A, B + 1\n.equ B, A\ndata A").unwrap_err();
+        assert!(errors
+            .iter()
+            .any(|error| matches!(error.kind, ErrorKind::CircularConstant(_))));
+    }
+
+    #[test]
+    fn unused_constant_with_unknown_label_errors() {
+        let errors = Assembler::assemble(".equ A, nowhere").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnknownLabel("nowhere".to_string())
+        );
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn division_by_zero_errors() {
+        let errors = Assembler::assemble("data 1 / 0").unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::DivisionByZero);
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 8 });
+    }
+
+    #[test]
+    fn division_by_zero_after_layout_errors() {
+        let errors = Assembler::assemble("data 1 % (end - end)\nend:").unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::DivisionByZero);
+    }
+
+    #[test]
+    fn out_of_range_result_errors() {
+        let errors = Assembler::assemble("data 200 + 100").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 300,
+                min: -128,
+                max: 255
+            }
+        );
+    }
+
+    #[test]
+    fn incomplete_expression_errors() {
+        let errors = Assembler::assemble("let r1, 1 +").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected expression, found `1 +`".to_string())
+        );
+    }
+}
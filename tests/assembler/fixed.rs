@@ -0,0 +1,145 @@
+mod literals {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn scale_by_fraction_bits() {
+        let bytes = Assembler::assemble("data 1.5q4, 0.25q8, 3q2, 0.75q0").unwrap();
+        assert_eq!(bytes, [0x18, 0x40, 12, 1]);
+    }
+
+    #[test]
+    fn negative_values_are_twos_complement() {
+        let bytes = Assembler::assemble("data -0.5q7\ndata2 -1.5q8\ndata3 -0.25q12").unwrap();
+        assert_eq!(bytes, [0xC0, 0xFE, 0x80, 0xFF, 0xFC, 0x00]);
+    }
+
+    #[test]
+    fn are_immediates() {
+        let [_, r1, r2, r3, ..] = run_virtual_machine(
+            r"
+            let     r1, 2.5q8
+            addi    r2, r1, 0.25q4
+            letall  r3, -1.5q16
+        ",
+        );
+        assert_eq!([r1, r2, r3], [0x280, 0x284, 0xFE8000]);
+    }
+
+    #[test]
+    fn split_across_let_and_lethi() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            .equ    SPEED, 123.5q12
+            let     r1, lo(SPEED)
+            lethi   r1, SPEED
+        ",
+        );
+        assert_eq!(r1, 123 << 12 | 1 << 11);
+    }
+
+    #[test]
+    fn underscores_separate_digits() {
+        let bytes = Assembler::assemble("data2 1_000.000_5q4").unwrap();
+        assert_eq!(bytes, Assembler::assemble("data2 16000").unwrap());
+    }
+
+    #[test]
+    fn prefixed_numbers_are_not_fixed_point() {
+        let bytes = Assembler::assemble("data 0b11, 0x1a, 0o7").unwrap();
+        assert_eq!(bytes, [3, 0x1a, 7]);
+    }
+}
+
+mod formats {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn fixed_gives_the_default_fraction_bits() {
+        let bytes = Assembler::assemble(
+            r"
+            .fixed  8
+            data2   1.5, -0.5
+            data    0.5q4
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, [0x01, 0x80, 0xFF, 0x80, 0x08]);
+    }
+
+    #[test]
+    fn later_fixed_replaces_earlier() {
+        let bytes = Assembler::assemble(".fixed 4\ndata 1.5\n.fixed 1\ndata 1.5").unwrap();
+        assert_eq!(bytes, [0x18, 3]);
+    }
+
+    #[test]
+    fn fractions_need_a_format() {
+        let errors = Assembler::assemble("data 1.5").unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::MissingFixedFormat("1.5".to_string()));
+        assert_eq!(
+            error.kind.to_string(),
+            "`1.5` needs a `q` suffix or a `.fixed` before it for its fraction bits"
+        );
+    }
+
+    #[test]
+    fn at_most_24_fraction_bits() {
+        let errors = Assembler::assemble("data3 0.5q25").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::InvalidNumber("0.5q25".to_string())
+        );
+        let errors = Assembler::assemble(".fixed 25").unwrap_err();
+        assert!(matches!(errors.0[0].kind, ErrorKind::OutOfRange { .. }));
+    }
+
+    #[test]
+    fn values_must_fit_their_field() {
+        let errors = Assembler::assemble("data 16q4").unwrap_err();
+        assert!(matches!(errors.0[0].kind, ErrorKind::OutOfRange { .. }));
+    }
+}
+
+mod precision {
+    use assembler::{Assembler, WarningKind};
+
+    #[test]
+    fn rounding_warns() {
+        let assembly = Assembler::default().build("nop\ndata2 0.1q8").unwrap();
+        let [warning] = &assembly.warnings[..] else {
+            panic!("{:?}", assembly.warnings)
+        };
+        assert_eq!(
+            warning.kind,
+            WarningKind::Rounded {
+                literal: "0.1q8".to_string(),
+                value: 26,
+                bits: 8
+            }
+        );
+        assert_eq!(
+            warning.kind.to_string(),
+            "`0.1q8` is rounded to 0.1015625 with 8 fraction bits"
+        );
+        assert_eq!(warning.span.start.line, 2);
+    }
+
+    #[test]
+    fn rounds_to_nearest() {
+        let bytes = Assembler::assemble("data 0.3q2, 0.4q2, 0.2q2").unwrap();
+        assert_eq!(bytes, [1, 2, 1]);
+    }
+
+    #[test]
+    fn exact_values_do_not_warn() {
+        let assembly = Assembler::default()
+            .build("data3 0.125q3, 1.0625q4")
+            .unwrap();
+        assert_eq!(assembly.warnings, []);
+    }
+}
@@ -0,0 +1,201 @@
+mod normalization {
+    use assembler::format;
+
+    #[test]
+    fn mnemonics_and_directives_are_lower_case() {
+        let formatted = format("MAIN:\n    LetAll R1, 0\n    .ORG 0x100\n    DATA2 1").unwrap();
+        assert_eq!(
+            formatted,
+            "MAIN:\n    letall  R1, 0\n    .org    0x100\n    data2   1\n"
+        );
+    }
+
+    #[test]
+    fn number_literals() {
+        let formatted = format("data 0XabC_d, 0B1_0, 0O17, 1_000, -(0xf)\ndata3 1.5Q12").unwrap();
+        assert_eq!(
+            formatted,
+            "    data    0xABC_D, 0b1_0, 0o17, 1_000, -0xF\n    data3   1.5q12\n"
+        );
+    }
+
+    #[test]
+    fn names_keep_their_case() {
+        let source = r"
+            .macro  Pad     Count
+                .space  \Count
+            .endm
+            Entry:
+                .alias  Sum = rA
+                Pad     0x1f
+                let     Sum, Entry.Data
+                .Data:
+        ";
+        let formatted = format(source).unwrap();
+        assert_eq!(
+            formatted,
+            r"    .macro  Pad Count
+    .space  \Count
+    .endm
+Entry:
+    .alias  Sum = rA
+    Pad     0x1f
+    let     Sum, Entry.Data
+    .Data:
+"
+        );
+    }
+}
+
+mod layout {
+    use assembler::format;
+
+    #[test]
+    fn local_labels_are_indented_under_global_labels() {
+        let formatted = format("main:\n.loop:\nnop\n    other:\n  1:\n  :\n").unwrap();
+        assert_eq!(
+            formatted,
+            "main:\n    .loop:\n    nop\nother:\n    1:\n    :\n"
+        );
+    }
+
+    #[test]
+    fn operands_line_up() {
+        let formatted = format("let r1,1\nlethi   r1 ,  1\n.pstring \"a\"\nnop").unwrap();
+        assert_eq!(
+            formatted,
+            "    let     r1, 1\n    lethi   r1, 1\n    .pstring \"a\"\n    nop\n"
+        );
+    }
+
+    #[test]
+    fn trailing_comments_line_up_between_blank_lines() {
+        let formatted = format(
+            r"
+            ; Own line.
+            main: ; entry
+                let r1, 17 ; first
+                addi r1, r1, 0x1 ; second
+                nop
+
+                nop ;third
+        ",
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            r"    ; Own line.
+main:                   ; entry
+    let     r1, 17      ; first
+    addi    r1, r1, 0x1 ; second
+    nop
+
+    nop ;third
+"
+        );
+    }
+}
+
+mod stability {
+    use assembler::{format, Assembler, FileResolver};
+
+    const BOOT: &str = include_str!("../../src/boot.kittyasm");
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        let once = format(BOOT).unwrap();
+        assert_eq!(format(&once).unwrap(), once);
+        let source = "X: ; a\n  .y:  data 1 ,\n 2 ; b\n\n\n\n;c\n  ; d\n.macro m a\n  let \\a , 1 ; e\n.endm\nm r1";
+        let once = format(source).unwrap();
+        assert_eq!(format(&once).unwrap(), once);
+    }
+
+    #[test]
+    fn formatted_source_assembles_the_same() {
+        let resolver = || FileResolver::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+        let original = Assembler::default()
+            .with_resolver(resolver())
+            .build(BOOT)
+            .ok()
+            .unwrap();
+        let formatted = Assembler::default()
+            .with_resolver(resolver())
+            .build(&format(BOOT).unwrap())
+            .ok()
+            .unwrap();
+        assert_eq!(formatted.bytes(), original.bytes());
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let source = "; one\nnop ; two\n; three";
+        let formatted = format(source).unwrap();
+        assert_eq!(formatted, "; one\n    nop ; two\n; three\n");
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let errors = format("nop\nlet r1,").unwrap_err();
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+}
+
+mod command {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    fn kittyfmt(arguments: &[&str], input: &str) -> (bool, String) {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_kittyfmt"))
+            .args(arguments)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    }
+
+    #[test]
+    fn formats_standard_input() {
+        let (success, output) = kittyfmt(&[], "MAIN: NOP");
+        assert!(success);
+        assert_eq!(output, "MAIN:\n    nop\n");
+    }
+
+    #[test]
+    fn check_fails_on_unformatted_input() {
+        assert_eq!(
+            kittyfmt(&["--check"], "nop"),
+            (false, "<stdin>\n".to_string())
+        );
+        assert_eq!(kittyfmt(&["--check"], "    nop\n"), (true, String::new()));
+    }
+
+    #[test]
+    fn formats_files_in_place() {
+        let path = std::env::temp_dir().join(format!("kittyfmt-{}.kittyasm", std::process::id()));
+        std::fs::write(&path, "main:  LET r1 , 1").unwrap();
+        let path_name = path.to_str().unwrap();
+        assert_eq!(
+            kittyfmt(&["--check", path_name], ""),
+            (false, format!("{}\n", path_name))
+        );
+        assert_eq!(kittyfmt(&[path_name], ""), (true, String::new()));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "main:\n    let     r1, 1\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
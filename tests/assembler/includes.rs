@@ -0,0 +1,244 @@
+mod include {
+    use assembler::{Assembler, MemoryResolver};
+
+    fn build(source: &str, files: &[(&str, &str)]) -> Vec<u8> {
+        let mut resolver = MemoryResolver::new();
+        for (name, contents) in files {
+            resolver.insert(*name, *contents);
+        }
+        let assembler = Assembler::default().with_resolver(resolver);
+        match assembler.build(source) {
+            Ok(assembly) => assembly.bytes(),
+            Err(errors) => panic!("{}", errors),
+        }
+    }
+
+    #[test]
+    fn include_inserts_lines() {
+        let bytes = build(
+            "data 1\n.include \"two.kittyasm\"\ndata 4",
+            &[("two.kittyasm", "data 2\ndata 3")],
+        );
+        assert_eq!(bytes, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn included_labels_and_constants_are_visible() {
+        let bytes = build(
+            ".include \"constants.kittyasm\"\nlet r2, SIZE\nlet r1, start",
+            &[("constants.kittyasm", ".equ SIZE, 42\nstart:")],
+        );
+        let expected = Assembler::assemble("start:\nlet r2, 42\nlet r1, start").unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn included_macros_are_visible() {
+        let bytes = build(
+            ".include \"macros.kittyasm\"\ntwice 7",
+            &[(
+                "macros.kittyasm",
+                ".macro twice value\ndata \\value, \\value\n.endm",
+            )],
+        );
+        assert_eq!(bytes, [7, 7]);
+    }
+
+    #[test]
+    fn includes_nest() {
+        let bytes = build(
+            ".include \"a.kittyasm\"",
+            &[
+                ("a.kittyasm", "data 1\n.include \"b.kittyasm\""),
+                ("b.kittyasm", "data 2"),
+            ],
+        );
+        assert_eq!(bytes, [1, 2]);
+    }
+
+    #[test]
+    fn file_may_be_included_twice() {
+        let bytes = build(
+            ".include \"one.kittyasm\"\n.include \"one.kittyasm\"",
+            &[("one.kittyasm", "data 1")],
+        );
+        assert_eq!(bytes, [1, 1]);
+    }
+}
+
+mod incbin {
+    use assembler::{Assembler, MemoryResolver};
+
+    fn build(source: &str) -> Vec<u8> {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("font.bin", [10, 11, 12, 13, 14]);
+        let assembler = Assembler::default().with_resolver(resolver);
+        match assembler.build(source) {
+            Ok(assembly) => assembly.bytes(),
+            Err(errors) => panic!("{}", errors),
+        }
+    }
+
+    #[test]
+    fn incbin_inserts_bytes() {
+        assert_eq!(
+            build("data 1\n.incbin \"font.bin\"\ndata 2"),
+            [1, 10, 11, 12, 13, 14, 2]
+        );
+    }
+
+    #[test]
+    fn incbin_from_offset() {
+        assert_eq!(build(".incbin \"font.bin\", 3"), [13, 14]);
+    }
+
+    #[test]
+    fn incbin_from_offset_for_length() {
+        assert_eq!(build(".incbin \"font.bin\", 1, 2"), [11, 12]);
+    }
+
+    #[test]
+    fn incbin_accepts_constants() {
+        assert_eq!(build(".equ SKIP, 4\n.incbin \"font.bin\", SKIP"), [14]);
+    }
+
+    #[test]
+    fn labels_after_incbin_account_for_its_bytes() {
+        let expected = Assembler::assemble("data 10, 11, 12, 13, 14\nlet r1, end\nend:");
+        assert_eq!(
+            build(".incbin \"font.bin\"\nlet r1, end\nend:"),
+            expected.unwrap()
+        );
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Location, MemoryResolver};
+
+    fn errors(source: &str, files: &[(&str, &str)]) -> assembler::AssembleErrors {
+        let mut resolver = MemoryResolver::new();
+        for (name, contents) in files {
+            resolver.insert(*name, *contents);
+        }
+        Assembler::default()
+            .with_resolver(resolver)
+            .build(source)
+            .err()
+            .unwrap()
+    }
+
+    #[test]
+    fn error_in_included_file_names_file() {
+        let errors = errors(
+            "nop\n.include \"bad.kittyasm\"",
+            &[("bad.kittyasm", "nop\naddi r1, r1, 100")],
+        );
+        assert_eq!(errors.0[0].file.as_deref(), Some("bad.kittyasm"));
+        assert_eq!(
+            errors.0[0].span.start,
+            Location {
+                line: 2,
+                column: 14
+            }
+        );
+        assert!(errors.to_string().starts_with("bad.kittyasm:2:14: "));
+    }
+
+    #[test]
+    fn error_in_main_source_has_no_file() {
+        let errors = errors(
+            ".include \"good.kittyasm\"\naddi r1, r1, 100",
+            &[("good.kittyasm", "nop")],
+        );
+        assert_eq!(errors.0[0].file, None);
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+
+    #[test]
+    fn errors_are_in_order_of_inclusion() {
+        let errors = errors(
+            "addi r1, r1, 100\n.include \"bad.kittyasm\"\naddi r1, r1, 100",
+            &[("bad.kittyasm", "nop\n\n\naddi r1, r1, 100")],
+        );
+        let files: Vec<_> = errors.iter().map(|error| error.file.as_deref()).collect();
+        assert_eq!(files, [None, Some("bad.kittyasm"), None]);
+    }
+
+    #[test]
+    fn macro_invoked_from_included_file_names_file() {
+        let errors = errors(
+            ".macro far\n    addi r1, r1, 100\n.endm\n.include \"user.kittyasm\"",
+            &[("user.kittyasm", "nop\nfar")],
+        );
+        assert_eq!(errors.0[0].file, None);
+        assert_eq!(errors.0[0].span.start.line, 2);
+        let expansion = &errors.0[0].expansions[0];
+        assert_eq!(expansion.file.as_deref(), Some("user.kittyasm"));
+        assert_eq!(expansion.span.start.line, 2);
+    }
+
+    #[test]
+    fn missing_file_errors() {
+        let errors = errors("nop\n.include \"missing.kittyasm\"", &[]);
+        assert!(matches!(
+            &errors.0[0].kind,
+            ErrorKind::UnreadableFile { name, .. } if name == "missing.kittyasm"
+        ));
+        assert_eq!(errors.0[0].span.start, Location { line: 2, column: 1 });
+    }
+
+    #[test]
+    fn missing_binary_errors() {
+        let errors = errors(".incbin \"missing.bin\"", &[]);
+        assert!(matches!(
+            &errors.0[0].kind,
+            ErrorKind::UnreadableFile { name, .. } if name == "missing.bin"
+        ));
+    }
+
+    #[test]
+    fn include_without_resolver_errors() {
+        let errors = Assembler::assemble(".include \"a.kittyasm\"").unwrap_err();
+        assert!(matches!(errors.0[0].kind, ErrorKind::UnreadableFile { .. }));
+    }
+
+    #[test]
+    fn include_cycle_errors() {
+        let errors = errors(
+            ".include \"a.kittyasm\"",
+            &[
+                ("a.kittyasm", ".include \"b.kittyasm\""),
+                ("b.kittyasm", "nop\n.include \"a.kittyasm\""),
+            ],
+        );
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::IncludeCycle("a.kittyasm".to_string())
+        );
+        assert_eq!(errors.0[0].file.as_deref(), Some("b.kittyasm"));
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+
+    #[test]
+    fn include_without_quotes_errors() {
+        let errors = errors(".include a.kittyasm", &[]);
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("expected file name in quotes".to_string())
+        );
+    }
+
+    #[test]
+    fn incbin_past_end_errors() {
+        let errors = errors(".incbin \"two.bin\", 1, 2", &[("two.bin", "ab")]);
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 2,
+                min: 0,
+                max: 1
+            }
+        );
+        assert_eq!(errors.0[0].span.start.column, 23);
+    }
+}
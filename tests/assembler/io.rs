@@ -0,0 +1,39 @@
+mod registers {
+    use assembler::{Assembler, ErrorKind};
+    use common::io;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn names_load_addresses() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, IO.COMPOSITE_MODE
+            lethi   r1, IO.COMPOSITE_MODE
+        ",
+        );
+        assert_eq!(r1, io::COMPOSITE_MODE);
+    }
+
+    #[test]
+    fn names_are_constants_for_conditions() {
+        let bytes = Assembler::assemble(
+            r"
+            .if IO.FRAMEBUFFER
+                nop
+            .endif
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("nop").unwrap());
+    }
+
+    #[test]
+    fn unknown_register_errors() {
+        let errors = Assembler::assemble("data3 IO.NOTHING").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnknownLabel("IO.NOTHING".to_string())
+        );
+    }
+}
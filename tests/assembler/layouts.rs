@@ -0,0 +1,231 @@
+mod structs {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    const PLAYER: &str = r"
+        .struct Point
+            x:      .res2 1
+            y:      .res2 1
+        .endstruct
+        .struct Player
+            position:   .res sizeof(Point)
+            health:     .res 1
+            name:       .res 8
+            score:      .res3 1
+            path:       .res sizeof(Point) * 4
+        .endstruct
+    ";
+
+    #[test]
+    fn fields_are_offsets() {
+        let bytes = Assembler::assemble(&format!(
+            "{}data Point.x, Point.y, Player.position, Player.health, Player.name, Player.score, Player.path",
+            PLAYER
+        ))
+        .unwrap();
+        assert_eq!(bytes, [0, 2, 0, 4, 5, 13, 16]);
+    }
+
+    #[test]
+    fn sizeof_is_the_size_of_all_fields() {
+        let bytes =
+            Assembler::assemble(&format!("{}data sizeof(Point), sizeof(Player)", PLAYER)).unwrap();
+        assert_eq!(bytes, [4, 32]);
+    }
+
+    #[test]
+    fn structs_place_no_bytes() {
+        let bytes = Assembler::assemble(&format!("{}nop", PLAYER)).unwrap();
+        assert_eq!(bytes, Assembler::assemble("nop").unwrap());
+    }
+
+    #[test]
+    fn fields_are_immediates() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            .struct Record
+                id:     .res 1
+                value:  .res3 1
+            .endstruct
+                let     rA, record
+                load3   r1, rA, Record.value
+                let     pc, end
+            record:
+                data    1
+                data3   1234
+            end:
+        ",
+        );
+        assert_eq!(r1, 1234);
+    }
+
+    #[test]
+    fn fields_may_be_used_before_the_struct() {
+        let bytes = Assembler::assemble(
+            r"
+            data    Pair.second, sizeof(Pair)
+            .struct Pair
+                first:  .res3 1
+                second: .res3 1
+            .endstruct
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, [3, 6]);
+    }
+
+    #[test]
+    fn labels_inside_do_not_change_the_scope() {
+        let bytes = Assembler::assemble(
+            r"
+            main:
+            .struct Pair
+                first:  .res 1
+            .endstruct
+            .next:
+                let     r1, main.next
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("let r1, 0").unwrap());
+    }
+}
+
+mod enums {
+    use assembler::Assembler;
+
+    #[test]
+    fn names_count_from_zero() {
+        let bytes = Assembler::assemble(
+            r"
+            .enum Color
+                red
+                green
+                blue
+            .endenum
+            data Color.red, Color.green, Color.blue
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, [0, 1, 2]);
+    }
+
+    #[test]
+    fn names_count_on_from_a_value() {
+        let bytes = Assembler::assemble(
+            r"
+            .equ BASE, 8
+            .enum State
+                idle
+                running = BASE
+                jumping
+                falling = 0x20
+                landed
+            .endenum
+            data State.idle, State.running, State.jumping, State.falling, State.landed
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, [0, 8, 9, 0x20, 0x21]);
+    }
+
+    #[test]
+    fn names_are_immediates() {
+        let bytes = Assembler::assemble(
+            r"
+            .enum Kind
+                none
+                wall
+            .endenum
+            addi r1, r1, Kind.wall
+        ",
+        )
+        .unwrap();
+        assert_eq!(bytes, Assembler::assemble("addi r1, r1, 1").unwrap());
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn unclosed_struct() {
+        let errors = Assembler::assemble(".struct Point\n    x: .res 1").unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::UnclosedBlock(".struct".to_string()));
+        assert_eq!(error.kind.to_string(), "`.struct` is missing `.endstruct`");
+        assert_eq!(error.span.start.line, 1);
+    }
+
+    #[test]
+    fn end_without_start() {
+        let errors = Assembler::assemble(".struct Point\n.endenum\n.endstruct").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.endenum` without `.enum`".to_string())
+        );
+    }
+
+    #[test]
+    fn instructions_inside_struct() {
+        let errors = Assembler::assemble(".struct Point\n    nop\n.endstruct").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("only labels and `.res` can be inside `.struct`".to_string())
+        );
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+
+    #[test]
+    fn res_outside_struct() {
+        let errors = Assembler::assemble(".res 3").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.res` outside `.struct`".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_field() {
+        let errors = Assembler::assemble(".struct Point\n    x: .res 1\n    x: .res 1\n.endstruct")
+            .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::DuplicateSymbol("Point.x".to_string())
+        );
+    }
+
+    #[test]
+    fn local_label_named_like_a_field() {
+        let errors = Assembler::assemble(
+            ".struct P\n    a: .res 2\n    x: .res 1\n.endstruct\nP:\n    nop\n.x:",
+        )
+        .unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::DuplicateSymbol("P.x".to_string()));
+        assert_eq!(error.span.start.line, 7);
+    }
+
+    #[test]
+    fn unknown_struct() {
+        let errors = Assembler::assemble("data sizeof(Nothing)").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnknownLabel("sizeof(Nothing)".to_string())
+        );
+    }
+
+    #[test]
+    fn lone_names_outside_enum_are_unknown_instructions() {
+        let errors = Assembler::assemble("halt").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("unknown instruction `halt`".to_string())
+        );
+    }
+}
@@ -0,0 +1,602 @@
+mod objects {
+    use assembler::{Assembler, Object, RelocationKind, SectionKind};
+
+    #[test]
+    fn labels_are_offsets_into_their_section() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                .code
+                main:
+                    nop
+                .data
+                table:
+                    data 1, 2
+                .code
+                next:
+                    nop
+                ",
+            )
+            .unwrap();
+        let code = object.section(SectionKind::Code).unwrap();
+        let data = object.section(SectionKind::Data).unwrap();
+        assert_eq!(code.bytes.len(), 6);
+        assert_eq!(data.bytes, [1, 2]);
+        let symbols: Vec<_> = object
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.section, symbol.value))
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("main", Some(SectionKind::Code), 0),
+                ("table", Some(SectionKind::Data), 0),
+                ("next", Some(SectionKind::Code), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn distances_within_a_section_need_no_relocation() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                main:
+                    addi pc, pc, ~.skip
+                    nop
+                .skip:
+                    subi pc, pc, ~main
+                ",
+            )
+            .unwrap();
+        assert!(object.relocations.is_empty());
+    }
+
+    #[test]
+    fn addresses_are_relocated() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                .extern putc
+                main:
+                    jump putc
+                    letall r1, table + 2
+                .data
+                table:
+                    data 0
+                ",
+            )
+            .unwrap();
+        let relocations: Vec<_> = object
+            .relocations
+            .iter()
+            .map(|relocation| {
+                let kind = relocation.kind.clone();
+                (
+                    relocation.offset,
+                    kind,
+                    relocation.symbol.as_str(),
+                    relocation.addend,
+                )
+            })
+            .collect();
+        assert_eq!(
+            relocations,
+            [
+                (0, RelocationKind::Absolute, "putc", 0),
+                (3, RelocationKind::High, "putc", 0),
+                (9, RelocationKind::Absolute, ".data", 2),
+                (12, RelocationKind::High, ".data", 2),
+            ]
+        );
+        assert_eq!(object.externs, ["putc"]);
+    }
+
+    #[test]
+    fn distance_to_another_section_is_relative() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                main:
+                    nop
+                    let r1, ~table
+                .data
+                table:
+                    data 0
+                ",
+            )
+            .unwrap();
+        let relocation = &object.relocations[0];
+        assert_eq!(relocation.kind, RelocationKind::Relative);
+        assert_eq!(relocation.symbol, ".data");
+        assert_eq!(relocation.offset, 3);
+    }
+
+    #[test]
+    fn local_offset_across_sections_is_a_delta() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                .data
+                table:
+                    data 0
+                .code
+                    let r1, table~.entry
+                .entry:
+                ",
+            )
+            .unwrap();
+        let relocation = &object.relocations[0];
+        assert_eq!(relocation.kind, RelocationKind::Delta(".data".to_string()));
+        assert_eq!(relocation.symbol, ".code");
+        assert_eq!(relocation.addend, 3);
+    }
+
+    #[test]
+    fn branch_to_extern_becomes_far_branch() {
+        let object = Assembler::default()
+            .build_object(".extern far\nbranch far")
+            .unwrap();
+        let code = object.section(SectionKind::Code).unwrap();
+        assert_eq!(code.bytes.len(), 9);
+        assert_eq!(object.relocations.len(), 2);
+    }
+
+    #[test]
+    fn alignment_is_kept_for_the_linker() {
+        let object = Assembler::default()
+            .build_object(".data\ndata 1\n.align 4\ndata 2")
+            .unwrap();
+        assert_eq!(object.section(SectionKind::Data).unwrap().alignment, 4);
+        assert_eq!(object.section(SectionKind::Code).unwrap().alignment, 1);
+    }
+
+    #[test]
+    fn global_constants_are_exported() {
+        let object = Assembler::default()
+            .build_object(".global SIZE\n.equ SIZE, 12")
+            .unwrap();
+        assert_eq!(object.symbols[0].name, "SIZE");
+        assert_eq!(object.symbols[0].section, None);
+        assert_eq!(object.symbols[0].value, 12);
+        assert!(object.symbols[0].global);
+    }
+
+    #[test]
+    fn object_file_reads_back() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                .global main
+                .extern putc
+                main:
+                    call putc
+                .data
+                .align 2
+                message:
+                    data 'h', 'i', 0
+                ",
+            )
+            .unwrap();
+        assert_eq!(Object::read(&object.write()).unwrap(), object);
+    }
+}
+
+mod roms {
+    use assembler::{Assembler, Linker, Placement, SectionKind};
+    use common::REGISTER_LINK;
+
+    use crate::common::run_rom;
+
+    #[test]
+    fn objects_call_each_other() {
+        let main = Assembler::default()
+            .build_object(
+                r"
+                .extern double, answer
+                main:
+                    let r1, 21
+                    call double
+                    letall r2, answer
+                    load3 r3, r2, 0
+                .halt:
+                    subi pc, pc, ~.halt
+                ",
+            )
+            .unwrap();
+        let library = Assembler::default()
+            .build_object(
+                r"
+                .global double, answer
+                double:
+                    add r1, r1, r1
+                    ret
+                .data
+                answer:
+                    data3 0x123456
+                ",
+            )
+            .unwrap();
+        let linked = Linker::new()
+            .with_object("main", main)
+            .with_object("library", library)
+            .link()
+            .unwrap();
+        let registers = run_rom(linked.assembly.bytes());
+        assert_eq!(registers[1], 42);
+        assert_eq!(registers[3], 0x123456);
+    }
+
+    #[test]
+    fn conditional_call_returns_after_linking() {
+        let first = Assembler::default()
+            .build_object("let r1, 1\nnop")
+            .unwrap();
+        let second = Assembler::default()
+            .build_object(
+                r"
+                main:
+                    or r0, r0, r0
+                    ccall function
+                    let r2, 2
+                .halt:
+                    subi pc, pc, ~.halt
+                function:
+                    ret
+                ",
+            )
+            .unwrap();
+        let linked = Linker::new()
+            .with_object("first", first)
+            .with_object("second", second)
+            .link()
+            .unwrap();
+        let registers = run_rom(linked.assembly.bytes());
+        assert_eq!(registers[REGISTER_LINK as usize], 0x18);
+        assert_eq!(registers[2], 2);
+    }
+
+    #[test]
+    fn code_comes_before_data() {
+        let first = Assembler::default()
+            .build_object(".data\ndata 1\n.code\nnop")
+            .unwrap();
+        let second = Assembler::default()
+            .build_object(".data\n.align 4\ndata 2\n.code\nnop")
+            .unwrap();
+        let linked = Linker::new()
+            .with_origin(0x100)
+            .with_object("first", first)
+            .with_object("second", second)
+            .link()
+            .unwrap();
+        let placement = |object: &str, section, address, length| Placement {
+            object: object.to_string(),
+            section,
+            address,
+            length,
+        };
+        assert_eq!(
+            linked.placements,
+            [
+                placement("first", SectionKind::Code, 0x100, 3),
+                placement("second", SectionKind::Code, 0x103, 3),
+                placement("first", SectionKind::Data, 0x106, 1),
+                placement("second", SectionKind::Data, 0x108, 1),
+            ]
+        );
+        assert_eq!(linked.assembly.segments[0].address, 0x100);
+    }
+
+    #[test]
+    fn data_address_moves_data() {
+        let object = Assembler::default()
+            .build_object("nop\n.data\ntable:\ndata 7")
+            .unwrap();
+        let linked = Linker::new()
+            .with_data_address(0x1000)
+            .with_object("only", object)
+            .link()
+            .unwrap();
+        assert_eq!(linked.assembly.segments.len(), 2);
+        assert_eq!(linked.assembly.segments[1].address, 0x1000);
+        assert_eq!(linked.assembly.symbolize(0x1000).unwrap(), "table");
+    }
+
+    #[test]
+    fn relocations_in_another_section_resolve() {
+        let object = Assembler::default()
+            .build_object(
+                r"
+                main:
+                    let r1, ~table
+                    let r2, main~.entry
+                .halt:
+                    subi pc, pc, ~.halt
+                .data
+                    data 0, 0
+                .entry:
+                table:
+                    data 0
+                ",
+            )
+            .unwrap();
+        let linked = Linker::new().with_object("only", object).link().unwrap();
+        let registers = run_rom(linked.assembly.bytes());
+        // `table` lies 2 bytes into the data after 9 bytes of code.
+        assert_eq!(registers[1], 11 - 3);
+        assert_eq!(registers[2], 11);
+    }
+
+    #[test]
+    fn pointer_tables_are_relocated() {
+        let main = Assembler::default()
+            .build_object(".extern handler\nnop\n.data\ndata 7\ntable:\ndata3 handler, table")
+            .unwrap();
+        let relocation = &main.relocations[0];
+        assert_eq!(
+            (relocation.offset, relocation.length, relocation.bytes),
+            (1, 24, 3)
+        );
+        let library = Assembler::default()
+            .build_object(".global handler\nnop\nhandler:\nnop")
+            .unwrap();
+        let linked = Linker::new()
+            .with_object("main", main)
+            .with_object("library", library)
+            .link()
+            .unwrap();
+        // Code of both objects, then the data of `main`.
+        let bytes = linked.assembly.bytes();
+        assert_eq!(bytes[9..], [7, 0, 0, 6, 0, 0, 10]);
+    }
+
+    #[test]
+    fn link_map_lists_sections_and_symbols() {
+        let object = Assembler::default()
+            .build_object("main:\nnop\n.data\ntable:\ndata 1")
+            .unwrap();
+        let linked = Linker::new().with_object("boot", object).link().unwrap();
+        assert_eq!(
+            linked.map(),
+            "000000      3 code boot\n000003      1 data boot\n\n000000 main\n000003 table\n"
+        );
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Linker, Location, SectionKind, WarningKind};
+
+    #[test]
+    fn undefined_extern_points_to_operand() {
+        let object = Assembler::default()
+            .build_object(".extern missing\n    jump missing")
+            .unwrap();
+        let errors = Linker::new()
+            .with_object("main.kittyasm", object)
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UndefinedExtern("missing".to_string())
+        );
+        assert_eq!(errors.0[0].file.as_deref(), Some("main.kittyasm"));
+        assert_eq!(
+            errors.0[0].span.start,
+            Location {
+                line: 2,
+                column: 10
+            }
+        );
+    }
+
+    #[test]
+    fn duplicate_globals_error() {
+        let object = || {
+            Assembler::default()
+                .build_object(".global main\nmain:\nnop")
+                .unwrap()
+        };
+        let errors = Linker::new()
+            .with_object("a", object())
+            .with_object("b", object())
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::DuplicateSymbol("main".to_string())
+        );
+        assert_eq!(errors.0[0].file.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn out_of_range_after_linking_errors() {
+        let object = Assembler::default()
+            .build_object(".extern far\naddi r1, r1, far")
+            .unwrap();
+        let library = Assembler::default()
+            .build_object(".global far\n.space 100\nfar:")
+            .unwrap();
+        let errors = Linker::new()
+            .with_object("main", object)
+            .with_object("library", library)
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 103,
+                min: 0,
+                max: 63
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_let_warns_after_linking() {
+        let object = Assembler::default()
+            .build_object(".extern far\nlet r1, far")
+            .unwrap();
+        let library = Assembler::default()
+            .build_object(".global far\n.space 5000\nfar:")
+            .unwrap();
+        let linked = Linker::new()
+            .with_object("main", object)
+            .with_object("library", library)
+            .link()
+            .unwrap();
+        assert_eq!(
+            linked.assembly.warnings[0].kind,
+            WarningKind::Truncated {
+                value: 5003,
+                bits: 12
+            }
+        );
+    }
+
+    #[test]
+    fn org_is_rejected_in_objects() {
+        let errors = Assembler::default()
+            .build_object(".org 0x100\nnop")
+            .unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::FixedAddress);
+    }
+
+    #[test]
+    fn layout_cannot_depend_on_addresses() {
+        let errors = Assembler::default()
+            .build_object("main:\n.space main")
+            .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::NotRelocatable("main".to_string())
+        );
+    }
+
+    #[test]
+    fn global_must_be_defined() {
+        let errors = Assembler::default()
+            .build_object(".global nowhere")
+            .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnknownLabel("nowhere".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_object_file_errors() {
+        let error = assembler::Object::read("kitty24 object\nbytes code 000000 12").unwrap_err();
+        assert_eq!(
+            error.kind,
+            ErrorKind::InvalidObject("bytes of an undeclared section".to_string())
+        );
+        assert_eq!(error.span.start.line, 2);
+    }
+
+    #[test]
+    fn records_of_undeclared_sections_error() {
+        let object = Assembler::default()
+            .build_object(".data\ndata3 main\n.code\nmain:\nnop")
+            .unwrap();
+        let mut text = String::from("kitty24 object\nsection code 1\n");
+        let relocation = object.write();
+        let relocation = relocation
+            .lines()
+            .find(|line| line.starts_with("relocation "))
+            .unwrap();
+        for (record, reason) in [
+            ("symbol foo data 000000 global", "symbol of an undeclared section"),
+            (relocation, "relocation of an undeclared section"),
+        ] {
+            let error = assembler::Object::read(&(text.clone() + record)).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::InvalidObject(reason.to_string()));
+            assert_eq!(error.span.start.line, 3);
+        }
+        text.push_str("section data 1\n");
+        assert!(assembler::Object::read(&(text + relocation)).is_ok());
+    }
+
+    #[test]
+    fn sections_missing_from_objects_error() {
+        let mut object = Assembler::default()
+            .build_object(".global table\n.data\ntable:\ndata3 main\n.code\nmain:\nnop")
+            .unwrap();
+        object
+            .sections
+            .retain(|section| section.kind == SectionKind::Code);
+        let errors = Linker::new()
+            .with_object("main", object)
+            .link()
+            .err()
+            .unwrap();
+        let kinds: Vec<_> = errors.0.iter().map(|error| error.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                ErrorKind::InvalidObject("symbol of an undeclared section".to_string()),
+                ErrorKind::InvalidObject("relocation of an undeclared section".to_string()),
+            ]
+        );
+        assert_eq!(errors.0[0].file.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn last_address_may_be_linked() {
+        let object = Assembler::default().build_object("nop\nnop").unwrap();
+        let linked = Linker::new()
+            .with_origin(0xFFFFFA)
+            .with_object("main", object)
+            .link()
+            .ok()
+            .unwrap();
+        assert_eq!(linked.placements[0].address, 0xFFFFFA);
+        assert_eq!(linked.placements[0].length, 6);
+    }
+
+    #[test]
+    fn sections_past_memory_error() {
+        let object = || {
+            Assembler::default()
+                .build_object("nop\nnop\n.data\ndata 1, 2")
+                .unwrap()
+        };
+        let errors = Linker::new()
+            .with_origin(0xFFFFFE)
+            .with_object("main", object())
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(errors.0[0].kind, ErrorKind::OutsideAddressSpace);
+        assert_eq!(errors.0[0].file.as_deref(), Some("main"));
+        let errors = Linker::new()
+            .with_object("main", object())
+            .with_data_address(0xFFFFFF)
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(errors.0[0].kind, ErrorKind::OutsideAddressSpace);
+    }
+
+    #[test]
+    fn origin_past_memory_errors() {
+        let object = Assembler::default().build_object("nop").unwrap();
+        let errors = Linker::new()
+            .with_origin(0x1000000)
+            .with_object("main", object)
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(errors.0[0].kind, ErrorKind::OutsideAddressSpace);
+    }
+
+    #[test]
+    fn programs_ignore_sections() {
+        let bytes = Assembler::assemble(".data\ndata 1\n.code\ndata 2").unwrap();
+        assert_eq!(bytes, [1, 2]);
+    }
+}
@@ -0,0 +1,298 @@
+mod instructions {
+    use assembler::{Assembler, WarningKind};
+
+    /// Names of the lints reported for `source`, with their lines.
+    fn lints(source: &str) -> Vec<(&'static str, usize)> {
+        let assembly = Assembler::default()
+            .with_lints()
+            .build(source)
+            .ok()
+            .unwrap();
+        assembly
+            .warnings
+            .iter()
+            .map(|warning| match &warning.kind {
+                WarningKind::Lint(lint) => (lint.name(), warning.span.start.line),
+                kind => panic!("{}", kind),
+            })
+            .collect()
+    }
+
+    const VECTOR: &str = r"
+        lessi   rF, ir, 0
+        caddi   pc, pc, ~main
+        lessi   rF, ir, 4
+        caddi   pc, pc, ~vblank
+        let     ir, 0
+    ";
+
+    #[test]
+    fn conditional_without_condition() {
+        let lints = lints(
+            r"
+            start:  ; lint: allow(unused-label)
+                let     r1, 5
+                caddi   r2, r2, 1
+                lessi   r3, r1, 5
+                caddi   r2, r2, 1
+                clet    r4, 0
+            ",
+        );
+        assert_eq!(lints, [("unset-condition", 4)]);
+    }
+
+    #[test]
+    fn labels_forget_the_condition() {
+        let lints = lints(
+            r"
+                subi    r1, r1, 1
+            next:
+                cjump   next
+            ",
+        );
+        assert_eq!(lints, [("unset-condition", 4)]);
+    }
+
+    #[test]
+    fn interrupt_writes_in_main() {
+        let lints = lints(&format!(
+            r"{}
+            main:
+                let     r1, 1
+            loop:
+                let     ir, 8
+                subi    pc, pc, ~loop
+            vblank:
+                addi    r2, r2, 1
+                let     ir, 0
+            ",
+            VECTOR
+        ));
+        assert_eq!(lints, [("interrupt-write", 11)]);
+    }
+
+    #[test]
+    fn global_writes_in_handlers() {
+        let lints = lints(&format!(
+            r"{}
+            main:
+                let     sp, 0xF00
+                subi    pc, pc, ~main
+            vblank:
+                push    r1
+                let     rB, helper
+                lethi   rB, helper
+                addi    rA, pc, ~.back
+                ori     pc, rB, 0
+            .back:
+                pop     r1
+                let     ir, 0
+            helper:
+                let     sp, 0
+                ori     pc, rA, 0
+            ",
+            VECTOR
+        ));
+        assert_eq!(lints, [("global-write", 21)]);
+    }
+
+    #[test]
+    fn code_after_jumps() {
+        let lints = lints(
+            r"
+            start:
+                jump    start
+                let     r1, 1
+                let     r2, 2
+            again:
+                subi    pc, pc, ~again
+                addi    r3, r3, 1
+            ",
+        );
+        assert_eq!(lints, [("unreachable-code", 4), ("unreachable-code", 8)]);
+    }
+
+    #[test]
+    fn code_after_calls_is_reached() {
+        let lints = lints(
+            r"
+                call    function
+                let     r1, 1
+            loop:
+                subi    pc, pc, ~loop
+            function:
+                ret
+                data    1, 2, 3
+            ",
+        );
+        assert_eq!(lints, []);
+    }
+
+    #[test]
+    fn only_when_asked() {
+        let assembly = Assembler::default()
+            .build("let r1, 1\ncaddi r1, r1, 1\nunused:")
+            .ok()
+            .unwrap();
+        assert_eq!(assembly.warnings, []);
+    }
+}
+
+mod labels {
+    use assembler::{Assembler, Lint, WarningKind};
+
+    fn lints(source: &str) -> Vec<Lint> {
+        let assembly = Assembler::default()
+            .with_lints()
+            .build(source)
+            .ok()
+            .unwrap();
+        assembly
+            .warnings
+            .into_iter()
+            .map(|warning| match warning.kind {
+                WarningKind::Lint(lint) => lint,
+                kind => panic!("{}", kind),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duplicates() {
+        let lints = lints(
+            r"
+            main:
+                subi    pc, pc, ~main
+            main:
+                data    1
+            ",
+        );
+        assert_eq!(lints, [Lint::DuplicateLabel("main".to_string())]);
+    }
+
+    #[test]
+    fn duplicate_locals() {
+        let lints = lints(
+            r"
+            main:
+            .loop:
+                subi    pc, pc, ~.loop
+            .loop:
+                data    main
+            ",
+        );
+        assert_eq!(lints, [Lint::DuplicateLabel("main.loop".to_string())]);
+    }
+
+    #[test]
+    fn unused() {
+        let lints = lints(
+            r"
+            main:
+                let     r1, table.first
+            .unused:
+                subi    pc, pc, ~main
+            table:
+            .first:
+                data    1
+            .second:
+                data    2
+            ",
+        );
+        assert_eq!(
+            lints,
+            [
+                Lint::UnusedLabel("main.unused".to_string()),
+                Lint::UnusedLabel("table".to_string()),
+                Lint::UnusedLabel("table.second".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn used_by_constants_distances_and_branches() {
+        let lints = lints(
+            r"
+            .equ    START, main
+            main:
+                let     r1, table~.end
+                branch  .end
+            .end:
+                subi    pc, pc, ~main
+            table:
+                data    START
+            .end:
+            ",
+        );
+        assert_eq!(lints, []);
+    }
+
+    #[test]
+    fn message() {
+        let assembly = Assembler::default()
+            .with_lints()
+            .build("unused:\n    nop")
+            .ok()
+            .unwrap();
+        assert_eq!(
+            assembly.warnings[0].to_string(),
+            "1:1: warning: label `unused` is never referred to [unused-label]"
+        );
+    }
+}
+
+mod suppression {
+    use assembler::{Assembler, MemoryResolver};
+
+    #[test]
+    fn allowed_on_the_line() {
+        let assembly = Assembler::default()
+            .with_lints()
+            .build(
+                r"
+                entry:  ; lint: allow(unused-label)
+                    let     r1, 1
+                    caddi   r1, r1, 1   ; lint: allow(global-write, unset-condition)
+                ",
+            )
+            .ok()
+            .unwrap();
+        assert_eq!(assembly.warnings, []);
+    }
+
+    #[test]
+    fn only_the_named_lint() {
+        let assembly = Assembler::default()
+            .with_lints()
+            .build("entry:  ; lint: allow(unset-condition)\n    nop")
+            .ok()
+            .unwrap();
+        assert_eq!(assembly.warnings.len(), 1);
+        assert_eq!(assembly.warnings[0].span.start.line, 1);
+    }
+
+    #[test]
+    fn in_macro_bodies_and_includes() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert(
+            "lib.kittyasm",
+            "helper: ; lint: allow(unused-label)\n    ret",
+        );
+        let assembly = Assembler::default()
+            .with_resolver(resolver)
+            .with_lints()
+            .build(
+                r#"
+                .macro  skip
+                    cret    ; lint: allow(unset-condition)
+                .endm
+                    skip
+                    skip
+                .include "lib.kittyasm"
+                "#,
+            )
+            .ok()
+            .unwrap();
+        assert_eq!(assembly.warnings, []);
+    }
+}
@@ -0,0 +1,91 @@
+mod rows {
+    use assembler::{Assembler, MemoryResolver};
+
+    fn listing(source: &str) -> String {
+        let assembly = Assembler::default().with_listing().build(source).unwrap();
+        assembly.listing.unwrap()
+    }
+
+    #[test]
+    fn list_address_bytes_fields_and_source() {
+        let source = "main:\n    let r1, 1\n    addi r1, r1, 2";
+        assert_eq!(
+            listing(source),
+            "\
+line  address  bytes   c op r  s  u    source
+1     000000                           main:
+2     000000   001001  0 00 01 00 01       let r1, 1
+3     000003   541042  0 25 01 01 02       addi r1, r1, 2
+"
+        );
+    }
+
+    #[test]
+    fn list_resolved_symbols() {
+        let source = ".equ STEP, 2\nlet r1, end\naddi r1, r1, STEP\nend:";
+        let listing = listing(source);
+        assert!(
+            listing.contains("let r1, end  ; end = 000006\n"),
+            "{}",
+            listing
+        );
+        assert!(
+            listing.contains("addi r1, r1, STEP  ; STEP = 2\n"),
+            "{}",
+            listing
+        );
+    }
+
+    #[test]
+    fn list_data_three_bytes_per_row() {
+        let listing = listing("data 1, 2, 3, 4");
+        let rows: Vec<_> = listing.lines().skip(1).collect();
+        assert_eq!(
+            rows,
+            [
+                "1     000000   010203                  data 1, 2, 3, 4",
+                "               04"
+            ]
+        );
+    }
+
+    #[test]
+    fn list_every_instruction_of_pseudo_instruction() {
+        let listing = listing("nop\nret");
+        let rows: Vec<_> = listing.lines().skip(1).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[1].starts_with("2     000003"), "{}", listing);
+    }
+
+    #[test]
+    fn list_addresses_after_org() {
+        let listing = listing(".org 0x100\ndata 1");
+        assert!(listing.contains("2     000100   01"), "{}", listing);
+    }
+
+    #[test]
+    fn list_expanded_macros() {
+        let listing = listing(".macro twice value\n    data \\value, \\value\n.endm\ntwice 7");
+        assert!(listing.contains("2     000000   0707"), "{}", listing);
+        assert!(listing.contains("data 7, 7"), "{}", listing);
+    }
+
+    #[test]
+    fn list_included_files_by_name() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("one.kittyasm", "data 1");
+        let assembly = Assembler::default()
+            .with_resolver(resolver)
+            .with_listing()
+            .build("nop\n.include \"one.kittyasm\"")
+            .unwrap();
+        let listing = assembly.listing.unwrap();
+        assert!(listing.contains("one.kittyasm:1  000003"), "{}", listing);
+    }
+
+    #[test]
+    fn listing_is_optional() {
+        let assembly = Assembler::default().build("nop").unwrap();
+        assert_eq!(assembly.listing, None);
+    }
+}
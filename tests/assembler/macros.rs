@@ -0,0 +1,248 @@
+mod expansion {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn substitute_parameters() {
+        let registers = run_virtual_machine(
+            r"
+            .macro  add3 dst, a, b
+                add \dst, \a, \b
+                addi \dst, \dst, 3
+            .endm
+                let r1, 10
+                let r2, 20
+                add3 r3, r1, r2
+            ",
+        );
+        assert_eq!(registers[3], 33);
+    }
+
+    #[test]
+    fn expand_to_same_code_as_written() {
+        let expanded = Assembler::assemble(
+            r"
+            .macro  clear register
+                xor \register, \register, \register
+            .endm
+                clear r1
+                clear r2
+            ",
+        )
+        .unwrap();
+        let written = Assembler::assemble("xor r1, r1, r1\nxor r2, r2, r2").unwrap();
+        assert_eq!(expanded, written);
+    }
+
+    #[test]
+    fn arguments_may_be_expressions() {
+        let registers = run_virtual_machine(
+            r"
+            .macro  load register, value
+                let \register, \value
+            .endm
+                load r1, (1 + 2) * 3
+                load r2, 'A'
+            ",
+        );
+        assert_eq!(registers[1], 9);
+        assert_eq!(registers[2], 65);
+    }
+
+    #[test]
+    fn local_labels_are_unique_per_expansion() {
+        let registers = run_virtual_machine(
+            r"
+            .macro  skip register
+                    addi pc, pc, ~.over
+                    let \register, 1
+                .over:
+            .endm
+                skip r1
+                skip r2
+            ",
+        );
+        assert_eq!(registers[1], 0);
+        assert_eq!(registers[2], 0);
+    }
+
+    #[test]
+    fn expansion_number_makes_names_unique() {
+        let registers = run_virtual_machine(
+            r"
+            .macro  numbered register
+                    let \register, value\@
+                    addi pc, pc, ~after\@
+                value\@:
+                    data3 0
+                after\@:
+            .endm
+                numbered r1
+                numbered r2
+            ",
+        );
+        assert_ne!(registers[1], registers[2]);
+    }
+
+    #[test]
+    fn macros_invoke_other_macros() {
+        let registers = run_virtual_machine(
+            r"
+            .macro  double register
+                add \register, \register, \register
+            .endm
+            .macro  quadruple register
+                double \register
+                double \register
+            .endm
+                let r1, 3
+                quadruple r1
+            ",
+        );
+        assert_eq!(registers[1], 12);
+    }
+
+    #[test]
+    fn comments_in_body_are_kept_as_written() {
+        let registers = run_virtual_machine(
+            r"
+            .macro  set register ; \register is not substituted here
+                let \register, 7 ; nor \register here
+            .endm
+                set r1
+            ",
+        );
+        assert_eq!(registers[1], 7);
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn error_in_body_points_to_body_and_invocation() {
+        let source = ".macro load register\n    addi \\register, \\register, 100\n.endm\n\nload r1";
+        let errors = Assembler::assemble(source).unwrap_err();
+        let error = &errors.0[0];
+        assert_eq!(
+            error.kind,
+            ErrorKind::OutOfRange {
+                value: 100,
+                min: 0,
+                max: 63
+            }
+        );
+        assert_eq!(
+            error.span.start,
+            Location {
+                line: 2,
+                column: 32
+            }
+        );
+        assert_eq!(
+            error.span.end,
+            Location {
+                line: 2,
+                column: 35
+            }
+        );
+        assert_eq!(error.expansions.len(), 1);
+        assert_eq!(error.expansions[0].name, "load");
+        assert_eq!(
+            error.expansions[0].span.start,
+            Location { line: 5, column: 1 }
+        );
+    }
+
+    #[test]
+    fn error_in_argument_points_to_invocation() {
+        let source = ".macro load register\n    let \\register, 1\n.endm\nload r99";
+        let errors = Assembler::assemble(source).unwrap_err();
+        assert_eq!(errors.0[0].span.start.line, 2);
+        assert_eq!(errors.0[0].expansions[0].span.start.line, 4);
+    }
+
+    #[test]
+    fn nested_expansions_are_listed_innermost_first() {
+        let source =
+            ".macro inner\n    addi r1, r1, 100\n.endm\n.macro outer\n    inner\n.endm\nouter";
+        let errors = Assembler::assemble(source).unwrap_err();
+        let names: Vec<_> = errors.0[0]
+            .expansions
+            .iter()
+            .map(|expansion| expansion.name.as_str())
+            .collect();
+        assert_eq!(names, ["inner", "outer"]);
+        assert_eq!(errors.0[0].expansions[0].span.start.line, 5);
+        assert_eq!(errors.0[0].expansions[1].span.start.line, 7);
+    }
+
+    #[test]
+    fn display_mentions_expansion() {
+        let source = ".macro load\n    addi r1, r1, 100\n.endm\nload";
+        let errors = Assembler::assemble(source).unwrap_err();
+        let message = errors.to_string();
+        assert!(
+            message.contains("in expansion of macro `load`"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn wrong_argument_count_errors() {
+        let source = ".macro pair a, b\n    data \\a, \\b\n.endm\npair 1";
+        let errors = Assembler::assemble(source).unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::MacroArguments {
+                name: "pair".to_string(),
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 4, column: 1 });
+    }
+
+    #[test]
+    fn unclosed_macro_errors() {
+        let errors = Assembler::assemble(".macro forever\n    nop").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnclosedMacro("forever".to_string())
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 8 });
+    }
+
+    #[test]
+    fn endm_without_macro_errors() {
+        let errors = Assembler::assemble("nop\n.endm").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.endm` without `.macro`".to_string())
+        );
+        assert_eq!(errors.0[0].span.start.line, 2);
+    }
+
+    #[test]
+    fn duplicate_macro_errors() {
+        let source = ".macro twice\n.endm\n.macro twice\n.endm";
+        let errors = Assembler::assemble(source).unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::DuplicateSymbol("twice".to_string())
+        );
+        assert_eq!(errors.0[0].span.start.line, 3);
+    }
+
+    #[test]
+    fn recursive_macro_errors() {
+        let source = ".macro forever\n    nop\n    forever\n.endm\nforever";
+        let errors = Assembler::assemble(source).unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::MacroRecursion("forever".to_string())
+        );
+    }
+}
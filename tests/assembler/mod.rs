@@ -1,2 +1,26 @@
+mod aliases;
+mod anonymous;
+mod ast;
+mod branches;
+mod conditionals;
+#[allow(clippy::module_inception)]
 mod data;
+mod disassembler;
+mod errors;
+mod expressions;
+mod fixed;
+mod format;
+mod includes;
+mod io;
 mod labels;
+mod layouts;
+mod linking;
+mod lints;
+mod listing;
+mod macros;
+mod placement;
+mod pseudo;
+mod ranges;
+mod relaxation;
+mod strings;
+mod symbols;
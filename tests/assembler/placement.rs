@@ -0,0 +1,222 @@
+mod org {
+    use assembler::{Assembler, Segment};
+
+    #[test]
+    fn org_places_next_bytes_at_address() {
+        let bytes = Assembler::assemble("data 1\n.org 4\ndata 2").unwrap();
+        assert_eq!(bytes, [1, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn org_output_is_sparse() {
+        let assembly = Assembler::default()
+            .build("data 1\n.org 0xFB0000\ndata 2, 3")
+            .unwrap();
+        assert_eq!(
+            assembly.segments,
+            [
+                Segment {
+                    address: 0,
+                    bytes: vec![1]
+                },
+                Segment {
+                    address: 0xFB0000,
+                    bytes: vec![2, 3]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_are_in_order_of_address() {
+        let assembly = Assembler::default()
+            .build(".org 0x20\ndata 2\n.org 0x10\ndata 1")
+            .unwrap();
+        let addresses: Vec<_> = assembly
+            .segments
+            .iter()
+            .map(|segment| segment.address)
+            .collect();
+        assert_eq!(addresses, [0x10, 0x20]);
+    }
+
+    #[test]
+    fn labels_take_address_from_org() {
+        let bytes = Assembler::assemble(".org 0x100\nstart:\nlet r1, start").unwrap();
+        let expected = Assembler::assemble("let r1, 0x100").unwrap();
+        assert_eq!(bytes[0x100..], expected);
+    }
+
+    #[test]
+    fn org_may_move_backward_into_gap() {
+        let bytes = Assembler::assemble("data 1\n.org 3\ndata 3\n.org 1\ndata 2").unwrap();
+        assert_eq!(bytes, [1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn org_accepts_earlier_labels() {
+        let bytes = Assembler::assemble("start:\ndata 1\n.org start + 2\ndata 2").unwrap();
+        assert_eq!(bytes, [1, 0, 2]);
+    }
+
+    #[test]
+    fn org_to_current_address_continues_segment() {
+        let assembly = Assembler::default()
+            .build("data 1\n.org 1\ndata 2")
+            .unwrap();
+        assert_eq!(assembly.segments.len(), 1);
+    }
+}
+
+mod reserve {
+    use assembler::Assembler;
+
+    #[test]
+    fn align_pads_to_multiple() {
+        let bytes = Assembler::assemble("data 1\n.align 4\ndata 2").unwrap();
+        assert_eq!(bytes, [1, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn align_when_aligned_adds_nothing() {
+        let bytes = Assembler::assemble("data 1, 2, 3\n.align 3\ndata 4").unwrap();
+        assert_eq!(bytes, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn fill_repeats_byte() {
+        let bytes = Assembler::assemble("data 1\n.fill 3, 0xAA").unwrap();
+        assert_eq!(bytes, [1, 0xAA, 0xAA, 0xAA]);
+    }
+
+    #[test]
+    fn space_places_zeros() {
+        let bytes = Assembler::assemble("data 1\n.space 2\ndata 2").unwrap();
+        assert_eq!(bytes, [1, 0, 0, 2]);
+    }
+
+    #[test]
+    fn labels_after_space_account_for_it() {
+        let bytes = Assembler::assemble(".space 0x10\nend:\nlet r1, end").unwrap();
+        let expected = Assembler::assemble("let r1, 0x10").unwrap();
+        assert_eq!(bytes[0x10..], expected);
+    }
+
+    #[test]
+    fn count_accepts_constants() {
+        let bytes = Assembler::assemble(".equ SIZE, 2\n.fill SIZE, 7").unwrap();
+        assert_eq!(bytes, [7, 7]);
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn overlapping_org_errors() {
+        let errors = Assembler::assemble("data 1, 2, 3\n.org 1\ndata 4").unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::Overlap { start: 1, end: 2 });
+        assert_eq!(error.span.start, Location { line: 2, column: 1 });
+        assert_eq!(
+            error.to_string(),
+            "2:1: bytes from 0x000001 to 0x000001 are already placed"
+        );
+    }
+
+    #[test]
+    fn overlap_with_later_segment_errors() {
+        let source = ".org 4\ndata 1, 2\n.org 0\n.space 5";
+        let errors = Assembler::assemble(source).unwrap_err();
+        assert_eq!(errors.0[0].kind, ErrorKind::Overlap { start: 4, end: 5 });
+        assert_eq!(errors.0[0].span.start.line, 3);
+    }
+
+    #[test]
+    fn org_past_memory_errors() {
+        let errors = Assembler::assemble(".org 0x1000000").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 0x1000000,
+                min: 0,
+                max: 0xFFFFFF
+            }
+        );
+    }
+
+    #[test]
+    fn bytes_past_memory_error() {
+        let errors = Assembler::assemble("nop\n.org 0xFFFFFF\ndata3 0xABCDEF").unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(error.kind, ErrorKind::OutsideAddressSpace);
+        assert_eq!(error.span.start, Location { line: 3, column: 1 });
+        assert_eq!(
+            error.to_string(),
+            "3:1: bytes are placed past the last address, 0xFFFFFF"
+        );
+    }
+
+    #[test]
+    fn last_address_may_hold_a_byte() {
+        assert!(Assembler::assemble(".org 0xFFFFFF\ndata 1").is_ok());
+    }
+
+    #[test]
+    fn space_past_memory_errors() {
+        let errors =
+            Assembler::assemble(".space 0xFFFFFF\n.space 0xFFFFFF\n.space 0xFFFFFF").unwrap_err();
+        let lines: Vec<_> = errors.0.iter().map(|error| error.span.start.line).collect();
+        assert_eq!(lines, [2, 3]);
+        assert!(errors
+            .0
+            .iter()
+            .all(|error| error.kind == ErrorKind::OutsideAddressSpace));
+    }
+
+    #[test]
+    fn align_to_zero_errors() {
+        let errors = Assembler::assemble(".align 0").unwrap_err();
+        assert!(matches!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange { value: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn fill_value_out_of_range_errors() {
+        let errors = Assembler::assemble(".fill 2, 256").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 256,
+                min: -128,
+                max: 255
+            }
+        );
+        assert_eq!(errors.0[0].span.start.column, 10);
+        assert_eq!(errors.0[0].span.end, Location { line: 1, column: 13 });
+    }
+
+    #[test]
+    fn org_before_label_errors() {
+        let errors = Assembler::assemble(".org later\nlater:").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::UnknownLabel("later".to_string())
+        );
+    }
+
+    #[test]
+    fn fill_without_value_errors() {
+        let errors = Assembler::assemble(".fill 2").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`.fill` expects 2 operands, found 1".to_string())
+        );
+    }
+}
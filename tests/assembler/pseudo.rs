@@ -0,0 +1,326 @@
+mod letall {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn loads_all_24_bits() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            letall  r1, 0xABCDEF
+        ",
+        );
+        assert_eq!(r1, 0xABCDEF);
+    }
+
+    #[test]
+    fn loads_label_address() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            letall  r1, address
+            load    r2, r1, 0
+            jump    address.end
+            address:
+                data    37
+            .end:
+        ",
+        );
+        assert_eq!(r2, 37);
+        assert_ne!(r1, 0);
+    }
+
+    #[test]
+    fn conditionally_loads_all_24_bits() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            or      r0, r0, r0
+            cletall r1, 0xABCDEF
+        ",
+        );
+        assert_eq!(r1, 0xABCDEF);
+    }
+
+    #[test]
+    fn conditionally_does_not_load() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            cletall r1, 0xABCDEF
+        ",
+        );
+        assert_eq!(r1, 17);
+    }
+}
+
+mod jump {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn jumps_further_than_12_bits() {
+        let [_, r1, ..] = run_virtual_machine(
+            &(r"
+            let     r1, 17
+            jump    far
+            "
+            .to_string()
+                + &"data3 0\n".repeat(2000)
+                + r"
+            let     r1, 34
+            far:
+        "),
+        );
+        assert_eq!(r1, 17);
+    }
+
+    #[test]
+    fn conditionally_jumps() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            or      r0, r0, r0
+            cjump   skip
+            let     r1, 34
+            skip:
+        ",
+        );
+        assert_eq!(r1, 17);
+    }
+
+    #[test]
+    fn conditionally_does_not_jump() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 17
+            cjump   skip
+            let     r1, 34
+            skip:
+        ",
+        );
+        assert_eq!(r1, 34);
+    }
+}
+
+mod call {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn returns_after_call() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     r1, 1
+            call    double
+            call    double
+            let     r2, 17
+            jump    end
+            double:
+                add     r1, r1, r1
+                ret
+            end:
+        ",
+        );
+        assert_eq!(r1, 4);
+        assert_eq!(r2, 17);
+    }
+
+    #[test]
+    fn conditionally_calls() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     r1, 1
+            or      r0, r0, r0
+            ccall   double
+            let     r2, 17
+            jump    end
+            double:
+                add     r1, r1, r1
+                ret
+            end:
+        ",
+        );
+        assert_eq!(r1, 2);
+        assert_eq!(r2, 17);
+    }
+
+    #[test]
+    fn conditionally_does_not_call() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     r1, 1
+            ccall   double
+            let     r2, 17
+            jump    end
+            double:
+                add     r1, r1, r1
+                ret
+            end:
+        ",
+        );
+        assert_eq!(r1, 1);
+        assert_eq!(r2, 17);
+    }
+
+    #[test]
+    fn conditionally_returns() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r1, 1
+            call    double_once
+            jump    end
+            double_once:
+                add     r1, r1, r1
+                lessi   r2, r1, 2
+                cret
+                add     r1, r1, r1
+                ret
+            end:
+        ",
+        );
+        assert_eq!(r1, 2);
+    }
+}
+
+mod mov {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn copies_register() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            r"
+            let     r2, 17
+            mov     r1, r2
+        ",
+        );
+        assert_eq!(r1, 17);
+        assert_eq!(r2, 17);
+    }
+
+    #[test]
+    fn keeps_condition() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r2, 17
+            or      r0, r0, r0
+            mov     r1, r2
+            clet    r1, 34
+        ",
+        );
+        assert_eq!(r1, 34);
+    }
+
+    #[test]
+    fn conditionally_does_not_copy() {
+        let [_, r1, ..] = run_virtual_machine(
+            r"
+            let     r2, 17
+            cmov    r1, r2
+        ",
+        );
+        assert_eq!(r1, 0);
+    }
+}
+
+mod nop {
+    use assembler::Assembler;
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn does_nothing() {
+        let [sp, r1, ..] = run_virtual_machine(
+            r"
+            let     sp, 3
+            let     r1, 17
+            nop
+        ",
+        );
+        assert_eq!(sp, 3);
+        assert_eq!(r1, 17);
+    }
+
+    #[test]
+    fn is_one_instruction() {
+        assert_eq!(Assembler::assemble("nop").unwrap().len(), 3);
+    }
+}
+
+mod stack {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn pops_in_reverse_order() {
+        let [sp, r1, r2, ..] = run_virtual_machine(
+            r"
+            letall  sp, 0xF00000
+            let     r1, 17
+            let     r2, 34
+            push    r1
+            push    r2
+            pop     r1
+            pop     r2
+        ",
+        );
+        assert_eq!(sp, 0xF00000);
+        assert_eq!(r1, 34);
+        assert_eq!(r2, 17);
+    }
+
+    #[test]
+    fn push_grows_downward() {
+        let [sp, _, r2, ..] = run_virtual_machine(
+            r"
+            letall  sp, 0xF00000
+            letall  r1, 0xABCDEF
+            push    r1
+            load3   r2, sp, 0
+        ",
+        );
+        assert_eq!(sp, 0xF00000 - 3);
+        assert_eq!(r2, 0xABCDEF);
+    }
+
+    #[test]
+    fn conditionally_pushes_and_pops() {
+        let [sp, r1, r2, ..] = run_virtual_machine(
+            r"
+            letall  sp, 0xF00000
+            let     r1, 17
+            or      r3, r3, r3
+            cpush   r1
+            or      r3, r3, r3
+            cpop    r2
+        ",
+        );
+        assert_eq!(sp, 0xF00000);
+        assert_eq!(r1, 17);
+        assert_eq!(r2, 17);
+    }
+
+    #[test]
+    fn conditionally_does_not_push() {
+        let [sp, ..] = run_virtual_machine(
+            r"
+            letall  sp, 0xF00000
+            let     r1, 17
+            cpush   r1
+        ",
+        );
+        assert_eq!(sp, 0xF00000);
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn missing_operand_is_reported() {
+        let errors = Assembler::assemble("mov r1").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::Syntax("`mov` expects 2 operands, found 1".to_string())
+        );
+    }
+
+    #[test]
+    fn out_of_range_letall_is_reported_once() {
+        let errors = Assembler::assemble("letall r1, 0x1000000").unwrap_err();
+        assert_eq!(errors.0.len(), 1);
+    }
+}
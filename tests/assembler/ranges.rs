@@ -0,0 +1,284 @@
+mod immediate {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn largest_value_assembles() {
+        assert!(Assembler::assemble("addi r1, r1, 63").is_ok());
+    }
+
+    #[test]
+    fn too_large_value_errors() {
+        let errors = Assembler::assemble("addi r1, r1, 64").unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::OutOfRange {
+                value: 64,
+                min: 0,
+                max: 63
+            }
+        );
+        assert_eq!(error.span.start, Location { line: 1, column: 14 });
+        assert_eq!(error.span.end, Location { line: 1, column: 16 });
+    }
+
+    #[test]
+    fn span_ends_before_comment() {
+        let errors = Assembler::assemble("addi r1, r2, 64 ; c\nnop").unwrap_err();
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 14 });
+        assert_eq!(errors.0[0].span.end, Location { line: 1, column: 16 });
+    }
+
+    #[test]
+    fn span_ends_after_closing_parenthesis() {
+        let errors = Assembler::assemble("addi r1, r2, (1 + (63)) ; (c)\nnop").unwrap_err();
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 14 });
+        assert_eq!(errors.0[0].span.end, Location { line: 1, column: 24 });
+    }
+
+    #[test]
+    fn too_small_value_errors() {
+        let errors = Assembler::assemble("addi r1, r1, -1").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: -1,
+                min: 0,
+                max: 63
+            }
+        );
+    }
+}
+
+mod offset {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn signed_offsets_assemble() {
+        assert!(Assembler::assemble("load r1, r2, -32\nstore3 r1, r2, 31").is_ok());
+    }
+
+    #[test]
+    fn too_large_load_offset_errors() {
+        let errors = Assembler::assemble("load2 r1, r2, 32").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 32,
+                min: -32,
+                max: 31
+            }
+        );
+    }
+
+    #[test]
+    fn too_small_store_offset_errors() {
+        let errors = Assembler::assemble("store r1, r2, -33").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: -33,
+                min: -32,
+                max: 31
+            }
+        );
+    }
+
+    #[test]
+    fn too_large_field_offset_errors() {
+        let errors = Assembler::assemble(
+            r"
+            record:
+                data3   0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+                .last:  data 0
+            main:
+                load    r1, r0, record~.last
+        ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 33,
+                min: -32,
+                max: 31
+            }
+        );
+    }
+}
+
+mod let_value {
+    use assembler::{Assembler, ErrorKind, Location, WarningKind};
+
+    #[test]
+    fn small_value_does_not_warn() {
+        let assembly = Assembler::default().build("let r1, 4095").unwrap();
+        assert!(assembly.warnings.is_empty());
+    }
+
+    #[test]
+    fn too_large_value_warns() {
+        let assembly = Assembler::default()
+            .build(
+                r"
+                let     r1, 4097
+            ",
+            )
+            .unwrap();
+        let [warning] = &assembly.warnings[..] else {
+            panic!("{:?}", assembly.warnings)
+        };
+        assert_eq!(
+            warning.kind,
+            WarningKind::Truncated {
+                value: 4097,
+                bits: 12
+            }
+        );
+        assert_eq!(warning.span.start, Location { line: 2, column: 29 });
+        assert_eq!(warning.span.end, Location { line: 2, column: 33 });
+    }
+
+    #[test]
+    fn negative_value_warns() {
+        let assembly = Assembler::default().build("let r1, -24").unwrap();
+        assert_eq!(assembly.warnings.len(), 1);
+    }
+
+    #[test]
+    fn value_completed_by_lethi_does_not_warn() {
+        let assembly = Assembler::default()
+            .build(
+                r"
+                let     r1, 0xABCDEF
+                let     r2, 17
+                lethi   r1, 0xABCDEF
+            ",
+            )
+            .unwrap();
+        assert!(assembly.warnings.is_empty());
+    }
+
+    #[test]
+    fn value_completed_for_another_register_warns() {
+        let assembly = Assembler::default()
+            .build(
+                r"
+                let     r1, 0xABCDEF
+                lethi   r2, 0xABCDEF
+            ",
+            )
+            .unwrap();
+        assert_eq!(assembly.warnings.len(), 1);
+    }
+
+    #[test]
+    fn far_label_warns() {
+        let source = "let r1, far\n".to_string() + &"data3 0\n".repeat(1366) + "far:";
+        let assembly = Assembler::default().build(&source).unwrap();
+        assert_eq!(
+            assembly.warnings[0].kind,
+            WarningKind::Truncated {
+                value: 4101,
+                bits: 12
+            }
+        );
+    }
+
+    #[test]
+    fn far_label_completed_by_lethi_does_not_warn() {
+        let source = "let r1, far\nlethi r1, far\n".to_string() + &"data3 0\n".repeat(1366) + "far:";
+        let assembly = Assembler::default().build(&source).unwrap();
+        assert!(assembly.warnings.is_empty());
+    }
+
+    #[test]
+    fn more_than_24_bits_errors() {
+        let errors = Assembler::assemble("let r1, 0x1000000").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 0x1000000,
+                min: -0x800000,
+                max: 0xFFFFFF
+            }
+        );
+    }
+}
+
+mod lethi_value {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn with_too_large_number_errors() {
+        let errors = Assembler::assemble("lethi r1, 0x1000000").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 0x1000000,
+                min: -0x800000,
+                max: 0xFFFFFF
+            }
+        );
+    }
+
+    #[test]
+    fn with_too_small_number_errors() {
+        let errors = Assembler::assemble("lethi r1, -0x800001").unwrap_err();
+        assert_eq!(errors.0.len(), 1);
+    }
+}
+
+mod relative {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn farthest_label_assembles() {
+        let source = "addi r1, pc, ~far\n".to_string() + &"data3 0\n".repeat(21) + "far:";
+        assert!(Assembler::assemble(&source).is_ok());
+    }
+
+    #[test]
+    fn too_far_label_errors() {
+        let source = "addi r1, pc, ~far\n".to_string() + &"data3 0\n".repeat(22) + "far:";
+        let errors = Assembler::assemble(&source).unwrap_err();
+        let [error] = &errors.0[..] else {
+            panic!("{}", errors)
+        };
+        assert_eq!(
+            error.kind,
+            ErrorKind::OutOfRange {
+                value: 66,
+                min: 0,
+                max: 63
+            }
+        );
+        assert_eq!(error.span.start, Location { line: 1, column: 15 });
+        assert_eq!(error.span.end, Location { line: 1, column: 18 });
+    }
+}
+
+mod data {
+    use assembler::{Assembler, ErrorKind};
+
+    #[test]
+    fn too_large_byte_errors() {
+        let errors = Assembler::assemble("data 256").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 256,
+                min: -128,
+                max: 255
+            }
+        );
+    }
+
+    #[test]
+    fn negative_words_assemble() {
+        let bytes = Assembler::assemble("data2 -1\ndata3 -2").unwrap();
+        assert_eq!(bytes, [0xFF, 0xFF, 0xFF, 0xFF, 0xFE]);
+    }
+}
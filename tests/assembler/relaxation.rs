@@ -0,0 +1,96 @@
+mod size {
+    use assembler::Assembler;
+
+    #[test]
+    fn near_branch_stays_one_instruction() {
+        let source = "addi pc, pc, ~far\n".to_string() + &"data3 0\n".repeat(21) + "far:";
+        assert_eq!(Assembler::assemble(&source).unwrap().len(), 3 + 63);
+    }
+
+    #[test]
+    fn far_branch_is_relaxed() {
+        let source = "addi pc, pc, ~far\n".to_string() + &"data3 0\n".repeat(22) + "far:";
+        assert_eq!(Assembler::assemble(&source).unwrap().len(), 9 + 66);
+    }
+
+    #[test]
+    fn relaxing_pushes_other_branches_out_of_range() {
+        // The backward branch relaxes first, which moves `end` out of reach
+        // of the forward branch.
+        let source = "start:\n".to_string()
+            + &"data3 0\n".repeat(22)
+            + "addi pc, pc, ~end\n"
+            + "subi pc, pc, ~start\n"
+            + &"data3 0\n".repeat(19)
+            + "end:";
+        assert_eq!(Assembler::assemble(&source).unwrap().len(), 66 + 9 + 9 + 57);
+    }
+
+    #[test]
+    fn other_relative_references_are_not_relaxed() {
+        let source = "addi r1, pc, ~far\n".to_string() + &"data3 0\n".repeat(22) + "far:";
+        assert!(Assembler::assemble(&source).is_err());
+    }
+}
+
+mod execution {
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn branches_forward() {
+        let [_, r1, ..] = run_virtual_machine(
+            &(r"
+            let     r1, 17
+            addi    pc, pc, ~far
+            "
+            .to_string()
+                + &"data3 0\n".repeat(100)
+                + r"
+            let     r1, 34
+            far:
+        "),
+        );
+        assert_eq!(r1, 17);
+    }
+
+    #[test]
+    fn loops_around_far_branches() {
+        let [_, r1, r2, ..] = run_virtual_machine(
+            &(r"
+            let     r1, 3
+            loop:
+                addi    r2, r2, 1
+                subi    r1, r1, 1
+                xori    r3, r1, 0
+                caddi   pc, pc, ~done
+                addi    pc, pc, ~.skip
+            "
+            .to_string()
+                + &"data3 0\n".repeat(30)
+                + r"
+            .skip:
+                subi    pc, pc, ~loop
+            done:
+        "),
+        );
+        assert_eq!(r1, 0);
+        assert_eq!(r2, 3);
+    }
+
+    #[test]
+    fn conditionally_does_not_branch() {
+        let [_, r1, ..] = run_virtual_machine(
+            &(r"
+            let     r1, 17
+            caddi   pc, pc, ~far
+            let     r1, 34
+            "
+            .to_string()
+                + &"data3 0\n".repeat(100)
+                + r"
+            far:
+        "),
+        );
+        assert_eq!(r1, 34);
+    }
+}
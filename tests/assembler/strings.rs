@@ -0,0 +1,188 @@
+mod escapes {
+    use assembler::Assembler;
+
+    #[test]
+    fn escapes_are_decoded() {
+        let bytes = Assembler::assemble(r#"data "a\n\t\\\"\'\0""#).unwrap();
+        assert_eq!(bytes, b"a\n\t\\\"'\0");
+    }
+
+    #[test]
+    fn hex_escape_is_a_byte() {
+        let bytes = Assembler::assemble(r#"data "\x00\xFF\x7f""#).unwrap();
+        assert_eq!(bytes, [0x00, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn unicode_escape_is_utf8() {
+        let bytes = Assembler::assemble(r#"data "\u{41}\u{E9}\u{1F431}""#).unwrap();
+        assert_eq!(bytes, "Aé🐱".as_bytes());
+    }
+
+    #[test]
+    fn semicolon_in_string_is_not_a_comment() {
+        let bytes = Assembler::assemble(r#"data "\";" ; comment"#).unwrap();
+        assert_eq!(bytes, b"\";");
+    }
+}
+
+mod directives {
+    use assembler::{Assembler, ErrorKind};
+
+    use crate::common::run_virtual_machine;
+
+    #[test]
+    fn ascii_places_strings() {
+        let bytes = Assembler::assemble(r#".ascii "ab", "c\n""#).unwrap();
+        assert_eq!(bytes, b"abc\n");
+    }
+
+    #[test]
+    fn asciz_ends_with_zero() {
+        let bytes = Assembler::assemble(r#".asciz "hi", """#).unwrap();
+        assert_eq!(bytes, b"hi\0\0");
+    }
+
+    #[test]
+    fn pstring_starts_with_length() {
+        let bytes = Assembler::assemble(r#".pstring "hey", "\x01""#).unwrap();
+        assert_eq!(bytes, [3, b'h', b'e', b'y', 1, 1]);
+    }
+
+    #[test]
+    fn labels_after_strings_account_for_terminators() {
+        let [_, r1, ..] = run_virtual_machine(
+            r#"
+                let     r1, end - start
+                jump    end
+            start:
+                .asciz  "abc"
+                .pstring "de"
+            end:
+            "#,
+        );
+        assert_eq!(r1, 7);
+    }
+
+    #[test]
+    fn long_pstring_errors() {
+        let source = format!(".pstring \"{}\"", "x".repeat(256));
+        let errors = Assembler::assemble(&source).unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 256,
+                min: 0,
+                max: 255
+            }
+        );
+    }
+}
+
+mod charmap {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn maps_characters_after_it() {
+        let source = r#"
+            data    "AB"
+            .charmap 'A', 1
+            data    "AB"
+            .ascii  "BA"
+            "#;
+        let bytes = Assembler::assemble(source).unwrap();
+        assert_eq!(bytes, [b'A', b'B', 1, b'B', b'B', 1]);
+    }
+
+    #[test]
+    fn string_maps_consecutive_bytes() {
+        let source = r#"
+            .equ     FONT_DIGITS, 0x10
+            .charmap "0123456789", FONT_DIGITS
+            .charmap ' ', 0
+            .asciz   "2024 12"
+            "#;
+        let bytes = Assembler::assemble(source).unwrap();
+        assert_eq!(bytes, [0x12, 0x10, 0x12, 0x14, 0, 0x11, 0x12, 0]);
+    }
+
+    #[test]
+    fn later_mapping_wins() {
+        let source = ".charmap 'a', 1\n.charmap 'a', 2\ndata \"a\"";
+        assert_eq!(Assembler::assemble(source).unwrap(), [2]);
+    }
+
+    #[test]
+    fn escaped_bytes_are_not_mapped() {
+        let source = ".charmap 'A', 1\ndata \"\\x41A\"";
+        assert_eq!(Assembler::assemble(source).unwrap(), [0x41, 1]);
+    }
+
+    #[test]
+    fn character_literals_are_not_mapped() {
+        let source = ".charmap 'A', 1\ndata 'A'";
+        assert_eq!(Assembler::assemble(source).unwrap(), [b'A']);
+    }
+
+    #[test]
+    fn byte_out_of_range_errors() {
+        let errors = Assembler::assemble(".charmap \"xyz\", 254").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::OutOfRange {
+                value: 256,
+                min: 0,
+                max: 255
+            }
+        );
+        assert_eq!(
+            errors.0[0].span.start,
+            Location {
+                line: 1,
+                column: 17
+            }
+        );
+    }
+
+    #[test]
+    fn code_point_must_be_a_character() {
+        let errors = Assembler::assemble(".charmap 0xD800, 1").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::InvalidCharacter("0xD800".to_string())
+        );
+    }
+}
+
+mod errors {
+    use assembler::{Assembler, ErrorKind, Location};
+
+    #[test]
+    fn surrogate_escape_errors() {
+        let errors = Assembler::assemble("data \"ab\\u{D800}\"").unwrap_err();
+        assert_eq!(
+            errors.0[0].kind,
+            ErrorKind::InvalidCharacter("\\u{D800}".to_string())
+        );
+        assert_eq!(errors.0[0].span.start, Location { line: 1, column: 9 });
+        assert_eq!(
+            errors.0[0].span.end,
+            Location {
+                line: 1,
+                column: 17
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_escape_errors() {
+        let errors = Assembler::assemble(r#"data "\q""#).unwrap_err();
+        assert!(matches!(errors.0[0].kind, ErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn short_hex_escape_errors() {
+        let errors = Assembler::assemble(r#"data "\x4""#).unwrap_err();
+        assert!(matches!(errors.0[0].kind, ErrorKind::Syntax(_)));
+    }
+}
@@ -0,0 +1,120 @@
+mod table {
+    use assembler::{Assembler, Symbol, SymbolKind};
+
+    #[test]
+    fn list_global_local_and_relative_labels() {
+        let assembly = Assembler::default()
+            .build("nop\nmain:\n    nop\n.loop:\n    nop")
+            .unwrap();
+        assert_eq!(
+            assembly.symbols,
+            [
+                Symbol {
+                    name: "main".to_string(),
+                    value: 3,
+                    kind: SymbolKind::Global
+                },
+                Symbol {
+                    name: "main~.loop".to_string(),
+                    value: 3,
+                    kind: SymbolKind::Relative
+                },
+                Symbol {
+                    name: "main.loop".to_string(),
+                    value: 6,
+                    kind: SymbolKind::Local
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn write_sym_file() {
+        let assembly = Assembler::default()
+            .build("main:\n    nop\n.loop:\n    nop\nvblank:")
+            .unwrap();
+        assert_eq!(
+            assembly.sym(),
+            "000000 main\n000003 main.loop\n000003 main~.loop\n000006 vblank\n"
+        );
+    }
+
+    #[test]
+    fn symbolize_addresses() {
+        let assembly = Assembler::default()
+            .build("vblank:\n    nop\n.after1:\n    nop\n    nop")
+            .unwrap();
+        assert_eq!(assembly.symbolize(0).as_deref(), Some("vblank"));
+        assert_eq!(assembly.symbolize(2).as_deref(), Some("vblank+2"));
+        assert_eq!(assembly.symbolize(3).as_deref(), Some("vblank.after1"));
+        assert_eq!(assembly.symbolize(6).as_deref(), Some("vblank.after1+3"));
+    }
+
+    #[test]
+    fn symbolize_before_first_label() {
+        let assembly = Assembler::default().build("nop\nmain:").unwrap();
+        assert_eq!(assembly.symbolize(0), None);
+    }
+}
+
+mod source_map {
+    use assembler::{Assembler, MemoryResolver, SourceLine};
+
+    #[test]
+    fn map_addresses_to_lines() {
+        let assembly = Assembler::default()
+            .build("main:\n    nop\n\n    data 1, 2\n    jump main")
+            .unwrap();
+        let lines: Vec<_> = assembly
+            .lines
+            .iter()
+            .map(|line| (line.address, line.length, line.line))
+            .collect();
+        assert_eq!(lines, [(0, 3, 2), (3, 2, 4), (5, 9, 5)]);
+    }
+
+    #[test]
+    fn find_line_of_address() {
+        let assembly = Assembler::default().build("nop\ndata 1, 2\nnop").unwrap();
+        assert_eq!(assembly.source_line(4).map(|line| line.line), Some(2));
+        assert_eq!(assembly.source_line(5).map(|line| line.line), Some(3));
+        assert_eq!(assembly.source_line(8), None);
+    }
+
+    #[test]
+    fn map_macro_body_lines() {
+        let source = ".macro pair\n    data 1\n    data 2\n.endm\npair";
+        let assembly = Assembler::default().build(source).unwrap();
+        let lines: Vec<_> = assembly.lines.iter().map(|line| line.line).collect();
+        assert_eq!(lines, [2, 3]);
+    }
+
+    #[test]
+    fn map_included_files() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("lib.kittyasm", "nop");
+        let assembly = Assembler::default()
+            .with_resolver(resolver)
+            .build("nop\n.include \"lib.kittyasm\"")
+            .unwrap();
+        assert_eq!(
+            assembly.lines[1],
+            SourceLine {
+                address: 3,
+                length: 3,
+                file: Some("lib.kittyasm".to_string()),
+                line: 1
+            }
+        );
+        assert_eq!(assembly.map(), "000000 3 1\n000003 3 lib.kittyasm:1\n");
+    }
+
+    #[test]
+    fn map_in_order_of_address() {
+        let assembly = Assembler::default()
+            .build(".org 6\nnop\n.org 0\nnop")
+            .unwrap();
+        let lines: Vec<_> = assembly.lines.iter().map(|line| line.line).collect();
+        assert_eq!(lines, [4, 2]);
+    }
+}
@@ -31,4 +31,9 @@ pub fn run_virtual_machine_without_harness(source: &str) -> [u32; REGISTER_COUNT
         }
         Err(error) => panic!("{}", error),
     }
-}
\ No newline at end of file
+}
+pub fn run_rom(rom: Vec<u8>) -> [u32; REGISTER_COUNT] {
+    let mut vm = VirtualMachine::new(rom);
+    vm.run();
+    vm.registers()
+}
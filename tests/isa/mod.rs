@@ -0,0 +1,109 @@
+mod instruction {
+    use assembler::Assembler;
+    use common::{Format, Instruction, Op};
+
+    #[test]
+    fn opcodes_are_in_order() {
+        for (opcode, op) in Op::ALL.into_iter().enumerate() {
+            assert_eq!(op as usize, opcode, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn decode_then_encode_gives_same_word() {
+        for word in (0..1 << 24).step_by(4099) {
+            assert_eq!(Instruction::decode(word).encode(), word, "{:#08X}", word);
+        }
+    }
+
+    #[test]
+    fn let_takes_twelve_bits() {
+        let instruction = Instruction::decode(0o0001_7654);
+        assert_eq!(instruction.op, Op::Let);
+        assert_eq!(instruction.op.format(), Format::L);
+        assert_eq!((instruction.r, instruction.s), (1, 0));
+        assert_eq!(instruction.u, 0o7654);
+    }
+
+    #[test]
+    fn offsets_are_sign_extended() {
+        let load = Instruction::decode((Op::Load as u32) << 18 | 0o77);
+        assert_eq!(load.immediate(), -1);
+        let addi = Instruction {
+            op: Op::Addi,
+            ..load
+        };
+        assert_eq!(addi.immediate(), 63);
+    }
+
+    #[test]
+    fn mnemonics_parse_with_conditional_variants() {
+        assert_eq!(Op::parse("addi"), Some((Op::Addi, false)));
+        assert_eq!(Op::parse("CADDI"), Some((Op::Addi, true)));
+        assert_eq!(Op::parse("cor"), Some((Op::Or, true)));
+        assert_eq!(Op::parse("frobnicate"), None);
+        assert_eq!(Op::parse("c"), None);
+    }
+
+    #[test]
+    fn every_op_assembles_to_its_encoding() {
+        for op in Op::ALL {
+            for conditional in [false, true] {
+                let instruction = Instruction {
+                    conditional,
+                    op,
+                    r: 1,
+                    s: 2,
+                    u: 3,
+                };
+                let instruction = Instruction::decode(instruction.encode());
+                let operands = match op.format() {
+                    Format::L if op == Op::Lethi => "r1, 3 << 12".to_string(),
+                    Format::L => "r1, 3".to_string(),
+                    Format::I => "r1, r2, 3".to_string(),
+                    Format::R => "r1, r2, r3".to_string(),
+                };
+                let source = format!("{} {}", instruction.mnemonic(), operands);
+                let bytes = Assembler::assemble(&source).unwrap();
+                let [_, a, b, c] = instruction.encode().to_be_bytes();
+                assert_eq!(bytes, [a, b, c], "{}", source);
+            }
+        }
+    }
+}
+
+mod io_registers {
+    use assembler::Assembler;
+    use common::io::{self, REGISTERS};
+
+    #[test]
+    fn registers_do_not_overlap() {
+        for pair in REGISTERS.windows(2) {
+            assert!(
+                pair[0].address + pair[0].width <= pair[1].address,
+                "{} overlaps {}",
+                pair[0].name,
+                pair[1].name
+            );
+        }
+    }
+
+    #[test]
+    fn every_register_is_predefined_in_the_assembler() {
+        for register in REGISTERS {
+            let source = format!("data3 IO.{}", register.name);
+            let bytes = Assembler::assemble(&source).unwrap();
+            let [_, a, b, c] = register.address.to_be_bytes();
+            assert_eq!(bytes, [a, b, c], "{}", source);
+        }
+    }
+
+    #[test]
+    fn constants_match_the_table() {
+        let framebuffer = REGISTERS
+            .iter()
+            .find(|register| register.name == "FRAMEBUFFER")
+            .unwrap();
+        assert_eq!(framebuffer.address, io::FRAMEBUFFER);
+    }
+}
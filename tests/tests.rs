@@ -1,3 +1,4 @@
 mod assembler;
 pub mod common;
+mod isa;
 mod vm;
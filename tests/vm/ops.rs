@@ -99,17 +99,6 @@ mod lethi_op {
         assert_eq!(r1, 0xFFF000);
     }
 
-    // TODO: Move this to assembler tests.
-    // #[test]
-    #[should_panic]
-    fn _with_too_large_number_errors() {
-        run_virtual_machine(
-            r"
-            lethi   r1, 0x1000000
-        ",
-        );
-    }
-
     #[test]
     fn conditionally_with_large_enough_number_shifts() {
         let [_, r1, ..] = run_virtual_machine(
@@ -303,12 +292,12 @@ mod slessi_op {
     }
 
     #[test]
-    fn with_less_than_minus_12_is_true() {
+    fn negative_with_12_is_true() {
         let [_, r1, ..] = run_virtual_machine(
             r"
             let     r1, -24
             lethi   r1, -24
-            slessi  r1, r1, -12
+            slessi  r1, r1, 12
         ",
         );
         assert_eq!(r1, 1);